@@ -1,224 +1,2990 @@
 use crate::{
+    bookmark::save_bookmark,
+    bot::RoundText,
+    bundle::Bundle,
+    command::{COMMAND_HELP, Command, parse_command},
+    config::{CompletionSignal, Config, TextAlign, Viewport, set_config_value},
+    drill::{DRILL_TOKEN_COUNT, generate_charset_drill, generate_drill},
+    events::{Event, EventSink},
+    fingers::{FingerMap, HandBalance, hand_speeds},
+    generator::generate_text,
+    ghost::{Ghost, load_ghost, save_ghost},
     helpers::{
-        build_target_lines_from_layout, build_typed_visible_from_layout,
-        cursor_row_col_from_layout, generate_text, layout_text,
+        BELL, TargetStyle, build_rolling_lines, build_stack_lines, build_target_lines_from_layout,
+        build_typed_visible_from_layout, clipboard_sequence, cursor_row_col_from_layout,
+        layout_text, line_offset, load_dictionary, load_language, load_system_dictionary,
+        pick_quote, stack_layout, typing_stats, word_range,
+    },
+    highlight::highlight_code,
+    history::{ErrorHistory, load_error_history, save_error_history},
+    history_screen::HistoryScreen,
+    keymap::{KeyResult, Keymap, Keymaps, format_key},
+    lesson::record_lesson_result,
+    live::{LiveStats, StatsFile, StatsServer},
+    popup::{Answer, Dialog, centered_rect, render_popup},
+    race::{Progress, Race},
+    recording::{Recording, RecordingHeader, save_recording},
+    replay::{Playback, Replay, mistake_windows, typed_at},
+    results::{ResultRecord, append_results, load_results, now_timestamp},
+    settings::{Settings, SettingsScreen},
+    tour::TOUR_PAGES,
+    training::{TRAINING_WORD_COUNT, save_training_progress},
+    types::{
+        Action, Args, DrillKind, Goal, KeystrokeEvent, PlaybackAction, Repeat, Requirement,
+        ReviewAction, Selection, TestResult, TextBookmark, TextOptions, TextSource, TourAction,
     },
-    types::TextSource,
 };
 
+#[cfg(feature = "tts")]
+use crate::speech::speak;
+
+use rand::{SeedableRng, rngs::StdRng};
 use ratatui::{
-    crossterm::event::{self, KeyCode},
+    crossterm::event::{
+        self, KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+    },
     prelude::*,
     widgets::*,
 };
-use std::time::Instant;
+use std::{
+    cell::Cell,
+    collections::VecDeque,
+    io, iter, process,
+    time::{Duration, Instant},
+};
 use tui_input::{Input, InputRequest};
 
+const PACE_WINDOW_SECONDS: f64 = 5.0;
+const PLAYLIST_ADVANCE_SECONDS: u64 = 3;
+const FLASH_MILLIS: u64 = 150;
+const ROLL_MILLIS: u64 = 80;
+const SERIES_WIDTH: u16 = 24;
+const STREAM_AHEAD_BYTES: usize = 600;
+// Words generated at a time for a streamed text, which is topped up as it is
+// typed rather than made up front.
+const STREAM_BATCH_WORDS: usize = 20;
+const FOCUS_RESUME_SECONDS: u64 = 3;
+// The smallest screen the layout fits in: a margin around the title, the
+// target, typed and stats panels.
+const MIN_WIDTH: u16 = 40;
+const MIN_HEIGHT: u16 = 16;
+// -min-wpm and -min-acc leave the first seconds alone, where a single key
+// swings the numbers.
+const MINIMUM_GRACE_SECONDS: f64 = 5.0;
+
 pub struct App {
     source: TextSource,
     target: String,
+    author: Option<String>,
     input: Input,
     started_at: Option<Instant>,
     finished_at: Option<Instant>,
     count: usize,
     seconds: usize,
+    text_options: TextOptions,
+    soft_cursor: bool,
+    keymap: Keymap<Action>,
+    review_keymap: Keymap<ReviewAction>,
+    playback_keymap: Keymap<PlaybackAction>,
+    selection: Selection,
+    notice: Option<String>,
+    tour: Option<usize>,
+    tour_keymap: Keymap<TourAction>,
+    errors: ErrorHistory,
+    key_times: Vec<Instant>,
+    pace_floor: Option<f64>,
+    pace_ceiling: Option<f64>,
+    pace_alert_seconds: f64,
+    off_pace_since: Option<Instant>,
+    pace_alerted: bool,
+    playlist: VecDeque<Args>,
+    playlist_results: Vec<TestResult>,
+    sentence_results: Vec<TestResult>,
+    advance_at: Option<Instant>,
+    goal: Option<Goal>,
+    goal_missed: bool,
+    repeat: Option<Repeat>,
+    require: Option<Requirement>,
+    last_result: Option<TestResult>,
+    minimum: Option<Requirement>,
+    // Why the test failed -min-wpm or -min-acc, once it has.
+    failed: Option<String>,
+    attempts: usize,
+    streak: usize,
+    last_attempt: Option<TestResult>,
+    best_attempt: Option<TestResult>,
+    clean: bool,
+    keystrokes: Vec<KeystrokeEvent>,
+    replay: Option<Replay>,
+    // Set by `ttt replay`, which only plays a recording back.
+    playback: Option<Playback>,
+    completion_signal: CompletionSignal,
+    flash_until: Option<Instant>,
+    // Escape sequences for the terminal, written with the next frame.
+    terminal_output: String,
+    blind: bool,
+    tab: String,
+    tab_width: usize,
+    events: Option<EventSink>,
+    stats_server: Option<StatsServer>,
+    stats_file: Option<StatsFile>,
+    json: bool,
+    // Everything saved to results.jsonl this session, for -json.
+    session_results: Vec<ResultRecord>,
+    race: Option<Race>,
+    bundle: Option<String>,
+    practice: bool,
+    bookmark: Option<TextBookmark>,
+    rng: StdRng,
+    // What `rng` was last seeded with, when not at random.
+    seed: Option<u64>,
+    zen: bool,
+    stream: bool,
+    fingers: FingerMap,
+    // The word list of tests started without options, from the config.
+    language: Option<String>,
+    dict: Option<String>,
+    show_hands: bool,
+    viewport: Viewport,
+    text_align: TextAlign,
+    first_line_indent: usize,
+    max_width: u16,
+    // Type straight into the target text, without the Typed Words panel.
+    overlay: bool,
+    typed_panel: bool,
+    // Leave out the title and the stats until the test is over.
+    focus: bool,
+    context_lines: usize,
+    center_line: bool,
+    progress: bool,
+    space_glyph: char,
+    // Whether ghosts are on, and the one racing the current text if any.
+    ghosts: bool,
+    ghost: Option<Ghost>,
+    record_replays: bool,
+    save_interrupted: bool,
+    confirm_quit: bool,
+    // Open dialogs, the last one on top and answering keys.
+    dialogs: Vec<Dialog>,
+    // The `:` prompt while it is open, and the answer to the last command.
+    command: Option<Input>,
+    command_reply: Option<String>,
+    // File name of the recording of the last test finished.
+    recording: Option<String>,
+    countdown: u64,
+    countdown_until: Option<Instant>,
+    paused_at: Option<Instant>,
+    afk_seconds: u64,
+    last_key_at: Option<Instant>,
+    // Set while paused because the terminal lost focus; `resume_at` counts
+    // down once it's back.
+    focus_lost: bool,
+    // Whether the screen was last too small for the layout, and whether that
+    // has been acted on: a test is paused until it fits again.
+    too_small: Cell<bool>,
+    shrunk: bool,
+    resume_at: Option<Instant>,
+    // Time spent paused during the current test.
+    paused: Duration,
+    // Caret line of the rolling view, the line it came from and when it moved.
+    roll: Cell<(u16, u16, Instant)>,
+    // Where the stats panel and the top dialog were last drawn, for the mouse.
+    stats_area: Cell<Rect>,
+    dialog_area: Cell<Rect>,
+    #[cfg(feature = "tts")]
+    tts_command: Option<String>,
+    quit: bool,
 }
 
 impl App {
-    pub fn new(source: TextSource, count: usize, seconds: usize) -> Self {
-        let target = match &source {
-            TextSource::RandomWords(dict) => generate_text(dict, count),
-            TextSource::Fixed(text) => text.clone(),
+    pub fn new(args: Args, keymaps: Keymaps, config: &Config) -> Self {
+        let Args {
+            count,
+            seconds,
+            mut source,
+            text: text_options,
+            playlist,
+            goal,
+            repeat,
+            require,
+            minimum,
+            blind,
+            events: _,
+            serve_stats: _,
+            stats_file: _,
+            json,
+            bundle,
+            practice,
+            bookmark,
+            seed,
+            zen,
+            stream,
+        } = args;
+
+        let errors = load_error_history();
+        // Tabs in the text and the Tab key both turn into `tab`, so the two
+        // always match whichever way the config asks for indentation.
+        let tab = if config.hard_tabs {
+            "\t".to_string()
+        } else {
+            " ".repeat(config.tab_width.get())
+        };
+
+        // A fixed seed makes every generated text reproducible, e.g. for two
+        // people racing on the same words.
+        let mut rng = match seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_os_rng(),
         };
 
-        Self {
+        let batch = if stream { STREAM_BATCH_WORDS } else { count };
+        let (target, author) = next_target(&mut source, batch, &text_options, &errors, &mut rng);
+        let target = target.replace('\t', &tab);
+
+        let mut tour_keymap = Keymap::new();
+        tour_keymap.bind(vec![KeyEvent::from(KeyCode::Right)], TourAction::Next);
+        tour_keymap.bind(vec![KeyEvent::from(KeyCode::Enter)], TourAction::Next);
+        tour_keymap.bind(vec![char_key('l')], TourAction::Next);
+        tour_keymap.bind(vec![KeyEvent::from(KeyCode::Left)], TourAction::Previous);
+        tour_keymap.bind(vec![char_key('h')], TourAction::Previous);
+        tour_keymap.bind(vec![KeyEvent::from(KeyCode::Esc)], TourAction::Close);
+        tour_keymap.bind(vec![char_key('q')], TourAction::Close);
+        tour_keymap.bind(vec![char_key('d')], TourAction::Dismiss);
+
+        let mut app = Self {
             source,
             target,
+            author,
             input: Input::default(),
             started_at: None,
             finished_at: None,
             count,
             seconds,
+            text_options,
+            soft_cursor: false,
+            keymap: keymaps.test,
+            review_keymap: keymaps.review,
+            playback_keymap: keymaps.playback,
+            selection: Selection::default(),
+            notice: None,
+            tour: config.show_tour.then_some(0),
+            tour_keymap,
+            errors,
+            key_times: Vec::new(),
+            pace_floor: config.pace_floor,
+            pace_ceiling: config.pace_ceiling,
+            pace_alert_seconds: config.pace_alert_seconds,
+            off_pace_since: None,
+            pace_alerted: false,
+            playlist_results: Vec::new(),
+            sentence_results: Vec::new(),
+            advance_at: None,
+            playlist: playlist.into(),
+            goal,
+            goal_missed: false,
+            repeat,
+            require,
+            last_result: None,
+            minimum,
+            failed: None,
+            attempts: 0,
+            streak: 0,
+            last_attempt: None,
+            best_attempt: None,
+            clean: true,
+            keystrokes: Vec::new(),
+            replay: None,
+            playback: None,
+            completion_signal: config.completion_signal,
+            flash_until: None,
+            terminal_output: String::new(),
+            blind,
+            tab,
+            tab_width: config.tab_width.get(),
+            events: None,
+            stats_server: None,
+            stats_file: None,
+            json,
+            session_results: Vec::new(),
+            race: None,
+            bundle,
+            practice,
+            bookmark,
+            rng,
+            seed,
+            zen,
+            stream,
+            fingers: FingerMap::new(&config.fingers),
+            language: config.language.clone().filter(|l| !l.is_empty()),
+            dict: config.dict.clone(),
+            show_hands: false,
+            viewport: config.viewport,
+            text_align: config.text_align,
+            first_line_indent: config.first_line_indent,
+            max_width: config.max_width,
+            overlay: config.overlay,
+            typed_panel: config.typed_panel,
+            focus: config.focus,
+            context_lines: config.context_lines,
+            center_line: config.center_line,
+            progress: config.progress,
+            space_glyph: config.space_glyph,
+            ghosts: config.ghost,
+            ghost: None,
+            record_replays: config.record_replays,
+            save_interrupted: config.save_interrupted,
+            confirm_quit: config.confirm_quit,
+            dialogs: Vec::new(),
+            command: None,
+            command_reply: None,
+            recording: None,
+            countdown: config.countdown,
+            countdown_until: None,
+            paused_at: None,
+            afk_seconds: config.afk_seconds,
+            last_key_at: None,
+            focus_lost: false,
+            too_small: Cell::new(false),
+            shrunk: false,
+            resume_at: None,
+            paused: Duration::ZERO,
+            roll: Cell::new((0, 0, Instant::now())),
+            stats_area: Cell::new(Rect::default()),
+            dialog_area: Cell::new(Rect::default()),
+            #[cfg(feature = "tts")]
+            tts_command: config.speak_results.then(|| config.tts_command.clone()),
+            quit: false,
+        };
+
+        app.extend_text();
+        app.load_ghost();
+
+        app
+    }
+
+    pub fn should_quit(&self) -> bool {
+        self.quit
+    }
+
+    // 0 unless -require-wpm or -require-acc weren't met by the last test
+    // finished, or no test was finished at all.
+    pub fn exit_code(&self) -> i32 {
+        match self.require {
+            Some(require) if !self.last_result.is_some_and(|r| require.met(&r)) => 1,
+            _ => 0,
         }
     }
 
-    fn reset(&mut self) {
-        self.target = match &self.source {
-            TextSource::RandomWords(dict) => generate_text(dict, self.count),
-            TextSource::Fixed(text) => text.clone(),
+    pub fn soft_cursor(&self) -> bool {
+        self.soft_cursor
+    }
+
+    pub fn use_soft_cursor(&mut self) {
+        self.soft_cursor = true;
+    }
+
+    pub fn take_terminal_output(&mut self) -> String {
+        std::mem::take(&mut self.terminal_output)
+    }
+
+    pub fn set_event_sink(&mut self, events: EventSink) {
+        self.events = Some(events);
+    }
+
+    pub fn set_stats_server(&mut self, server: StatsServer) {
+        self.stats_server = Some(server);
+    }
+
+    pub fn set_stats_file(&mut self, file: StatsFile) {
+        self.stats_file = Some(file);
+    }
+
+    // Rounded, so overlays only hear about changes they would show.
+    fn publish_stats(&mut self) {
+        if self.stats_server.is_none() && self.stats_file.is_none() {
+            return;
+        }
+
+        let stats = self.stats();
+        let state = if self.finished_at.is_some() {
+            "finished"
+        } else if self.paused_at.is_some() {
+            "paused"
+        } else if self.started_at.is_some() {
+            "typing"
+        } else {
+            "waiting"
+        };
+        let live = LiveStats {
+            state,
+            wpm: (stats.wpm * 10.0).round() / 10.0,
+            accuracy: (stats.accuracy * 10.0).round() / 10.0,
+            seconds: (stats.seconds * 10.0).round() / 10.0,
+            typed: self.input.value().chars().count(),
+            total: self.target.chars().count(),
+        };
+
+        if let Some(file) = &mut self.stats_file {
+            file.publish(live.clone());
+        }
+        if let Some(server) = &mut self.stats_server {
+            server.publish(live);
+        }
+    }
+
+    pub fn set_playback(&mut self, recording: Recording, controls: bool) {
+        self.target = recording.header.target;
+        self.author = None;
+        self.keystrokes = recording.keystrokes;
+        self.started_at = Some(Instant::now());
+        let duration = recording.header.result.seconds;
+        self.playback = Some(if controls {
+            Playback::new(duration)
+        } else {
+            Playback::frozen(duration)
+        });
+        self.tour = None;
+        self.ghost = None;
+        self.record_replays = false;
+    }
+
+    pub fn seek_playback(&mut self, seconds: f64) {
+        if let Some(playback) = &mut self.playback {
+            playback.seek(seconds);
+        }
+    }
+
+    pub fn set_race(&mut self, race: Race) {
+        self.race = Some(race);
+        self.ghost = None;
+        self.set_race_text();
+        self.update_race();
+    }
+
+    // Bots need to know what the round is typed on, and for how long, to
+    // know when they are done.
+    fn set_race_text(&mut self) {
+        let text = RoundText {
+            length: self.target.chars().count(),
+            endless: self.stream,
+            seconds: (!self.zen).then_some(self.seconds as f64),
         };
+
+        if let Some(race) = &mut self.race {
+            race.set_text(text);
+        }
+    }
+
+    fn update_race(&mut self) {
+        let typed = self.input.value().chars().count();
+        let total = self.target.chars().count();
+        let stats = self.stats();
+        let result = self.finished_at.is_some().then_some(stats);
+
+        if let Some(race) = &mut self.race {
+            race.update(Progress {
+                typed,
+                total,
+                wpm: stats.wpm,
+                result,
+                ..Progress::default()
+            });
+        }
+    }
+
+    // The next round of a race, on new words everyone gets the same of.
+    fn rematch(&mut self) {
+        if let Some(race) = &mut self.race {
+            race.rematch();
+            self.rng = StdRng::seed_from_u64(race.seed());
+            self.seed = Some(race.seed());
+        }
+
+        self.reset();
+        self.set_race_text();
+        self.update_race();
+    }
+
+    fn emit(&mut self, event: Event) {
+        if let Some(events) = &mut self.events {
+            events.emit(&event);
+        }
+    }
+
+    fn reset(&mut self) {
+        let batch = self.batch();
+        (self.target, self.author) = next_target(
+            &mut self.source,
+            batch,
+            &self.text_options,
+            &self.errors,
+            &mut self.rng,
+        );
+        self.target = self.target.replace('\t', &self.tab);
+        self.extend_text();
+        self.retype();
+        self.selection = Selection::default();
+        self.notice = None;
+        self.advance_at = None;
+        self.sentence_results.clear();
+        self.goal_missed = false;
+        self.attempts = 0;
+        self.streak = 0;
+        self.last_attempt = None;
+        self.best_attempt = None;
+    }
+
+    // Starts typing the current target again from scratch.
+    fn retype(&mut self) {
         self.input = Input::default();
         self.started_at = None;
         self.finished_at = None;
+        self.key_times.clear();
+        self.off_pace_since = None;
+        self.pace_alerted = false;
+        self.clean = true;
+        self.keystrokes.clear();
+        self.replay = None;
+        self.countdown_until = None;
+        self.paused_at = None;
+        self.paused = Duration::ZERO;
+        self.last_key_at = None;
+        self.focus_lost = false;
+        self.resume_at = None;
+        self.dialogs
+            .retain(|dialog| !matches!(dialog, Dialog::Paused { .. }));
+        self.failed = None;
+        self.load_ghost();
+        self.update_race();
+    }
+
+    // Streamed texts grow as they are typed and races have real opponents, so
+    // neither gets a ghost.
+    fn load_ghost(&mut self) {
+        self.ghost = if self.ghosts && !self.stream && self.race.is_none() {
+            load_ghost(&self.target)
+        } else {
+            None
+        };
+    }
+
+    fn in_playlist(&self) -> bool {
+        !self.playlist.is_empty() || !self.playlist_results.is_empty()
+    }
+
+    fn advance_playlist(&mut self) {
+        let Some(args) = self.playlist.pop_front() else {
+            return;
+        };
+
+        self.source = args.source;
+        self.count = args.count;
+        self.seconds = args.seconds;
+        self.text_options = args.text;
+        self.goal = args.goal;
+        self.repeat = args.repeat;
+        self.minimum = args.minimum;
+        self.blind = args.blind;
+        self.bookmark = args.bookmark;
+        self.zen = args.zen;
+        self.stream = args.stream;
+        if let Some(seed) = args.seed {
+            self.rng = StdRng::seed_from_u64(seed);
+            self.seed = Some(seed);
+        }
+        self.reset();
     }
 
+    // Test time so far, not counting pauses.
     fn elapsed(&self) -> f64 {
+        if let Some(playback) = &self.playback {
+            return playback.position();
+        }
+
         self.started_at
             .map(|t| {
-                if self.finished_at.is_some() {
-                    self.finished_at.unwrap().duration_since(t).as_secs_f64()
-                } else {
-                    t.elapsed().as_secs_f64()
-                }
+                let end = self
+                    .finished_at
+                    .or(self.paused_at)
+                    .unwrap_or_else(Instant::now);
+
+                end.duration_since(t)
+                    .saturating_sub(self.paused)
+                    .as_secs_f64()
             })
             .unwrap_or(0.0)
     }
 
-    fn stats(&self) -> (f64, f64) {
-        let typed = self.input.value();
-        let total_typed = typed.chars().count() as u32;
-
-        let correct = self
-            .target
-            .chars()
-            .zip(typed.chars())
-            .filter(|(a, b)| a == b)
-            .count() as u32;
-
-        let elapsed = self.elapsed();
-        let wpm = if elapsed > 0.0 {
-            let minutes = elapsed / 60.0;
-            if minutes > 0.0 {
-                (total_typed as f64 / 5.0) / minutes
-            } else {
-                0.0
+    // Races run on a shared clock, so they can't be paused.
+    fn pause(&mut self, at: Instant) {
+        if self.started_at.is_some()
+            && self.finished_at.is_none()
+            && self.paused_at.is_none()
+            && self.race.is_none()
+        {
+            self.paused_at = Some(at);
+            self.dialogs.push(Dialog::Paused { resume_at: None });
+        }
+    }
+
+    pub fn pause_now(&mut self) {
+        self.pause(Instant::now());
+    }
+
+    pub fn focus_lost(&mut self) {
+        if self.paused_at.is_none() {
+            self.pause_now();
+            self.focus_lost = self.paused_at.is_some();
+        }
+    }
+
+    pub fn focus_gained(&mut self) {
+        if self.focus_lost && self.paused_at.is_some() {
+            let at = Instant::now() + Duration::from_secs(FOCUS_RESUME_SECONDS);
+            self.resume_at = Some(at);
+            for dialog in &mut self.dialogs {
+                if let Dialog::Paused { resume_at } = dialog {
+                    *resume_at = Some(at);
+                }
             }
-        } else {
-            0.0
+        }
+    }
+
+    fn resume(&mut self) {
+        self.focus_lost = false;
+        self.resume_at = None;
+        self.dialogs
+            .retain(|dialog| !matches!(dialog, Dialog::Paused { .. }));
+        if let Some(paused_at) = self.paused_at.take() {
+            self.paused += paused_at.elapsed();
+            self.last_key_at = Some(Instant::now());
+        }
+    }
+
+    // Idle for `afk_seconds` pauses the test as of the last key, so the time
+    // away doesn't count.
+    fn pause_when_idle(&mut self) {
+        if self.afk_seconds == 0 {
+            return;
+        }
+
+        if let Some(last) = self.last_key_at
+            && last.elapsed() >= Duration::from_secs(self.afk_seconds)
+        {
+            self.pause(last);
+        }
+    }
+
+    fn stats(&self) -> TestResult {
+        typing_stats(&self.target, self.input.value(), self.elapsed())
+    }
+
+    fn perform(&mut self, action: Action) {
+        match action {
+            Action::Restart => self.reset(),
+            Action::Retry => {
+                self.retype();
+                self.selection = Selection::default();
+                self.notice = None;
+                self.advance_at = None;
+                self.goal_missed = false;
+            }
+            Action::Quit
+                if self.confirm_quit && self.started_at.is_some() && self.finished_at.is_none() =>
+            {
+                let running = self.paused_at.is_none();
+                self.pause_now();
+                self.dialogs.push(Dialog::Quit {
+                    resume: running && self.paused_at.is_some(),
+                });
+            }
+            Action::Quit => {
+                self.save_bookmark();
+                self.quit = true;
+            }
+            Action::TogglePractice => self.practice = !self.practice,
+            Action::Pause => {
+                if self.paused_at.is_some() {
+                    self.resume();
+                } else {
+                    self.pause_now();
+                }
+            }
+            Action::Settings => {
+                let running = self.paused_at.is_none();
+                self.pause_now();
+
+                self.dialogs.push(Dialog::Settings {
+                    screen: SettingsScreen::new(self.current_settings()),
+                    resume: running && self.paused_at.is_some(),
+                });
+            }
+            Action::DeleteWord => self.delete_word(),
+            Action::ToggleTyped => self.typed_panel = !self.typed_panel,
+            Action::ToggleFocus => self.focus = !self.focus,
+            Action::Stop => {
+                if self.zen && self.started_at.is_some() && self.finished_at.is_none() {
+                    self.finish();
+                    self.update_race();
+                }
+            }
+            // Never bound here: the main loop takes these keys first.
+            Action::Suspend | Action::Interrupt => {}
+        }
+    }
+
+    // How far into the target this session got, rounded back to the start of
+    // the word being typed so a resumed session doesn't begin mid-word.
+    fn bookmark_progress(&self) -> usize {
+        let target: Vec<char> = self.target.chars().collect();
+        let typed = self.input.value().chars().count().min(target.len());
+
+        if typed == target.len() {
+            return typed;
+        }
+
+        target[..typed]
+            .iter()
+            .rposition(|c| c.is_whitespace())
+            .map_or(0, |i| i + 1)
+    }
+
+    fn save_bookmark(&mut self) {
+        let Some(bookmark) = &self.bookmark else {
+            return;
+        };
+
+        let position = bookmark.start + self.bookmark_progress();
+        if let Err(e) = save_bookmark(&bookmark.file, position) {
+            self.show_error(format!("Failed to save bookmark: {}", e));
+        }
+    }
+
+    // Fails the test once it's past the grace period and below -min-wpm or
+    // -min-acc.
+    fn check_minimum(&mut self) {
+        let Some(minimum) = self.minimum else {
+            return;
         };
 
-        let accuracy = if total_typed > 0 {
-            (correct as f64 / total_typed as f64) * 100.0
+        if self.started_at.is_none() || self.finished_at.is_some() || self.paused_at.is_some() {
+            return;
+        }
+
+        let result = self.stats();
+        if result.seconds < MINIMUM_GRACE_SECONDS {
+            return;
+        }
+
+        self.failed = if result.wpm < minimum.min_wpm {
+            Some(format!("WPM dropped below {:.0}", minimum.min_wpm))
+        } else if result.accuracy < minimum.min_accuracy {
+            Some(format!(
+                "accuracy dropped below {:.0}%",
+                minimum.min_accuracy
+            ))
         } else {
-            100.0
+            None
         };
+        if self.failed.is_some() {
+            self.finish();
+            self.update_race();
+        }
+    }
+
+    // The time runs out whether or not anything is being typed, so a racer
+    // who stops doesn't keep the round open for everyone else.
+    fn check_time_limit(&mut self) {
+        if self.zen || self.started_at.is_none() || self.finished_at.is_some() {
+            return;
+        }
 
-        (wpm, accuracy)
+        if self.elapsed() >= self.seconds as f64 {
+            self.finish();
+            self.update_race();
+        }
     }
 
-    pub fn handle_key(&mut self, key: event::KeyEvent) {
-        if self.finished_at.is_some() {
-            match key.code {
-                KeyCode::Enter => self.reset(),
-                _ => {}
-            }
+    // A failed test is saved as such, and Enter tries it again instead of
+    // moving on.
+    fn record_failure(&mut self, result: TestResult, reason: &str) {
+        self.notice = Some(format!("Failed: {}. Press Enter to try again.", reason));
+        self.goal_missed = true;
 
-            return;
+        let record = ResultRecord {
+            practice: self.practice,
+            failed: true,
+            replay: self.recording.clone(),
+            ..ResultRecord::new(result, source_name(&self.source).to_string())
+        };
+        if let Err(e) = self.save_result(record) {
+            self.show_error(format!("Failed to save results: {}", e));
         }
+    }
 
-        if self.started_at.is_none() {
-            self.started_at = Some(Instant::now());
+    fn save_recording(&mut self, result: TestResult) -> Option<String> {
+        if !self.record_replays {
+            return None;
         }
 
-        match key.code {
-            KeyCode::Char(c) => {
-                self.input.handle(InputRequest::InsertChar(c));
+        let recording = Recording {
+            header: RecordingHeader {
+                timestamp: now_timestamp(),
+                source: source_name(&self.source).to_string(),
+                target: self.target.clone(),
+                result,
+            },
+            keystrokes: self.keystrokes.clone(),
+        };
+
+        save_recording(&recording)
+            .inspect_err(|e| self.show_error(format!("Failed to save replay: {}", e)))
+            .ok()
+    }
+
+    fn show_error(&mut self, message: String) {
+        self.dialogs.push(Dialog::Error(message));
+    }
+
+    fn close_dialog(&mut self, dialog: Dialog, answer: Answer) {
+        match (dialog, answer) {
+            (Dialog::Quit { .. }, Answer::Yes) => {
+                self.save_bookmark();
+                self.quit = true;
             }
-            KeyCode::F(5) => {
-                self.reset();
+            (Dialog::Quit { resume: true }, Answer::No) => self.resume(),
+            (Dialog::Paused { .. }, Answer::Yes) => self.resume(),
+            // Still paused behind the question, and again if it's answered no.
+            (dialog @ Dialog::Paused { .. }, Answer::No) => {
+                self.dialogs.push(dialog);
+                self.perform(Action::Quit);
             }
-            KeyCode::Backspace => {
-                self.input.handle(InputRequest::DeletePrevChar);
+            (Dialog::Settings { screen, resume }, Answer::Yes) => {
+                self.apply_settings(screen, resume)
             }
+            (Dialog::Settings { resume: true, .. }, Answer::No) => self.resume(),
             _ => {}
         }
+    }
 
-        let typed = self.input.value();
-        if typed.len() >= self.target.len() {
-            self.finished_at = Some(Instant::now());
+    // Restarts a test of generated words with what was changed straight away,
+    // and saves it as the defaults for new tests. If the new words can't be
+    // loaded the dialog stays open, saying why.
+    fn apply_settings(&mut self, mut screen: SettingsScreen, resume: bool) {
+        let changes = screen.changes();
+        if changes.is_empty() {
+            if resume {
+                self.resume();
+            }
+
+            return;
         }
 
-        if self.started_at.is_some() && self.elapsed() >= self.seconds as f64 {
-            self.finished_at = Some(Instant::now());
+        let language_changed = changes.iter().any(|(key, _)| *key == "language");
+        let restarted = match self.use_settings(screen.settings.clone(), language_changed) {
+            Ok(restarted) => restarted,
+            Err(e) => {
+                screen.error = Some(e);
+                self.dialogs.push(Dialog::Settings { screen, resume });
+
+                return;
+            }
+        };
+
+        for (key, value) in &changes {
+            if let Err(e) = set_config_value(key, value) {
+                self.show_error(format!("Failed to save config: {}", e));
+
+                break;
+            }
         }
-    }
+        self.language = screen.settings.language;
 
-    pub fn draw_ui(&self, f: &mut Frame) {
-        let area = f.area();
+        if !restarted {
+            self.command_reply =
+                Some("Saved for the next tests started without options.".to_string());
+            if resume {
+                self.resume();
+            }
+        }
+    }
 
-        let chunks = Layout::default()
-            .direction(Direction::Vertical)
-            .margin(1)
-            .constraints(
-                [
-                    Constraint::Length(3), // Title
-                    Constraint::Min(5),    // Target (multi-line)
-                    Constraint::Length(3), // Typed
-                    Constraint::Length(3), // Stats
-                    Constraint::Min(0),
-                ]
-                .as_ref(),
-            )
-            .split(area);
+    fn current_settings(&self) -> Settings {
+        Settings {
+            time: self.seconds,
+            count: if self.stream && !self.zen {
+                0
+            } else {
+                self.count
+            },
+            language: self.language.clone(),
+            punctuation: self.text_options.punctuation,
+            numbers: self.text_options.numbers,
+        }
+    }
 
-        let title = Paragraph::new("Terminal Typing").alignment(Alignment::Center);
-        f.render_widget(title, chunks[0]);
+    // Restarts a test of generated words with `settings`. Other tests can't
+    // change on the fly, and are left alone.
+    fn use_settings(&mut self, settings: Settings, language_changed: bool) -> Result<bool, String> {
+        let generated = matches!(self.source, TextSource::RandomWords(_));
+        if !generated || self.zen || self.race.is_some() || self.in_playlist() {
+            return Ok(false);
+        }
 
-        let typed_block = Block::default().title("Typed Words").borders(Borders::ALL);
-        let typed_inner = typed_block.inner(chunks[2]);
-        let typed_width = typed_inner.width.max(1);
+        let Settings {
+            time,
+            count,
+            language,
+            punctuation,
+            numbers,
+        } = settings;
 
-        let typed_layout = layout_text(self.input.value(), typed_width);
+        if language_changed {
+            self.source = TextSource::RandomWords(match (&language, &self.dict) {
+                (Some(name), _) => load_language(name)?,
+                (None, Some(path)) => load_dictionary(path)?,
+                (None, None) => load_system_dictionary()?,
+            });
+        }
 
-        let (cursor_row, cursor_col) =
-            cursor_row_col_from_layout(&typed_layout, self.input.cursor());
+        self.seconds = time;
+        self.stream = count == 0 && self.repeat.is_none();
+        if count > 0 {
+            self.count = count;
+        }
+        self.text_options.punctuation = punctuation;
+        self.text_options.numbers = numbers;
 
-        let typed_visible_height = typed_inner.height.max(1);
-        let total_lines = typed_layout.len() as u16;
-        let max_scroll = total_lines.saturating_sub(typed_visible_height);
-        let desired_scroll = cursor_row.saturating_sub(typed_visible_height - 1);
-        let scroll_y = desired_scroll.min(max_scroll);
+        self.reset();
 
-        let target_block = Block::default().title("Target Text").borders(Borders::ALL);
-        let target_inner = target_block.inner(chunks[1]);
-        let target_width = target_inner.width.max(1);
-        let target_visible_height = target_inner.height.max(1);
+        Ok(true)
+    }
 
-        let target_layout = layout_text(&self.target, target_width);
+    // The `:` prompt is there between tests, and before one as long as the
+    // text doesn't itself start with a colon.
+    fn can_open_command(&self) -> bool {
+        self.race.is_none()
+            && (self.finished_at.is_some()
+                || (self.started_at.is_none()
+                    && self.countdown_until.is_none()
+                    && !self.target.starts_with(':')))
+    }
 
-        let target_lines = build_target_lines_from_layout(
-            &target_layout,
-            self.input.value(),
-            scroll_y,
-            target_visible_height,
-        );
+    fn edit_command(&mut self, key: KeyEvent) {
+        let Some(command) = &mut self.command else {
+            return;
+        };
 
-        let target_paragraph = Paragraph::new(target_lines)
-            .block(target_block)
-            .wrap(Wrap { trim: false });
-        f.render_widget(target_paragraph, chunks[1]);
+        let request = match key.code {
+            KeyCode::Enter => {
+                let line = command.value().to_string();
+                self.command = None;
+                self.run_command(&line);
 
-        let typed_visible =
-            build_typed_visible_from_layout(&typed_layout, scroll_y, typed_visible_height);
+                return;
+            }
+            KeyCode::Esc => {
+                self.command = None;
 
-        let typed_paragraph = Paragraph::new(typed_visible)
-            .block(typed_block)
-            .wrap(Wrap { trim: false });
-        f.render_widget(typed_paragraph, chunks[2]);
-
-        let cursor_screen_x = typed_inner.x + cursor_col;
-        let cursor_screen_y = typed_inner.y + cursor_row.saturating_sub(scroll_y);
-        f.set_cursor_position((cursor_screen_x, cursor_screen_y));
-
-        let (wpm, accuracy) = self.stats();
-        let stats_text = format!(
-            "Time: {:.0}s | WPM: {:.1} | Accuracy: {:.1}%",
-            self.elapsed(),
-            wpm,
-            accuracy
-        );
+                return;
+            }
+            KeyCode::Backspace if command.value().is_empty() => {
+                self.command = None;
 
-        let status = if self.finished_at.is_some() {
-            format!(
-                "{} | Finished! Press Enter to restart or ESC to quit.",
-                stats_text
-            )
-        } else {
-            stats_text
+                return;
+            }
+            KeyCode::Backspace => InputRequest::DeletePrevChar,
+            KeyCode::Left => InputRequest::GoToPrevChar,
+            KeyCode::Right => InputRequest::GoToNextChar,
+            KeyCode::Char(c) => InputRequest::InsertChar(c),
+            _ => return,
         };
 
-        let stats_block = Block::default().title("Stats").borders(Borders::ALL);
-        let stats_paragraph = Paragraph::new(status).block(stats_block);
-        f.render_widget(stats_paragraph, chunks[3]);
+        command.handle(request);
     }
+
+    fn run_command(&mut self, line: &str) {
+        let command = match parse_command(line) {
+            Ok(command) => command,
+            Err(e) => {
+                self.command_reply = Some(e);
+
+                return;
+            }
+        };
+
+        let mut settings = self.current_settings();
+        let mut language_changed = false;
+
+        match command {
+            Command::Restart => return self.perform(Action::Restart),
+            Command::Retry => return self.perform(Action::Retry),
+            Command::Quit => {
+                self.save_bookmark();
+                self.quit = true;
+
+                return;
+            }
+            Command::Practice => return self.perform(Action::TogglePractice),
+            Command::Settings => return self.perform(Action::Settings),
+            Command::History => {
+                match load_results() {
+                    Ok(records) => self
+                        .dialogs
+                        .push(Dialog::History(HistoryScreen::new(records))),
+                    Err(e) => self.show_error(format!("Failed to load results: {}", e)),
+                }
+
+                return;
+            }
+            Command::Help => {
+                self.command_reply = Some(format!("Commands: {}", COMMAND_HELP));
+
+                return;
+            }
+            Command::Time(seconds) => settings.time = seconds,
+            Command::Words(count) => settings.count = count,
+            Command::Language(language) => {
+                settings.language = language;
+                language_changed = true;
+            }
+            Command::Punctuation(on) => {
+                settings.punctuation = on.unwrap_or(!settings.punctuation);
+            }
+            Command::Numbers(on) => settings.numbers = on.unwrap_or(!settings.numbers),
+            Command::Typed(on) => {
+                self.typed_panel = on.unwrap_or(!self.typed_panel);
+
+                return;
+            }
+            Command::Focus(on) => {
+                self.focus = on.unwrap_or(!self.focus);
+
+                return;
+            }
+        }
+
+        match self.use_settings(settings, language_changed) {
+            Ok(true) => {}
+            Ok(false) => {
+                self.command_reply = Some("Only a test of generated words can change".to_string());
+            }
+            Err(e) => self.command_reply = Some(e),
+        }
+    }
+
+    // Called when Ctrl+C or a signal ends the program mid-test: the bookmark is
+    // kept, and unless turned off what was typed so far is recorded as an
+    // incomplete result.
+    pub fn save_partial(&mut self) -> io::Result<()> {
+        self.save_bookmark();
+
+        if !self.save_interrupted || self.started_at.is_none() || self.finished_at.is_some() {
+            return Ok(());
+        }
+
+        let result = self.stats();
+        let record = ResultRecord {
+            practice: self.practice,
+            incomplete: true,
+            replay: self.save_recording(result),
+            ..ResultRecord::new(result, source_name(&self.source).to_string())
+        };
+
+        self.save_result(record)
+    }
+
+    // Kept for -json even if it couldn't be saved.
+    fn save_result(&mut self, record: ResultRecord) -> io::Result<()> {
+        self.session_results.push(record.clone());
+
+        append_results(&[record])
+    }
+
+    // With -json, once the terminal is back to normal.
+    pub fn print_results(&self) {
+        if !self.json {
+            return;
+        }
+
+        for record in &self.session_results {
+            if let Ok(line) = serde_json::to_string(record) {
+                println!("{}", line);
+            }
+        }
+    }
+
+    // After a test, the next one continues the text rather than starting it
+    // over, unless the whole text has been typed.
+    fn advance_bookmark(&mut self) {
+        self.save_bookmark();
+
+        // The chunks of this session are used up; typing them again must not
+        // move the bookmark any further.
+        if let TextSource::Chunks(_) = self.source {
+            self.bookmark = None;
+        }
+
+        let progress = self.bookmark_progress();
+        if let Some(bookmark) = &mut self.bookmark
+            && let TextSource::Fixed(text) = &mut self.source
+            && progress < text.chars().count()
+        {
+            *text = text.chars().skip(progress).collect();
+            bookmark.start += progress;
+        }
+    }
+
+    // Chunks are consecutive, so a finished one moves the bookmark past it and
+    // the space it was cut at. A chunk cut short by the time limit leaves a
+    // gap, so the bookmark stays where typing stopped for good.
+    fn advance_chunk_bookmark(&mut self) {
+        self.save_bookmark();
+
+        let progress = self.bookmark_progress();
+        if progress < self.target.chars().count() {
+            self.bookmark = None;
+        } else if let Some(bookmark) = &mut self.bookmark {
+            bookmark.start += progress + 1;
+        }
+    }
+
+    fn review(&mut self, action: ReviewAction) {
+        let target: Vec<char> = self.target.chars().collect();
+        let last = target.len().saturating_sub(1);
+        let caret = self.selection.caret;
+
+        match action {
+            ReviewAction::Left => self.selection.caret = caret.saturating_sub(1),
+            ReviewAction::Right => self.selection.caret = (caret + 1).min(last),
+            ReviewAction::WordLeft => {
+                let mut i = caret.saturating_sub(1);
+                while i > 0 && !(target[i - 1] == ' ' && target[i] != ' ') {
+                    i -= 1;
+                }
+                self.selection.caret = i;
+            }
+            ReviewAction::WordRight => {
+                let mut i = caret + 1;
+                while i < last && !(target[i - 1] == ' ' && target[i] != ' ') {
+                    i += 1;
+                }
+                self.selection.caret = i.min(last);
+            }
+            ReviewAction::Mark => {
+                self.selection.mark = match self.selection.mark {
+                    Some(_) => None,
+                    None => Some(caret),
+                };
+            }
+            ReviewAction::Copy => {
+                let (start, end) = self.selection.range();
+                let target_part: String = target.iter().skip(start).take(end - start).collect();
+                let typed_part: String = self
+                    .input
+                    .value()
+                    .chars()
+                    .skip(start)
+                    .take(end - start)
+                    .collect();
+
+                let text = format!("Target: {}\nTyped:  {}\n", target_part, typed_part);
+
+                self.terminal_output.push_str(&clipboard_sequence(&text));
+                self.notice = Some(format!("Copied {} characters", end - start));
+                self.selection.mark = None;
+            }
+            ReviewAction::Replay => {
+                let windows = mistake_windows(&self.keystrokes);
+
+                if windows.is_empty() {
+                    self.notice = Some("No clusters of mistakes to replay.".to_string());
+                } else {
+                    self.replay = Some(Replay::new(windows));
+                }
+            }
+            ReviewAction::Hands => self.show_hands = true,
+        }
+    }
+
+    fn tour(&mut self, action: TourAction) {
+        let Some(page) = self.tour else {
+            return;
+        };
+
+        self.tour = match action {
+            TourAction::Next if page + 1 < TOUR_PAGES.len() => Some(page + 1),
+            TourAction::Next => None,
+            TourAction::Previous => Some(page.saturating_sub(1)),
+            TourAction::Close => None,
+            TourAction::Dismiss => {
+                if let Err(e) = set_config_value("show_tour", "false") {
+                    self.show_error(format!("Failed to save config: {}", e));
+                }
+
+                None
+            }
+        };
+    }
+
+    fn control_playback(&mut self, key: KeyEvent) {
+        let KeyResult::Action(action) = self.playback_keymap.feed(key) else {
+            return;
+        };
+        let Some(playback) = &mut self.playback else {
+            return;
+        };
+
+        match action {
+            PlaybackAction::Pause => playback.toggle(),
+            PlaybackAction::Back => playback.seek_back(),
+            PlaybackAction::Forward => playback.seek_forward(),
+            PlaybackAction::Faster => playback.faster(),
+            PlaybackAction::Slower => playback.slower(),
+            PlaybackAction::Quit => self.quit = true,
+        }
+    }
+
+    pub fn handle_key(&mut self, key: event::KeyEvent) {
+        // Nothing else is on the screen to type into, and a test stays paused
+        // until it can be seen.
+        if self.too_small.get() && matches!(self.dialogs.last(), None | Some(Dialog::Paused { .. }))
+        {
+            return;
+        }
+
+        if let Some(dialog) = self.dialogs.last_mut() {
+            if let Some(answer) = dialog.answer(key)
+                && let Some(dialog) = self.dialogs.pop()
+            {
+                self.close_dialog(dialog, answer);
+            }
+
+            return;
+        }
+
+        if self.playback.is_some() {
+            self.control_playback(key);
+
+            return;
+        }
+
+        // Spectators have nothing to lose by leaving, so it isn't confirmed.
+        if self.race.as_ref().is_some_and(Race::spectator) {
+            if let KeyResult::Action(Action::Quit) = self.keymap.feed(key) {
+                self.quit = true;
+            }
+
+            return;
+        }
+
+        if self.tour.is_some() {
+            if let KeyResult::Action(action) = self.tour_keymap.feed(key) {
+                self.tour(action);
+            }
+
+            return;
+        }
+
+        if self.replay.take().is_some() {
+            return;
+        }
+
+        if self.show_hands {
+            self.show_hands = false;
+
+            return;
+        }
+
+        self.command_reply = None;
+        if self.command.is_some() {
+            self.edit_command(key);
+
+            return;
+        }
+
+        // Code is indented with Tab, so there it types rather than restarts.
+        let key = match self.keymap.feed(key) {
+            KeyResult::Action(Action::Restart)
+                if key.code == KeyCode::Tab && is_code(&self.source) =>
+            {
+                key
+            }
+            KeyResult::Action(action) => {
+                self.perform(action);
+
+                return;
+            }
+            KeyResult::Pending | KeyResult::Cancelled => return,
+            KeyResult::Unbound(key) => key,
+        };
+
+        if key.code == KeyCode::Char(':') && self.can_open_command() {
+            self.command = Some(Input::default());
+
+            return;
+        }
+
+        if self.finished_at.is_some() {
+            match self.review_keymap.feed(key) {
+                KeyResult::Action(action) => self.review(action),
+                KeyResult::Unbound(key) if key.code == KeyCode::Enter => {
+                    if self.advance_at.is_some() {
+                        self.advance_playlist();
+                    } else if self.goal_missed {
+                        self.reset();
+                    } else if self.race.is_some() {
+                        self.rematch();
+                    } else {
+                        self.playlist_results.clear();
+                        self.reset();
+                    }
+                }
+                _ => {}
+            }
+
+            return;
+        }
+
+        // Races start for everyone at once; keys before that don't count.
+        self.start_when_scheduled();
+        if let Some(race) = &mut self.race
+            && self.started_at.is_none()
+        {
+            if key.code == KeyCode::Enter && race.is_host() {
+                race.request_start();
+            } else if key.code == KeyCode::Enter {
+                race.toggle_ready();
+            }
+
+            return;
+        }
+
+        // With a countdown, the first key only gets it going.
+        if self.started_at.is_none() && self.countdown > 0 {
+            self.countdown_until
+                .get_or_insert_with(|| Instant::now() + Duration::from_secs(self.countdown));
+
+            return;
+        }
+
+        if self.started_at.is_none() {
+            self.start_test(Instant::now());
+        }
+        self.last_key_at = Some(Instant::now());
+
+        match key.code {
+            KeyCode::Char(c) => self.type_char(c),
+            KeyCode::Enter => self.type_char('\n'),
+            KeyCode::Tab => {
+                for c in self.tab.clone().chars() {
+                    self.type_char(c);
+                }
+            }
+            KeyCode::Backspace => {
+                self.input.handle(InputRequest::DeletePrevChar);
+                self.record_keystroke(KeystrokeEvent {
+                    seconds: self.elapsed(),
+                    key: None,
+                    correct: true,
+                });
+            }
+            _ => {}
+        }
+
+        self.extend_text();
+
+        let typed = self.input.value();
+        if typed.len() >= self.target.len() {
+            self.finish();
+        }
+
+        if !self.zen && self.started_at.is_some() && self.elapsed() >= self.seconds as f64 {
+            self.finish();
+        }
+
+        self.update_race();
+    }
+
+    fn start_test(&mut self, at: Instant) {
+        self.started_at = Some(at);
+        self.last_key_at = Some(at);
+        self.emit(Event::TestStart {
+            timestamp: now_timestamp(),
+            source: source_name(&self.source),
+            length: self.target.chars().count(),
+        });
+    }
+
+    // When the test starts by itself: at a race's shared start or at the end
+    // of the countdown.
+    fn scheduled_start(&self) -> Option<Instant> {
+        self.race
+            .as_ref()
+            .and_then(Race::start)
+            .or(self.countdown_until)
+    }
+
+    // The clock then runs from that moment, not from the first key.
+    fn start_when_scheduled(&mut self) {
+        if self.started_at.is_some() || self.finished_at.is_some() {
+            return;
+        }
+
+        if let Some(start) = self.scheduled_start()
+            && Instant::now() >= start
+        {
+            self.countdown_until = None;
+            self.start_test(start);
+        }
+    }
+
+    // How many words to generate for a new text.
+    fn batch(&self) -> usize {
+        if self.stream {
+            STREAM_BATCH_WORDS
+        } else {
+            self.count
+        }
+    }
+
+    // A streamed text is kept a few lines ahead of the caret, so it never
+    // runs out and no more is generated than gets typed.
+    fn extend_text(&mut self) {
+        if !self.stream {
+            return;
+        }
+
+        while self.target.len() < self.input.value().len() + STREAM_AHEAD_BYTES {
+            let (more, _) = next_target(
+                &mut self.source,
+                STREAM_BATCH_WORDS,
+                &self.text_options,
+                &self.errors,
+                &mut self.rng,
+            );
+            if more.is_empty() {
+                break;
+            }
+
+            self.target.push(self.text_options.separator.unwrap_or(' '));
+            self.target.push_str(&more.replace('\t', &self.tab));
+        }
+    }
+
+    fn type_char(&mut self, c: char) {
+        let position = self.input.value().chars().count();
+        let correct = self.target.chars().nth(position) == Some(c);
+        if !correct {
+            self.clean = false;
+        }
+
+        self.input.handle(InputRequest::InsertChar(c));
+        self.key_times.push(Instant::now());
+        self.record_keystroke(KeystrokeEvent {
+            seconds: self.elapsed(),
+            key: Some(c),
+            correct,
+        });
+    }
+
+    // Clicks answer dialogs and pick settings and results, and after a test a
+    // click on the stats panel, which says to press Enter, does what Enter
+    // does. The wheel moves through the settings, the results and the tour.
+    pub fn handle_mouse(&mut self, mouse: MouseEvent) {
+        if self.too_small.get() {
+            return;
+        }
+
+        let at = Position::new(mouse.column, mouse.row);
+        let area = self.dialog_area.get();
+        let left_click = mouse.kind == MouseEventKind::Down(MouseButton::Left);
+        let wheel = match mouse.kind {
+            MouseEventKind::ScrollUp => Some(false),
+            MouseEventKind::ScrollDown => Some(true),
+            _ => None,
+        };
+
+        if let Some(dialog) = self.dialogs.last_mut() {
+            match dialog {
+                Dialog::Settings { screen, .. } if left_click && area.contains(at) => {
+                    // Below the top border.
+                    screen.click((at.y - area.y).saturating_sub(1) as usize);
+                }
+                Dialog::Settings { screen, .. } if wheel.is_some() => {
+                    screen.scroll(wheel == Some(true));
+                }
+                Dialog::History(screen) if left_click && area.contains(at) => {
+                    screen.click((at.y - area.y).saturating_sub(1) as usize);
+                }
+                Dialog::History(screen) if wheel.is_some() => {
+                    screen.scroll(wheel == Some(true));
+                }
+                Dialog::Paused { .. } if left_click => {
+                    if let Some(dialog) = self.dialogs.pop() {
+                        self.close_dialog(dialog, Answer::Yes);
+                    }
+                }
+                _ if left_click && !area.contains(at) => {
+                    if let Some(dialog) = self.dialogs.pop() {
+                        self.close_dialog(dialog, Answer::No);
+                    }
+                }
+                Dialog::Error(_) if left_click => {
+                    self.dialogs.pop();
+                }
+                _ => {}
+            }
+
+            return;
+        }
+
+        if self.tour.is_some() {
+            match wheel {
+                Some(true) => self.tour(TourAction::Next),
+                Some(false) => self.tour(TourAction::Previous),
+                None => {}
+            }
+
+            return;
+        }
+
+        if left_click
+            && self.finished_at.is_some()
+            && self.playback.is_none()
+            && self.stats_area.get().contains(at)
+        {
+            self.handle_key(KeyEvent::from(KeyCode::Enter));
+        }
+    }
+
+    // Deletes back to the start of the word before the caret, recorded as one
+    // Backspace per character so replays and stats see the same thing.
+    fn delete_word(&mut self) {
+        if self.started_at.is_none() || self.finished_at.is_some() || self.paused_at.is_some() {
+            return;
+        }
+
+        let typed: Vec<char> = self.input.value().chars().collect();
+        let spaces = typed.iter().rev().take_while(|c| c.is_whitespace()).count();
+        let word = typed[..typed.len() - spaces]
+            .iter()
+            .rev()
+            .take_while(|c| !c.is_whitespace())
+            .count();
+
+        for _ in 0..spaces + word {
+            self.input.handle(InputRequest::DeletePrevChar);
+            self.record_keystroke(KeystrokeEvent {
+                seconds: self.elapsed(),
+                key: None,
+                correct: true,
+            });
+        }
+        self.update_race();
+    }
+
+    fn record_keystroke(&mut self, stroke: KeystrokeEvent) {
+        self.keystrokes.push(stroke);
+        self.emit(Event::Keystroke(stroke));
+    }
+
+    // WPM over the last few seconds, so pace alerts react to slowdowns quickly.
+    fn rolling_wpm(&self) -> Option<f64> {
+        let started_at = self.started_at?;
+        if started_at.elapsed().as_secs_f64() < PACE_WINDOW_SECONDS {
+            return None;
+        }
+
+        let recent = self
+            .key_times
+            .iter()
+            .rev()
+            .take_while(|t| t.elapsed().as_secs_f64() <= PACE_WINDOW_SECONDS)
+            .count();
+
+        Some((recent as f64 / 5.0) / (PACE_WINDOW_SECONDS / 60.0))
+    }
+
+    pub fn tick(&mut self) {
+        self.publish_stats();
+
+        // A recording plays back into the input as if it were being typed.
+        if let Some(playback) = &self.playback {
+            self.input = Input::new(typed_at(&self.keystrokes, playback.position()));
+
+            return;
+        }
+
+        if let Some(race) = &mut self.race {
+            race.poll();
+        }
+
+        // Spectators follow the racers from round to round and type nothing.
+        if self.race.as_ref().is_some_and(Race::spectator) {
+            if self.race.as_ref().is_some_and(Race::rematch_requested) {
+                self.rematch();
+            }
+            self.start_when_scheduled();
+
+            return;
+        }
+
+        self.start_when_scheduled();
+        self.pause_when_idle();
+        self.check_minimum();
+        self.check_time_limit();
+
+        // A test that can't be seen is paused as if the terminal lost focus,
+        // and counts down to going on once it can.
+        let too_small = self.too_small.get();
+        if too_small && !self.shrunk {
+            self.focus_lost();
+        } else if !too_small && self.shrunk {
+            self.focus_gained();
+        }
+        self.shrunk = too_small;
+
+        if !too_small && self.resume_at.is_some_and(|t| Instant::now() >= t) {
+            self.resume();
+        }
+
+        // Once finished, a rematch someone else started is joined right away.
+        if self.finished_at.is_some() && self.race.as_ref().is_some_and(Race::rematch_requested) {
+            self.rematch();
+        }
+
+        if let Some(replay) = &mut self.replay
+            && !replay.tick()
+        {
+            self.replay = None;
+        }
+
+        if self.advance_at.is_some_and(|t| Instant::now() >= t) {
+            self.advance_playlist();
+        }
+
+        if self.finished_at.is_some()
+            || self.paused_at.is_some()
+            || (self.pace_floor.is_none() && self.pace_ceiling.is_none())
+        {
+            return;
+        }
+
+        let Some(wpm) = self.rolling_wpm() else {
+            return;
+        };
+
+        let too_slow = self.pace_floor.is_some_and(|floor| wpm < floor);
+        let too_fast = self.pace_ceiling.is_some_and(|ceiling| wpm > ceiling);
+
+        if !too_slow && !too_fast {
+            self.off_pace_since = None;
+            self.pace_alerted = false;
+
+            return;
+        }
+
+        let since = *self.off_pace_since.get_or_insert_with(Instant::now);
+        if !self.pace_alerted && since.elapsed().as_secs_f64() >= self.pace_alert_seconds {
+            self.terminal_output.push_str(BELL);
+            self.pace_alerted = true;
+        }
+    }
+
+    fn finish(&mut self) {
+        if self.finished_at.is_some() {
+            return;
+        }
+
+        self.finished_at = Some(Instant::now());
+
+        // Words generated ahead but never reached aren't part of the test.
+        if self.stream {
+            let typed = self.input.value().chars().count();
+            self.target = self.target.chars().take(typed).collect();
+        }
+
+        self.errors.record(&self.target, self.input.value());
+        if let Err(e) = save_error_history(&self.errors) {
+            self.show_error(format!("Failed to save error history: {}", e));
+        }
+
+        let result = self.stats();
+
+        // Every timed run gets its own start and end, including each sentence
+        // of -sentences and each attempt of -repeat.
+        self.emit(Event::TestEnd {
+            timestamp: now_timestamp(),
+            result,
+        });
+
+        if let Some(path) = &self.bundle {
+            let bundle = Bundle::new(&self.target, &self.keystrokes, result, self.seed);
+            if let Err(e) = bundle.write(path) {
+                self.show_error(format!("Failed to write bundle: {}", e));
+            }
+        }
+
+        self.recording = self.save_recording(result);
+
+        if let Some(reason) = self.failed.clone() {
+            self.record_failure(result, &reason);
+
+            return;
+        }
+
+        // Ghosts replay recordings, so runs that aren't recorded don't get one.
+        if self.ghosts
+            && !self.stream
+            && self.race.is_none()
+            && let Some(replay) = &self.recording
+            && let Err(e) = save_ghost(&self.target, now_timestamp(), result.wpm, replay)
+        {
+            self.show_error(format!("Failed to save ghost: {}", e));
+        }
+
+        if let Some(repeat) = self.repeat {
+            let flawless = self.clean && self.input.value() == self.target;
+            let passed = (flawless || !repeat.flawless)
+                && result.wpm >= repeat.min_wpm
+                && result.accuracy >= repeat.min_accuracy;
+
+            self.attempts += 1;
+            self.streak = if passed { self.streak + 1 } else { 0 };
+            self.last_attempt = Some(result);
+            if self.best_attempt.is_none_or(|best| result.wpm > best.wpm) {
+                self.best_attempt = Some(result);
+            }
+
+            if self.streak < repeat.times {
+                self.retype();
+
+                return;
+            }
+
+            self.streak = 0;
+            self.notice = Some(if repeat.flawless {
+                format!(
+                    "Typed perfectly {} times in a row, {} attempts in total.",
+                    repeat.times, self.attempts
+                )
+            } else {
+                format!(
+                    "Reached {:.0} WPM at {:.0}% in {} attempts.",
+                    repeat.min_wpm, repeat.min_accuracy, self.attempts
+                )
+            });
+        }
+
+        let result = if let TextSource::Sentences(parts) | TextSource::Chunks(parts) = &self.source
+        {
+            self.sentence_results.push(result);
+
+            if let Some(next) = parts.get(self.sentence_results.len()).cloned() {
+                if let TextSource::Chunks(_) = self.source {
+                    self.advance_chunk_bookmark();
+                }
+
+                self.target = next;
+                self.retype();
+
+                return;
+            }
+
+            combine_results(&self.sentence_results)
+        } else {
+            result
+        };
+
+        self.advance_bookmark();
+        self.last_result = Some(result);
+
+        let record = ResultRecord {
+            practice: self.practice,
+            replay: self.recording.clone(),
+            ..ResultRecord::new(result, source_name(&self.source).to_string())
+        };
+        if let Err(e) = self.save_result(record) {
+            self.show_error(format!("Failed to save results: {}", e));
+        }
+
+        match self.completion_signal {
+            CompletionSignal::None => {}
+            CompletionSignal::Bell => self.terminal_output.push_str(BELL),
+            CompletionSignal::Flash => {
+                self.flash_until = Some(Instant::now() + Duration::from_millis(FLASH_MILLIS));
+            }
+        }
+
+        #[cfg(feature = "tts")]
+        if let Some(command) = &self.tts_command {
+            let text = format!(
+                "{:.0} words per minute, {:.0} percent accuracy",
+                result.wpm, result.accuracy
+            );
+
+            if let Err(e) = speak(command, &text) {
+                self.show_error(format!("Failed to run {}: {}", command, e));
+            }
+        }
+
+        if let Some(goal) = &self.goal
+            && let Some(lesson) = &goal.lesson
+            && let Err(e) = record_lesson_result(lesson, &result, goal.met(&result))
+        {
+            self.show_error(format!("Failed to save course progress: {}", e));
+        }
+
+        if let Some(goal) = &self.goal
+            && !goal.met(&result)
+        {
+            self.goal_missed = true;
+            self.notice = Some(format!(
+                "Not passed yet, {} needs {:.0} WPM at {:.0}% accuracy. Press Enter to retry.",
+                goal.name, goal.min_wpm, goal.min_accuracy
+            ));
+        } else if self.in_playlist() {
+            self.playlist_results.push(result);
+
+            if !self.playlist.is_empty() {
+                self.advance_at =
+                    Some(Instant::now() + Duration::from_secs(PLAYLIST_ADVANCE_SECONDS));
+            }
+        }
+
+        if let TextSource::Training(training) = &mut self.source
+            && let Some(letter) = training.progress.evaluate(
+                &self.target,
+                self.input.value(),
+                result.wpm,
+                result.accuracy,
+            )
+        {
+            match save_training_progress(&training.progress) {
+                Ok(()) => {
+                    self.notice = Some(format!(
+                        "Unlocked letter '{}'! Press Enter to continue.",
+                        letter
+                    ))
+                }
+                Err(e) => self.show_error(format!("Failed to save training progress: {}", e)),
+            }
+        }
+    }
+
+    // Moving to another line first draws the lines a row short of where they
+    // end up, so the view rolls rather than jumps.
+    fn roll_offset(&self, caret_row: u16) -> i16 {
+        let (row, from, moved_at) = self.roll.get();
+        if row != caret_row {
+            self.roll.set((caret_row, row, Instant::now()));
+
+            return if caret_row > row { 1 } else { -1 };
+        }
+
+        if moved_at.elapsed() < Duration::from_millis(ROLL_MILLIS) {
+            if row > from { 1 } else { -1 }
+        } else {
+            0
+        }
+    }
+
+    // Left/right load and alternation of the text and of what was typed, with
+    // the speed of each hand.
+    fn draw_hands(&self, f: &mut Frame) {
+        let text = HandBalance::of_text(&self.fingers, &self.target);
+        let typed = HandBalance::of_text(&self.fingers, self.input.value());
+        let (left_wpm, right_wpm) = hand_speeds(&self.fingers, &self.keystrokes);
+        let wpm = |wpm: Option<f64>| wpm.map_or("-".to_string(), |w| format!("{:.1}", w));
+
+        let body = vec![
+            Line::from(format!(
+                "Text:   left {:>5.1}%  right {:>5.1}%  alternating {:>5.1}%",
+                text.left_percent(),
+                text.right_percent(),
+                text.alternation_percent()
+            )),
+            Line::from(format!(
+                "Typed:  left {:>5.1}%  right {:>5.1}%  alternating {:>5.1}%",
+                typed.left_percent(),
+                typed.right_percent(),
+                typed.alternation_percent()
+            )),
+            Line::from(""),
+            Line::from(format!(
+                "Speed:  left {} WPM  right {} WPM",
+                wpm(left_wpm),
+                wpm(right_wpm)
+            )),
+        ];
+
+        render_popup(f, "Hand balance", body, " any key: close ");
+    }
+
+    // A bar per racer, ourselves first, under the stats.
+    fn draw_race(&self, f: &mut Frame, area: Rect) {
+        let Some(race) = &self.race else {
+            return;
+        };
+
+        let block = Block::default()
+            .title(match race.hosting() {
+                Some(addr) => format!("Race: join with --join {}", addr),
+                None => format!("Race: {}", race.room()),
+            })
+            .borders(Borders::ALL);
+        let inner = block.inner(area);
+
+        let name_width = race
+            .opponents()
+            .keys()
+            .map(|name| name.chars().count())
+            .chain([race.name().chars().count()])
+            .max()
+            .unwrap_or(0);
+        let bar_width = (inner.width as usize)
+            .saturating_sub(name_width + 24)
+            .max(1);
+
+        let line = |name: &str, progress: &Progress, style: Style| {
+            let done = if progress.total > 0 {
+                progress.typed.min(progress.total) as f64 / progress.total as f64
+            } else {
+                0.0
+            };
+            let filled = (done * bar_width as f64).round() as usize;
+
+            Line::from(vec![
+                Span::styled(format!("{:<width$} ", name, width = name_width), style),
+                Span::raw("█".repeat(filled)),
+                Span::raw("░".repeat(bar_width - filled)),
+                Span::raw(format!(
+                    " {:>3.0}% {:>5.1} WPM{}",
+                    done * 100.0,
+                    progress.wpm,
+                    if progress.finished() { " ✓" } else { "" }
+                )),
+            ])
+        };
+
+        let mut lines = Vec::new();
+        if !race.spectator() {
+            lines.push(line(
+                race.name(),
+                race.progress(),
+                Style::default().add_modifier(Modifier::BOLD),
+            ));
+        }
+        lines.extend(
+            race.opponents()
+                .iter()
+                .map(|(name, progress)| line(name, progress, Style::default())),
+        );
+
+        if !race.connected() {
+            lines.push(Line::from("Lost the connection to the relay."));
+        } else if race.opponents().is_empty() {
+            lines.push(Line::from("Waiting for others to join..."));
+        } else if race.spectator()
+            && let Some(standings) = race.standings()
+        {
+            lines.push(Line::from(""));
+            lines.extend(
+                standings.iter().enumerate().map(|(i, (name, wpm))| {
+                    Line::from(format!("{}. {} {:.1} WPM", i + 1, name, wpm))
+                }),
+            );
+        }
+
+        f.render_widget(Paragraph::new(lines).block(block), area);
+    }
+
+    // Who is in the room and ready, until the host starts the round.
+    fn draw_lobby(&self, f: &mut Frame) {
+        let Some(race) = &self.race else {
+            return;
+        };
+
+        let ours = (!race.spectator()).then_some((race.name(), race.progress()));
+        let body: Vec<Line> = ours
+            .into_iter()
+            .chain(
+                race.opponents()
+                    .iter()
+                    .map(|(name, progress)| (name.as_str(), progress)),
+            )
+            .map(|(name, progress)| {
+                let state = if race.host() == Some(name) {
+                    "host"
+                } else if progress.round == race.round() && progress.ready {
+                    "ready"
+                } else {
+                    "not ready"
+                };
+
+                Line::from(format!("{}  {}", name, state))
+            })
+            .collect();
+
+        let footer = if race.spectator() {
+            " waiting for the host "
+        } else if race.is_host() && race.all_ready() {
+            " Enter: start "
+        } else if race.is_host() {
+            " waiting for everyone to be ready "
+        } else if race.progress().ready {
+            " Enter: not ready "
+        } else {
+            " Enter: ready "
+        };
+
+        render_popup(f, &format!("Lobby: {}", race.room()), body, footer);
+    }
+
+    // Rounds won by each racer in a best-of-N series.
+    fn draw_series(&self, f: &mut Frame, area: Rect) {
+        let Some(race) = &self.race else {
+            return;
+        };
+
+        let block = Block::default()
+            .title(format!("Best of {}", race.best_of()))
+            .borders(Borders::ALL);
+
+        let mut lines = vec![Line::from(format!("Round {}", race.round() + 1))];
+        lines.extend(
+            iter::once(race.name())
+                .chain(race.opponents().keys().map(String::as_str))
+                .map(|name| {
+                    let wins = race.wins().get(name).copied().unwrap_or(0);
+
+                    Line::from(format!("{:<16} {:>2}", name, wins))
+                }),
+        );
+
+        if let Some(winner) = race.series_winner() {
+            lines.push(Line::from(""));
+            lines.push(Line::styled(
+                format!("{} wins the series!", winner),
+                Style::default().add_modifier(Modifier::BOLD),
+            ));
+        }
+
+        f.render_widget(Paragraph::new(lines).block(block), area);
+    }
+
+    pub fn draw_ui(&self, f: &mut Frame) {
+        let area = f.area();
+
+        // Drawn again at the right size as soon as the terminal is resized.
+        self.too_small
+            .set(area.width < MIN_WIDTH || area.height < MIN_HEIGHT);
+        if self.too_small.get() {
+            let message = format!(
+                "Terminal too small: need {}x{}, have {}x{}",
+                MIN_WIDTH, MIN_HEIGHT, area.width, area.height
+            );
+            let y = area.y + area.height / 2;
+            let row = Rect::new(
+                area.x,
+                y.min(area.bottom().saturating_sub(1)),
+                area.width,
+                1,
+            );
+
+            f.render_widget(
+                Paragraph::new(message)
+                    .alignment(Alignment::Center)
+                    .wrap(Wrap { trim: true }),
+                Rect {
+                    height: area.bottom() - row.y,
+                    ..row
+                },
+            );
+
+            return;
+        }
+
+        // On a wide screen everything goes in a centered column, `max_width`
+        // characters of text across plus the margins and borders.
+        let area = match self.max_width {
+            0 => area,
+            max_width => centered_rect(
+                max_width.saturating_add(4).max(MIN_WIDTH),
+                area.height,
+                area,
+            ),
+        };
+
+        // Without the Typed Words panel the caret is in the target text.
+        let show_typed = self.typed_panel && !self.overlay;
+        let typed_height = if show_typed { 3 } else { 0 };
+        let focused = self.focus && self.finished_at.is_none();
+        let panel_height = if focused { 0 } else { 3 };
+        let progress_height = if self.progress && !self.zen { 1 } else { 0 };
+
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .margin(1)
+            .constraints(
+                [
+                    Constraint::Length(panel_height),    // Title
+                    Constraint::Min(5),                  // Target (multi-line)
+                    Constraint::Length(progress_height), // Progress
+                    Constraint::Length(typed_height),    // Typed
+                    Constraint::Length(panel_height),    // Stats
+                    Constraint::Min(0),
+                ]
+                .as_ref(),
+            )
+            .split(area);
+
+        let title = Paragraph::new("Terminal Typing").alignment(Alignment::Center);
+        f.render_widget(title, chunks[0]);
+
+        let typed_block = Block::default().title("Typed Words").borders(Borders::ALL);
+        let typed_inner = typed_block.inner(chunks[3]);
+        let typed_width = typed_inner.width.max(1);
+
+        // While replaying, both panels show the input as it was at that moment.
+        let (typed, cursor) = match &self.replay {
+            Some(replay) => {
+                let typed = typed_at(&self.keystrokes, replay.position());
+                let cursor = typed.chars().count();
+
+                (typed, cursor)
+            }
+            None => (self.input.value().to_string(), self.input.cursor()),
+        };
+
+        // The rolling and stacked views are always centered, and an indent
+        // only makes sense for left-aligned text.
+        let (align, indent) = match self.viewport {
+            Viewport::Rolling | Viewport::Stack => (TextAlign::Left, 0),
+            Viewport::Full if self.text_align == TextAlign::Left => {
+                (TextAlign::Left, self.first_line_indent)
+            }
+            Viewport::Full => (self.text_align, 0),
+        };
+
+        let typed_layout = layout_text(&typed, typed_width, self.tab_width, indent);
+
+        let (cursor_row, cursor_col) = cursor_row_col_from_layout(&typed_layout, cursor);
+
+        let typed_visible_height = typed_inner.height.max(1);
+        let total_lines = typed_layout.len() as u16;
+        let max_scroll = total_lines.saturating_sub(typed_visible_height);
+        let desired_scroll = cursor_row.saturating_sub(typed_visible_height - 1);
+        let scroll_y = desired_scroll.min(max_scroll);
+
+        let target_block = Block::default().title("Target Text").borders(Borders::ALL);
+        let target_inner = target_block.inner(chunks[1]);
+        let target_width = target_inner.width.max(1);
+        let target_visible_height = target_inner.height.max(1);
+
+        let target_layout = if self.viewport == Viewport::Stack {
+            stack_layout(&self.target)
+        } else {
+            layout_text(&self.target, target_width, self.tab_width, indent)
+        };
+
+        let selection = self
+            .finished_at
+            .filter(|_| self.replay.is_none())
+            .map(|_| self.selection.range());
+        let caret = if selection.is_some() {
+            self.selection.caret
+        } else {
+            cursor
+        };
+        let (caret_row, caret_col) = cursor_row_col_from_layout(&target_layout, caret);
+
+        // The caret's line is kept `context_lines` below the top, or halfway
+        // down with `center_line`, so what was just typed stays in sight,
+        // until the end of the text is in view.
+        let context = if self.center_line {
+            (target_visible_height - 1) / 2
+        } else {
+            (self.context_lines as u16).min(target_visible_height - 1)
+        };
+        let target_scroll = caret_row
+            .saturating_sub(context)
+            .min((target_layout.len() as u16).saturating_sub(target_visible_height));
+
+        let colors = if is_code(&self.source) {
+            highlight_code(&self.target)
+        } else {
+            Vec::new()
+        };
+
+        // Eyes-closed practice: nothing typed is shown until the test is over.
+        let hidden = self.blind && self.finished_at.is_none();
+
+        let target_style = TargetStyle {
+            selection,
+            word: word_range(&self.target, cursor).filter(|_| selection.is_none() && !hidden),
+            colors: &colors,
+            space: self.space_glyph,
+            ghost: self
+                .ghost
+                .as_ref()
+                .filter(|_| self.started_at.is_some() && self.finished_at.is_none())
+                .map(|ghost| ghost.position(self.elapsed())),
+            overlay: self.overlay,
+            dim_untyped: !hidden,
+        };
+
+        // A paused test hides the text, so it can't be read ahead.
+        let target_lines = if self.paused_at.is_some() {
+            Vec::new()
+        } else if self.viewport == Viewport::Rolling {
+            build_rolling_lines(
+                &target_layout,
+                if hidden { "" } else { &typed },
+                caret_row,
+                target_visible_height,
+                self.roll_offset(caret_row),
+                &target_style,
+            )
+        } else if self.viewport == Viewport::Stack {
+            build_stack_lines(
+                &target_layout,
+                if hidden { "" } else { &typed },
+                caret_row,
+                target_visible_height,
+                &target_style,
+            )
+        } else {
+            build_target_lines_from_layout(
+                &target_layout,
+                if hidden { "" } else { &typed },
+                target_scroll,
+                target_visible_height,
+                &target_style,
+            )
+            .into_iter()
+            .enumerate()
+            .map(|(i, mut line)| {
+                let row = target_scroll as usize + i;
+                let offset = line_offset(&target_layout, row, target_width, align, indent);
+                line.spans.insert(0, Span::raw(" ".repeat(offset as usize)));
+
+                line
+            })
+            .collect()
+        };
+
+        let target_paragraph = Paragraph::new(target_lines)
+            .block(target_block)
+            .wrap(Wrap { trim: false });
+        f.render_widget(target_paragraph, chunks[1]);
+
+        if progress_height > 0 {
+            self.draw_progress(f, chunks[2], cursor);
+        }
+
+        let typed_visible = if hidden {
+            String::new()
+        } else {
+            build_typed_visible_from_layout(&typed_layout, scroll_y, typed_visible_height)
+                .split('\n')
+                .enumerate()
+                .map(|(i, line)| {
+                    let row = scroll_y as usize + i;
+                    let offset = line_offset(&typed_layout, row, typed_width, align, indent);
+
+                    format!("{}{}", " ".repeat(offset as usize), line)
+                })
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+
+        let typed_paragraph = Paragraph::new(typed_visible)
+            .block(typed_block)
+            .wrap(Wrap { trim: false });
+        f.render_widget(typed_paragraph, chunks[3]);
+
+        let (cursor_screen_x, cursor_screen_y) = if !show_typed {
+            if hidden || self.paused_at.is_some() {
+                (target_inner.x, target_inner.y)
+            } else {
+                let len = target_layout
+                    .get(caret_row as usize)
+                    .map_or(0, |line| line.len());
+                let centered_x = target_width.saturating_sub(len as u16) / 2 + caret_col;
+
+                let (x, y) = match self.viewport {
+                    Viewport::Rolling => (
+                        centered_x,
+                        (target_visible_height / 2)
+                            .saturating_add_signed(self.roll_offset(caret_row)),
+                    ),
+                    Viewport::Stack => (centered_x, target_visible_height / 3),
+                    Viewport::Full => (
+                        line_offset(
+                            &target_layout,
+                            caret_row as usize,
+                            target_width,
+                            align,
+                            indent,
+                        ) + caret_col,
+                        caret_row.saturating_sub(target_scroll),
+                    ),
+                };
+
+                (target_inner.x + x, target_inner.y + y)
+            }
+        } else if hidden {
+            (typed_inner.x, typed_inner.y)
+        } else {
+            let offset = line_offset(
+                &typed_layout,
+                cursor_row as usize,
+                typed_width,
+                align,
+                indent,
+            );
+
+            (
+                typed_inner.x + offset + cursor_col,
+                typed_inner.y + cursor_row.saturating_sub(scroll_y),
+            )
+        };
+        if self.soft_cursor {
+            let caret = Position::new(cursor_screen_x, cursor_screen_y);
+            if typed_inner.contains(caret) || target_inner.contains(caret) {
+                f.buffer_mut()[caret].set_style(Style::default().add_modifier(Modifier::REVERSED));
+            }
+        } else {
+            f.set_cursor_position((cursor_screen_x, cursor_screen_y));
+        }
+
+        let (seconds, wpm, accuracy) = match &self.source {
+            TextSource::Sentences(_) | TextSource::Chunks(_) if self.finished_at.is_some() => {
+                let total = combine_results(&self.sentence_results);
+
+                (total.seconds, total.wpm, total.accuracy)
+            }
+            _ => {
+                let result = self.stats();
+
+                (result.seconds, result.wpm, result.accuracy)
+            }
+        };
+
+        let stats_text = if hidden {
+            format!("Time: {:.0}s | WPM: {:.1}", seconds, wpm)
+        } else {
+            format!(
+                "Time: {:.0}s | WPM: {:.1} | Accuracy: {:.1}%",
+                seconds, wpm, accuracy
+            )
+        };
+
+        let stats_text = match &self.source {
+            TextSource::Training(training) => {
+                let letters: String = training.progress.letters().into_iter().collect();
+
+                format!("{} | Letters: {}", stats_text, letters)
+            }
+            TextSource::Sentences(parts) | TextSource::Chunks(parts) => {
+                let done = self.sentence_results.len();
+                let (label, total_label) = match self.source {
+                    TextSource::Chunks(_) => ("Chunk", "Chunks"),
+                    _ => ("Sentence", "Sentences"),
+                };
+
+                if self.finished_at.is_some() {
+                    format!("{}: {} | {}", total_label, done, stats_text)
+                } else {
+                    let last = self
+                        .sentence_results
+                        .last()
+                        .map(|r| format!(" | Last: {:.1} WPM, {:.1}%", r.wpm, r.accuracy))
+                        .unwrap_or_default();
+
+                    format!(
+                        "{} {}/{} | {}{}",
+                        label,
+                        done + 1,
+                        parts.len(),
+                        stats_text,
+                        last
+                    )
+                }
+            }
+            _ => stats_text,
+        };
+
+        let stats_text = match self.repeat {
+            Some(repeat) if self.finished_at.is_none() && repeat.flawless => {
+                let last = self
+                    .last_attempt
+                    .map(|r| format!(" | Last: {:.1} WPM, {:.1}%", r.wpm, r.accuracy))
+                    .unwrap_or_default();
+
+                format!(
+                    "Attempt {} | Streak {}/{} | {}{}",
+                    self.attempts + 1,
+                    self.streak,
+                    repeat.times,
+                    stats_text,
+                    last
+                )
+            }
+            Some(repeat) if self.finished_at.is_none() => {
+                let best = self
+                    .best_attempt
+                    .map(|r| format!(" | Best: {:.1} WPM, {:.1}%", r.wpm, r.accuracy))
+                    .unwrap_or_default();
+
+                format!(
+                    "Attempt {} | Goal {:.0} WPM at {:.0}% | {}{}",
+                    self.attempts + 1,
+                    repeat.min_wpm,
+                    repeat.min_accuracy,
+                    stats_text,
+                    best
+                )
+            }
+            _ => stats_text,
+        };
+
+        let status = if self.finished_at.is_some() {
+            let author = self
+                .author
+                .as_ref()
+                .map(|a| format!(" | Quote by {}", a))
+                .unwrap_or_default();
+
+            let replaying = self.replay.as_ref().map(|replay| {
+                let (current, total) = replay.progress();
+
+                format!(
+                    "Replaying mistakes {}/{} at half speed, press any key to stop.",
+                    current, total
+                )
+            });
+
+            let hint = replaying
+                .or_else(|| self.notice.clone())
+                .unwrap_or_else(|| {
+                    if self.advance_at.is_some() && self.goal.is_some() {
+                        "Passed! Next lesson starts shortly, press Enter to start now.".to_string()
+                    } else if self.advance_at.is_some() {
+                        "Finished! Next test starts shortly, press Enter to start now.".to_string()
+                    } else if self.race.is_some() {
+                        format!(
+                            "Finished! Press Enter for a rematch{}. {}",
+                            self.quit_hint(),
+                            self.review_hint()
+                        )
+                    } else {
+                        let retry = self
+                            .keymap
+                            .describe(Action::Retry)
+                            .map(|key| format!(", {} to retry the same one", key))
+                            .unwrap_or_default();
+
+                        format!(
+                            "Finished! Press Enter for a new text{}{}. {}",
+                            retry,
+                            self.quit_hint(),
+                            self.review_hint()
+                        )
+                    }
+                });
+
+            format!("{}{} | {}", stats_text, author, hint)
+        } else {
+            stats_text
+        };
+
+        let status = match &self.playback {
+            Some(playback) if playback.controls() => format!(
+                "{} | Replay {:.1}/{:.1}s at {}x{} | {}",
+                status,
+                playback.position(),
+                playback.duration(),
+                playback.speed(),
+                if playback.paused() { ", paused" } else { "" },
+                self.playback_hint()
+            ),
+            _ => status,
+        };
+
+        let status = if let Some(goal) = &self.goal {
+            format!(
+                "{}: {:.0} WPM at {:.0}% to pass | {}",
+                goal.name, goal.min_wpm, goal.min_accuracy, status
+            )
+        } else {
+            status
+        };
+
+        let status = if let Some(require) = &self.require {
+            let outcome = match self.last_result {
+                Some(result) if self.finished_at.is_some() && require.met(&result) => " (met)",
+                Some(_) if self.finished_at.is_some() => " (not met)",
+                _ => "",
+            };
+
+            format!(
+                "Required: {:.0} WPM at {:.0}%{} | {}",
+                require.min_wpm, require.min_accuracy, outcome, status
+            )
+        } else {
+            status
+        };
+
+        let status = match &self.ghost {
+            Some(ghost) if self.finished_at.is_none() => {
+                format!("Ghost: {:.0} WPM | {}", ghost.wpm, status)
+            }
+            _ => status,
+        };
+
+        let status = match &self.minimum {
+            Some(minimum) if self.finished_at.is_none() => format!(
+                "Minimum: {:.0} WPM at {:.0}% | {}",
+                minimum.min_wpm, minimum.min_accuracy, status
+            ),
+            _ => status,
+        };
+
+        let status = if let Some(lesson) = self.goal.as_ref().and_then(|g| g.lesson.as_ref()) {
+            format!("Lesson {}/{} | {}", lesson.index + 1, lesson.total, status)
+        } else if self.in_playlist() {
+            let done = self.playlist_results.len();
+            let current = if self.finished_at.is_some() {
+                done
+            } else {
+                done + 1
+            };
+
+            format!(
+                "Test {}/{} | {}",
+                current,
+                current + self.playlist.len(),
+                status
+            )
+        } else {
+            status
+        };
+
+        let waiting = self.started_at.is_none() && self.finished_at.is_none();
+        let status = if self.race.as_ref().is_some_and(Race::spectator) {
+            match self.keymap.describe(Action::Quit) {
+                Some(key) => format!("Spectating, {} to quit", key),
+                None => "Spectating".to_string(),
+            }
+        } else if waiting && self.race.as_ref().is_some_and(|r| r.start().is_none()) {
+            format!("Waiting in the lobby | {}", status)
+        } else {
+            status
+        };
+
+        let status = if self.zen && self.finished_at.is_none() {
+            match self.keymap.describe(Action::Stop) {
+                Some(key) => format!("Zen, {} stops | {}", key, status),
+                None => format!("Zen | {}", status),
+            }
+        } else {
+            status
+        };
+
+        let status = if self.practice {
+            format!("Practice | {}", status)
+        } else {
+            status
+        };
+
+        let pending = self.keymap.pending();
+        let status = if pending.is_empty() {
+            status
+        } else {
+            let keys: Vec<String> = pending.iter().map(format_key).collect();
+
+            format!("{} | Keys: {} ...", status, keys.join(" "))
+        };
+
+        let stats_block = Block::default().title("Stats").borders(Borders::ALL);
+        let stats_paragraph = Paragraph::new(status).block(stats_block);
+        f.render_widget(stats_paragraph, chunks[4]);
+        self.stats_area.set(chunks[4]);
+
+        if self.race.as_ref().is_some_and(|race| race.best_of() > 1) {
+            let columns = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Min(0), Constraint::Length(SERIES_WIDTH)])
+                .split(chunks[5]);
+
+            self.draw_race(f, columns[0]);
+            self.draw_series(f, columns[1]);
+        } else {
+            self.draw_race(f, chunks[5]);
+        }
+
+        if self.finished_at.is_some()
+            && !self.goal_missed
+            && self.playlist.is_empty()
+            && self.in_playlist()
+        {
+            let results = &self.playlist_results;
+            let n = results.len() as f64;
+
+            let mut body: Vec<Line> = results
+                .iter()
+                .enumerate()
+                .map(|(i, r)| {
+                    Line::from(format!(
+                        "Test {:>2}: {:>6.1} WPM  {:>5.1}%  {:>4.0}s",
+                        i + 1,
+                        r.wpm,
+                        r.accuracy,
+                        r.seconds
+                    ))
+                })
+                .collect();
+
+            body.push(Line::from(""));
+            body.push(Line::from(format!(
+                "Average: {:>6.1} WPM  {:>5.1}%  {:>4.0}s total",
+                results.iter().map(|r| r.wpm).sum::<f64>() / n,
+                results.iter().map(|r| r.accuracy).sum::<f64>() / n,
+                results.iter().map(|r| r.seconds).sum::<f64>()
+            )));
+
+            let title = if self.goal.is_some() {
+                "Course complete"
+            } else {
+                "Playlist complete"
+            };
+
+            let footer = match self.keymap.describe(Action::Quit) {
+                Some(key) => format!(" Enter: new test | {}: quit ", key),
+                None => " Enter: new test ".to_string(),
+            };
+
+            render_popup(f, title, body, &footer);
+        }
+
+        if self.show_hands {
+            self.draw_hands(f);
+        }
+
+        if self.started_at.is_none()
+            && self.finished_at.is_none()
+            && self
+                .race
+                .as_ref()
+                .is_some_and(|race| race.start().is_none())
+        {
+            self.draw_lobby(f);
+        }
+
+        if self.started_at.is_none()
+            && let Some(start) = self.scheduled_start()
+        {
+            let left = start.saturating_duration_since(Instant::now());
+            let body = vec![
+                Line::styled(
+                    format!("{}", left.as_secs() + 1),
+                    Style::default().add_modifier(Modifier::BOLD),
+                )
+                .centered(),
+            ];
+
+            render_popup(f, "Get ready", body, "");
+        }
+
+        if self.flash_until.is_some_and(|t| Instant::now() < t) {
+            f.buffer_mut()
+                .set_style(area, Style::default().add_modifier(Modifier::REVERSED));
+        }
+
+        if let Some(page) = self.tour {
+            let (title, text) = TOUR_PAGES[page];
+            let body: Vec<Line> = text.iter().map(|l| Line::from(*l)).collect();
+            let footer = format!(
+                " {}/{} | Left/Right: page | Esc: close | d: don't show again ",
+                page + 1,
+                TOUR_PAGES.len()
+            );
+
+            render_popup(f, title, body, &footer);
+        }
+
+        self.draw_command_line(f);
+
+        if let Some(dialog) = self.dialogs.last() {
+            self.dialog_area.set(dialog.render(f));
+        }
+    }
+
+    // Hints name whatever keys the config bound, and leave out what it
+    // unbound.
+    fn quit_hint(&self) -> String {
+        self.keymap
+            .describe(Action::Quit)
+            .map(|key| format!(" or {} to quit", key))
+            .unwrap_or_default()
+    }
+
+    fn review_hint(&self) -> String {
+        let keys = |action| self.review_keymap.describe(action);
+
+        let moves: Vec<String> = [
+            ReviewAction::Left,
+            ReviewAction::Right,
+            ReviewAction::WordRight,
+            ReviewAction::WordLeft,
+        ]
+        .into_iter()
+        .filter_map(keys)
+        .collect();
+
+        let mut hints = Vec::new();
+        if !moves.is_empty() {
+            hints.push(format!("{} move", moves.join("/")));
+        }
+        for (action, what) in [
+            (ReviewAction::Mark, "marks"),
+            (ReviewAction::Copy, "copies"),
+            (ReviewAction::Replay, "replays mistakes"),
+            (ReviewAction::Hands, "shows hand balance"),
+        ] {
+            if let Some(key) = keys(action) {
+                hints.push(format!("{} {}", key, what));
+            }
+        }
+
+        if hints.is_empty() {
+            String::new()
+        } else {
+            format!("{}.", hints.join(", "))
+        }
+    }
+
+    fn playback_hint(&self) -> String {
+        let keys = |actions: &[PlaybackAction]| {
+            let keys: Vec<String> = actions
+                .iter()
+                .filter_map(|action| self.playback_keymap.describe(*action))
+                .collect();
+
+            (!keys.is_empty()).then(|| keys.join("/"))
+        };
+
+        [
+            (&[PlaybackAction::Pause][..], "play/pause"),
+            (&[PlaybackAction::Back, PlaybackAction::Forward], "seek"),
+            (&[PlaybackAction::Faster, PlaybackAction::Slower], "speed"),
+            (&[PlaybackAction::Quit], "quit"),
+        ]
+        .into_iter()
+        .filter_map(|(actions, what)| keys(actions).map(|keys| format!("{}: {}", keys, what)))
+        .collect::<Vec<_>>()
+        .join(", ")
+    }
+
+    // How far into the test this is: the time left when the text keeps coming
+    // until the time is up, otherwise how much of the text is typed and how
+    // many words are left.
+    fn draw_progress(&self, f: &mut Frame, area: Rect, cursor: usize) {
+        let (ratio, label) = if self.stream {
+            let elapsed = if self.started_at.is_some() {
+                self.elapsed()
+            } else {
+                0.0
+            };
+            let left = (self.seconds as f64 - elapsed).max(0.0);
+
+            (
+                elapsed / self.seconds.max(1) as f64,
+                format!("{:.0}s left", left.ceil()),
+            )
+        } else {
+            let total = self.target.chars().count();
+            let words_left = self
+                .target
+                .chars()
+                .skip(cursor)
+                .collect::<String>()
+                .split_whitespace()
+                .count();
+
+            (
+                cursor as f64 / total.max(1) as f64,
+                format!(
+                    "{:.0}% | {} {} left",
+                    cursor as f64 * 100.0 / total.max(1) as f64,
+                    words_left,
+                    if words_left == 1 { "word" } else { "words" }
+                ),
+            )
+        };
+
+        let gauge = LineGauge::default()
+            .ratio(ratio.clamp(0.0, 1.0))
+            .label(label)
+            .filled_style(Style::default().fg(Color::Green))
+            .unfilled_style(Style::default().add_modifier(Modifier::DIM));
+        f.render_widget(gauge, area.inner(Margin::new(1, 0)));
+    }
+
+    // The `:` prompt, or the answer to the last command, on the bottom row.
+    fn draw_command_line(&self, f: &mut Frame) {
+        let area = f.area();
+        if area.height == 0 {
+            return;
+        }
+        let row = Rect::new(area.x, area.bottom() - 1, area.width, 1);
+
+        if let Some(command) = &self.command {
+            f.render_widget(Paragraph::new(format!(":{}", command.value())), row);
+
+            let caret = Position::new(row.x + 1 + command.visual_cursor() as u16, row.y);
+            if !row.contains(caret) {
+                return;
+            }
+            if self.soft_cursor {
+                f.buffer_mut()[caret].set_style(Style::default().add_modifier(Modifier::REVERSED));
+            } else {
+                f.set_cursor_position(caret);
+            }
+        } else if let Some(reply) = &self.command_reply {
+            f.render_widget(Paragraph::new(reply.as_str()), row);
+        }
+    }
+}
+
+fn next_target(
+    source: &mut TextSource,
+    count: usize,
+    text_options: &TextOptions,
+    errors: &ErrorHistory,
+    rng: &mut StdRng,
+) -> (String, Option<String>) {
+    match source {
+        TextSource::RandomWords(dict) => {
+            let history = text_options.adaptive.then_some(errors);
+
+            (
+                separate(
+                    generate_text(dict.as_ref(), count, text_options, history, rng),
+                    text_options,
+                ),
+                None,
+            )
+        }
+        TextSource::Fixed(text) => (text.clone(), None),
+        TextSource::Sentences(parts) | TextSource::Chunks(parts) => (parts[0].clone(), None),
+        TextSource::Code(code) => (code.clone(), None),
+        // Rotating means every passage comes up once before any repeats.
+        TextSource::Passages(passages) => {
+            passages.rotate_left(1);
+
+            (passages[0].text.clone(), None)
+        }
+        TextSource::Drill(kind) => {
+            let history = text_options.adaptive.then_some(errors);
+
+            (
+                separate(
+                    generate_drill(*kind, count.min(DRILL_TOKEN_COUNT), history, rng),
+                    text_options,
+                ),
+                None,
+            )
+        }
+        TextSource::Charset(chars) => (
+            separate(
+                generate_charset_drill(chars, count.min(DRILL_TOKEN_COUNT), rng),
+                text_options,
+            ),
+            None,
+        ),
+        TextSource::Training(training) => (
+            separate(
+                training
+                    .model
+                    .generate(&training.progress, count.min(TRAINING_WORD_COUNT), rng),
+                text_options,
+            ),
+            None,
+        ),
+        // The list never changes, so this can only fail on the first pick,
+        // before the screen is taken over.
+        TextSource::Quote(quotes) => {
+            let Some(quote) = pick_quote(quotes, rng) else {
+                eprintln!("No quotes to pick from");
+
+                process::exit(1);
+            };
+
+            (quote.text.clone(), Some(quote.author.clone()))
+        }
+    }
+}
+
+// Generated words are always joined with single spaces, so those are the
+// ones to swap for another separator.
+fn separate(text: String, text_options: &TextOptions) -> String {
+    match text_options.separator {
+        Some(separator) => text.replace(' ', &separator.to_string()),
+        None => text,
+    }
+}
+
+// Code from one of several files is typed and highlighted like a single
+// code file. The passage being typed is always the first.
+fn is_code(source: &TextSource) -> bool {
+    match source {
+        TextSource::Code(_) => true,
+        TextSource::Passages(passages) => passages[0].code,
+        _ => false,
+    }
+}
+
+fn source_name(source: &TextSource) -> &'static str {
+    match source {
+        TextSource::RandomWords(_) => "words",
+        TextSource::Fixed(_) => "text",
+        TextSource::Sentences(_) => "sentences",
+        TextSource::Chunks(_) => "chunks",
+        TextSource::Code(_) => "code",
+        TextSource::Passages(_) => "text",
+        TextSource::Quote(_) => "quote",
+        TextSource::Training(_) => "training",
+        TextSource::Drill(DrillKind::Bigram) => "bigram drill",
+        TextSource::Drill(DrillKind::Trigram) => "trigram drill",
+        TextSource::Charset(_) => "charset",
+    }
+}
+
+// Overall result of consecutive runs: WPM weighted by time spent on each run.
+fn combine_results(results: &[TestResult]) -> TestResult {
+    let seconds: f64 = results.iter().map(|r| r.seconds).sum();
+    let wpm = if seconds > 0.0 {
+        results.iter().map(|r| r.wpm * r.seconds).sum::<f64>() / seconds
+    } else {
+        0.0
+    };
+    let accuracy = if results.is_empty() {
+        100.0
+    } else {
+        results.iter().map(|r| r.accuracy).sum::<f64>() / results.len() as f64
+    };
+
+    TestResult {
+        wpm,
+        accuracy,
+        seconds,
+    }
+}
+
+fn char_key(c: char) -> KeyEvent {
+    KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE)
 }
@@ -0,0 +1,34 @@
+use crate::history::data_dir;
+
+use std::{collections::HashMap, fs, io, path::PathBuf};
+
+fn bookmarks_path() -> Option<PathBuf> {
+    data_dir().map(|dir| dir.join("bookmarks.json"))
+}
+
+// Character positions in the prepared text, keyed by the file's canonical path.
+fn load_all_bookmarks() -> HashMap<String, usize> {
+    bookmarks_path()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+pub fn load_bookmark(file: &str) -> usize {
+    load_all_bookmarks().get(file).copied().unwrap_or(0)
+}
+
+pub fn save_bookmark(file: &str, position: usize) -> io::Result<()> {
+    let Some(path) = bookmarks_path() else {
+        return Ok(());
+    };
+
+    let mut all = load_all_bookmarks();
+    all.insert(file.to_string(), position);
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    fs::write(path, serde_json::to_string(&all)?)
+}
@@ -0,0 +1,125 @@
+use crate::{race::Progress, types::TestResult};
+
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use std::time::{Duration, Instant};
+
+// Each round a bot types up to this much faster or slower than its speed, and
+// each key takes between the two shares of the average time between keys.
+const FORM_SPREAD: f64 = 0.08;
+const KEY_SPREAD: (f64, f64) = (0.4, 1.6);
+const CHARS_PER_WORD: f64 = 5.0;
+
+// What a round is typed on: a text of `length` characters, which keeps
+// coming when it is `endless`, for at most `seconds` if the round is timed.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RoundText {
+    pub length: usize,
+    pub endless: bool,
+    pub seconds: Option<f64>,
+}
+
+// A simulated opponent, typing at about `wpm` from the start of each round.
+pub struct Bot {
+    name: String,
+    wpm: f64,
+    rng: StdRng,
+    // Seconds per key this round.
+    pace: f64,
+    typed: usize,
+    next_key: Option<Instant>,
+    finished_at: Option<Instant>,
+}
+
+impl Bot {
+    pub fn new(name: String, wpm: f64) -> Self {
+        let mut bot = Self {
+            name,
+            wpm,
+            rng: StdRng::from_os_rng(),
+            pace: 0.0,
+            typed: 0,
+            next_key: None,
+            finished_at: None,
+        };
+        bot.reset();
+
+        bot
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn reset(&mut self) {
+        let form = 1.0 + self.rng.random_range(-FORM_SPREAD..=FORM_SPREAD);
+        self.pace = 60.0 / (self.wpm * form * CHARS_PER_WORD);
+        self.typed = 0;
+        self.next_key = None;
+        self.finished_at = None;
+    }
+
+    fn key_interval(&mut self) -> Duration {
+        let spread = self.rng.random_range(KEY_SPREAD.0..KEY_SPREAD.1);
+
+        Duration::from_secs_f64(self.pace * spread)
+    }
+
+    // Types every key due by now in a round that began at `start`. The bot is
+    // done at the end of the text or when the time is up, whichever is first.
+    pub fn advance(&mut self, round: u64, start: Instant, text: RoundText) -> Progress {
+        let now = Instant::now();
+        let limit = if text.endless {
+            usize::MAX
+        } else {
+            text.length
+        };
+        let deadline = text
+            .seconds
+            .map(|seconds| start + Duration::from_secs_f64(seconds));
+
+        if text.length > 0 && now >= start && self.finished_at.is_none() {
+            let mut next = match self.next_key {
+                Some(next) => next,
+                None => start + self.key_interval(),
+            };
+
+            while next <= now && self.typed < limit && deadline.is_none_or(|d| next <= d) {
+                self.typed += 1;
+                if self.typed == limit {
+                    self.finished_at = Some(next);
+                }
+                next += self.key_interval();
+            }
+
+            self.next_key = Some(next);
+
+            if let Some(deadline) = deadline.filter(|d| now >= *d) {
+                self.finished_at.get_or_insert(deadline);
+            }
+        }
+
+        let end = self.finished_at.unwrap_or(now);
+        let seconds = end.saturating_duration_since(start).as_secs_f64();
+        let minutes = seconds / 60.0;
+        let wpm = if minutes > 0.0 {
+            self.typed as f64 / CHARS_PER_WORD / minutes
+        } else {
+            0.0
+        };
+
+        Progress {
+            round,
+            typed: self.typed,
+            total: text.length.max(self.typed),
+            wpm,
+            // Bots never make mistakes.
+            result: self.finished_at.map(|_| TestResult {
+                wpm,
+                accuracy: 100.0,
+                seconds,
+            }),
+            // Bots are always ready to go.
+            ready: true,
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use crate::{
+    helpers::{fnv1a, typing_stats},
+    results::now_timestamp,
+    types::{KeystrokeEvent, TestResult},
+};
+
+use serde::{Deserialize, Serialize};
+use std::{env, fs, io, process};
+
+// Stats are rounded for display, so re-simulated numbers only need to agree
+// to within this much.
+const TOLERANCE: f64 = 0.01;
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Environment {
+    pub os: String,
+    pub arch: String,
+    pub term: Option<String>,
+}
+
+// Everything needed to check a result after the fact: the text, every
+// keystroke with its timing, and the options the test was run with.
+// `checksum` only catches accidental changes: anyone can compute it, so it
+// proves nothing about who wrote the bundle.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Bundle {
+    pub version: String,
+    pub created: u64,
+    pub settings: Vec<String>,
+    // The seed the words were generated from, given with -seed or by a race.
+    #[serde(default)]
+    pub seed: Option<u64>,
+    pub environment: Environment,
+    pub target: String,
+    pub keystrokes: Vec<KeystrokeEvent>,
+    #[serde(flatten)]
+    pub result: TestResult,
+    #[serde(alias = "replay_hash")]
+    pub checksum: String,
+}
+
+impl Bundle {
+    pub fn new(
+        target: &str,
+        keystrokes: &[KeystrokeEvent],
+        result: TestResult,
+        seed: Option<u64>,
+    ) -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            created: now_timestamp(),
+            settings: env::args().skip(1).collect(),
+            seed,
+            environment: Environment {
+                os: env::consts::OS.to_string(),
+                arch: env::consts::ARCH.to_string(),
+                term: env::var("TERM").ok(),
+            },
+            target: target.to_string(),
+            keystrokes: keystrokes.to_vec(),
+            result,
+            checksum: checksum(target, keystrokes),
+        }
+    }
+
+    pub fn write(&self, path: &str) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+
+        fs::write(path, json)
+    }
+}
+
+// FNV-1a over the text and keystrokes. It is stable across builds and
+// platforms, unlike the standard library's hasher.
+fn checksum(target: &str, keystrokes: &[KeystrokeEvent]) -> String {
+    let mut bytes = target.as_bytes().to_vec();
+    for stroke in keystrokes {
+        bytes.extend_from_slice(&stroke.seconds.to_bits().to_le_bytes());
+        bytes.extend_from_slice(&stroke.key.map_or(u32::MAX, u32::from).to_le_bytes());
+        bytes.push(stroke.correct as u8);
+    }
+
+    format!("{:016x}", fnv1a(&bytes))
+}
+
+// Replays the keystrokes against the text and checks that they produce the
+// claimed stats.
+fn verify(bundle: &Bundle) -> Result<(), String> {
+    if checksum(&bundle.target, &bundle.keystrokes) != bundle.checksum {
+        return Err("checksum does not match the keystrokes".to_string());
+    }
+
+    let mut typed: Vec<char> = Vec::new();
+    let mut last = 0.0;
+
+    for (i, stroke) in bundle.keystrokes.iter().enumerate() {
+        if stroke.seconds < last || stroke.seconds > bundle.result.seconds + TOLERANCE {
+            return Err(format!("keystroke {} is out of order", i + 1));
+        }
+        last = stroke.seconds;
+
+        match stroke.key {
+            Some(c) => {
+                let correct = bundle.target.chars().nth(typed.len()) == Some(c);
+                if correct != stroke.correct {
+                    return Err(format!("keystroke {} is marked wrongly", i + 1));
+                }
+
+                typed.push(c);
+            }
+            None => {
+                typed.pop();
+            }
+        }
+    }
+
+    let typed: String = typed.into_iter().collect();
+    let TestResult { wpm, accuracy, .. } =
+        typing_stats(&bundle.target, &typed, bundle.result.seconds);
+
+    if (wpm - bundle.result.wpm).abs() > TOLERANCE {
+        return Err(format!(
+            "claimed {:.2} WPM, replay gives {:.2}",
+            bundle.result.wpm, wpm
+        ));
+    }
+
+    if (accuracy - bundle.result.accuracy).abs() > TOLERANCE {
+        return Err(format!(
+            "claimed {:.2}% accuracy, replay gives {:.2}%",
+            bundle.result.accuracy, accuracy
+        ));
+    }
+
+    Ok(())
+}
+
+pub fn run_verify_bundle(path: &str) {
+    let bundle: Bundle = fs::read_to_string(path)
+        .map_err(|e| e.to_string())
+        .and_then(|s| serde_json::from_str(&s).map_err(|e| e.to_string()))
+        .unwrap_or_else(|e| {
+            eprintln!("Failed to read bundle {}: {}", path, e);
+
+            process::exit(1);
+        });
+
+    if let Err(e) = verify(&bundle) {
+        eprintln!("Bundle does not verify: {}", e);
+
+        process::exit(1);
+    }
+
+    println!(
+        "OK: {:.0} WPM, {:.0}% accuracy over {:.1}s ({} keystrokes, ttt {})",
+        bundle.result.wpm,
+        bundle.result.accuracy,
+        bundle.result.seconds,
+        bundle.keystrokes.len(),
+        bundle.version
+    );
+}
@@ -0,0 +1,144 @@
+use crate::helpers::fnv1a;
+
+use std::{
+    env, fs,
+    io::{self, Read, Write},
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const CACHE_MAGIC: &[u8; 4] = b"TTTC";
+const CACHE_VERSION: u8 = 1;
+
+pub fn cache_dir() -> Option<PathBuf> {
+    env::var_os("XDG_CACHE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+        .map(|dir| dir.join("ttt"))
+}
+
+// The same file shares one entry however its path is written.
+fn canonical(source: &str) -> String {
+    fs::canonicalize(source)
+        .ok()
+        .and_then(|path| path.to_str().map(str::to_string))
+        .unwrap_or_else(|| source.to_string())
+}
+
+fn cache_file(source: &str, filters: &str) -> Option<PathBuf> {
+    // The separator keeps `a` + `bc` apart from `ab` + `c`.
+    let hash = fnv1a(format!("{}\0{}", source, filters).as_bytes());
+
+    cache_dir().map(|dir| dir.join(format!("dict-{:016x}.bin", hash)))
+}
+
+fn mtime_parts(mtime: SystemTime) -> (u64, u32) {
+    mtime
+        .duration_since(UNIX_EPOCH)
+        .map(|d| (d.as_secs(), d.subsec_nanos()))
+        .unwrap_or((0, 0))
+}
+
+pub fn load_cached_words(source: &str, filters: &str, mtime: SystemTime) -> Option<Vec<String>> {
+    let source = canonical(source);
+    let path = cache_file(&source, filters)?;
+    let mut bytes = Vec::new();
+    fs::File::open(path).ok()?.read_to_end(&mut bytes).ok()?;
+
+    let mut reader = ByteReader { bytes: &bytes };
+
+    if reader.take(4)? != CACHE_MAGIC || reader.take(1)? != [CACHE_VERSION] {
+        return None;
+    }
+
+    if reader.string()? != source || reader.string()? != filters {
+        return None;
+    }
+
+    if (reader.u64()?, reader.u32()?) != mtime_parts(mtime) {
+        return None;
+    }
+
+    // Every word takes at least its 4-byte length, so a corrupt count can't
+    // reserve more than the file could hold.
+    let count = reader.u32()? as usize;
+    let mut words = Vec::with_capacity(count.min(reader.bytes.len() / 4));
+    for _ in 0..count {
+        words.push(reader.string()?);
+    }
+
+    Some(words)
+}
+
+pub fn store_cached_words(
+    source: &str,
+    filters: &str,
+    mtime: SystemTime,
+    words: &[String],
+) -> io::Result<()> {
+    let source = canonical(source);
+    let Some(path) = cache_file(&source, filters) else {
+        return Ok(());
+    };
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let (secs, nanos) = mtime_parts(mtime);
+
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(CACHE_MAGIC);
+    bytes.push(CACHE_VERSION);
+    write_string(&mut bytes, &source);
+    write_string(&mut bytes, filters);
+    bytes.extend_from_slice(&secs.to_le_bytes());
+    bytes.extend_from_slice(&nanos.to_le_bytes());
+    bytes.extend_from_slice(&(words.len() as u32).to_le_bytes());
+    for word in words {
+        write_string(&mut bytes, word);
+    }
+
+    let tmp = path.with_extension("tmp");
+    fs::File::create(&tmp)?.write_all(&bytes)?;
+    fs::rename(tmp, path)
+}
+
+fn write_string(bytes: &mut Vec<u8>, s: &str) {
+    bytes.extend_from_slice(&(s.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(s.as_bytes());
+}
+
+struct ByteReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> ByteReader<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        if self.bytes.len() < n {
+            return None;
+        }
+
+        let (head, tail) = self.bytes.split_at(n);
+        self.bytes = tail;
+
+        Some(head)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.take(4)
+            .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        self.take(8)
+            .map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+    }
+
+    fn string(&mut self) -> Option<String> {
+        let len = self.u32()? as usize;
+
+        String::from_utf8(self.take(len)?.to_vec()).ok()
+    }
+}
@@ -0,0 +1,134 @@
+use crate::{
+    app::App,
+    config::Config,
+    keymap::{Keymaps, load_keymaps},
+    recording::{Recording, load_replay, playback_args},
+};
+
+use ratatui::{Terminal, TerminalOptions, Viewport, backend::CrosstermBackend, layout::Rect};
+use serde::Serialize;
+use std::{
+    cell::RefCell,
+    fs::File,
+    io::{self, BufWriter, Write},
+    process,
+    rc::Rc,
+};
+
+const CAST_WIDTH: u16 = 80;
+const CAST_HEIGHT: u16 = 24;
+// How long the last frame stays up before the cast ends.
+const HOLD_SECONDS: f64 = 2.0;
+
+// The first line of an asciinema v2 file.
+#[derive(Serialize)]
+struct CastHeader {
+    version: u8,
+    width: u16,
+    height: u16,
+    timestamp: u64,
+    title: String,
+}
+
+// What the terminal is sent while drawing, kept to be taken after each frame.
+#[derive(Clone, Default)]
+struct Output(Rc<RefCell<Vec<u8>>>);
+
+impl Output {
+    fn take(&self) -> String {
+        let bytes = std::mem::take(&mut *self.0.borrow_mut());
+
+        String::from_utf8_lossy(&bytes).into_owned()
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// `ttt export-cast REPLAY OUT`: renders the test screen once per keystroke
+// and writes what would have gone to the terminal as an asciinema cast.
+pub fn run_export_cast(replay: &str, out: &str, config: &Config) {
+    let recording = load_replay(replay);
+
+    let keymaps = load_keymaps(config).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+
+        process::exit(1);
+    });
+
+    if let Err(e) = write_cast(recording, keymaps, config, out) {
+        eprintln!("Failed to write cast {}: {}", out, e);
+
+        process::exit(1);
+    }
+
+    println!("Wrote {}", out);
+}
+
+fn write_cast(
+    recording: Recording,
+    keymaps: Keymaps,
+    config: &Config,
+    out: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let header = CastHeader {
+        version: 2,
+        width: CAST_WIDTH,
+        height: CAST_HEIGHT,
+        timestamp: recording.header.timestamp,
+        title: format!(
+            "ttt: {:.0} WPM, {:.0}% accuracy",
+            recording.header.result.wpm, recording.header.result.accuracy
+        ),
+    };
+    let duration = recording.header.result.seconds;
+    let mut times: Vec<f64> = recording.keystrokes.iter().map(|k| k.seconds).collect();
+    times.insert(0, 0.0);
+    times.push(duration);
+
+    let mut app = App::new(playback_args(&recording, config), keymaps, config);
+    app.set_playback(recording, false);
+
+    let output = Output::default();
+    let backend = CrosstermBackend::new(output.clone());
+    let mut terminal = Terminal::with_options(
+        backend,
+        TerminalOptions {
+            viewport: Viewport::Fixed(Rect::new(0, 0, CAST_WIDTH, CAST_HEIGHT)),
+        },
+    )?;
+    terminal.clear()?;
+
+    let mut file = BufWriter::new(File::create(out)?);
+    writeln!(file, "{}", serde_json::to_string(&header)?)?;
+
+    for seconds in times {
+        app.seek_playback(seconds);
+        app.tick();
+        terminal.draw(|frame| app.draw_ui(frame))?;
+
+        writeln!(
+            file,
+            "{}",
+            serde_json::to_string(&(seconds, "o", output.take()))?
+        )?;
+    }
+
+    // An empty event so players keep the last frame up for a moment.
+    writeln!(
+        file,
+        "{}",
+        serde_json::to_string(&(duration + HOLD_SECONDS, "o", ""))?
+    )?;
+
+    file.flush()?;
+
+    Ok(())
+}
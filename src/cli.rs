@@ -0,0 +1,518 @@
+use crate::{
+    drill::parse_charset,
+    fingers::Hand,
+    importers::ImportFormat,
+    race::DEFAULT_PORT,
+    types::{DrillKind, QuoteLength},
+};
+
+use clap::{Args, CommandFactory, Parser, Subcommand, error::ErrorKind};
+use std::{collections::HashSet, env, ffi::OsString, fmt};
+
+#[derive(Parser)]
+#[command(
+    name = "ttt",
+    about = "Typing tests in the terminal",
+    version,
+    args_conflicts_with_subcommands = true,
+    after_help = "By default, a random text using system dictionary is generated, falling back\n\
+                  to the built-in english-1k word list when it is missing. Options can also\n\
+                  be given with a single dash, e.g. -seconds 30."
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    #[command(flatten)]
+    pub test: TestOptions,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Take a typing test; the same as giving no command
+    Run(TestOptions),
+    /// Show averages and bests of saved results
+    #[command(
+        long_about = "Summarizes the results history. Only ranked results are counted \
+                            unless --practice (practice runs only) or --all is given. Results \
+                            matching the [outliers] rules in config.toml are not counted \
+                            either. Tests interrupted by a signal or failed by -min-wpm or \
+                            -min-acc are never counted."
+    )]
+    Stats(StatsOptions),
+    /// Export or import saved results
+    History {
+        #[command(subcommand)]
+        command: HistoryCommand,
+    },
+    /// Race others through a relay, on a LAN or against bots
+    Race(RaceArgs),
+    /// Run a relay for races
+    #[command(
+        long_about = "Runs a relay for `ttt race`. Racers only need to reach the relay, \
+                            so nobody else has to open a port."
+    )]
+    Relay {
+        /// Port to listen on
+        #[arg(long, default_value_t = DEFAULT_PORT)]
+        port: u16,
+    },
+    /// Play back a recorded test
+    Replay {
+        /// Replay file, or the name of one in the replays folder
+        file: String,
+    },
+    /// Turn a replay into an asciinema recording
+    ExportCast {
+        /// Replay file, or the name of one in the replays folder
+        replay: String,
+        /// Where to write the .cast file
+        out: String,
+    },
+    /// Check a result bundle written with -bundle
+    VerifyBundle {
+        /// Bundle to check
+        file: String,
+    },
+    /// Import results from another typing tool
+    #[command(
+        long_about = "Adds the results stored in PATH by another typing trainer to the \
+                            ttt results history."
+    )]
+    Import {
+        /// Which tool wrote PATH
+        #[arg(long, value_enum)]
+        from: ImportFormat,
+        /// File to import
+        path: String,
+    },
+    /// Show or change settings in the config file
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+}
+
+#[derive(Args)]
+pub struct StatsOptions {
+    /// Count practice runs only
+    #[arg(long, conflicts_with = "all")]
+    pub practice: bool,
+    /// Count every result, practice runs included
+    #[arg(long)]
+    pub all: bool,
+    /// List the last results, flagging the ones left out
+    #[arg(long)]
+    pub history: bool,
+    /// Show how often each finger mistypes, by the finger mapping in config.toml
+    #[arg(long)]
+    pub fingers: bool,
+}
+
+#[derive(Subcommand)]
+pub enum HistoryCommand {
+    /// Write every saved result as CSV, oldest first
+    #[command(
+        long_about = "Writes every saved result to FILE as CSV, oldest first, or to \
+                            stdout if FILE is -. The columns are always, in this order: \
+                            timestamp, date, wpm, accuracy, seconds, source, practice, incomplete, failed, \
+                            replay. date is the timestamp in UTC. accuracy and seconds are \
+                            empty for imported results that lack them."
+    )]
+    Export {
+        /// File to write, or - for stdout
+        #[arg(long, value_name = "FILE")]
+        csv: String,
+    },
+    /// Add the results of a Monkeytype CSV export (Account, Export CSV)
+    Import {
+        /// Monkeytype CSV export
+        #[arg(long, value_name = "FILE")]
+        monkeytype: String,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ConfigCommand {
+    /// Print where the config file is
+    Path,
+    /// Print the value of KEY, e.g. viewport or outliers.max_wpm
+    Get { key: String },
+    /// Set a top-level KEY to VALUE, given as TOML (words are taken as
+    /// strings), checking it before keeping it
+    Set { key: String, value: String },
+}
+
+#[derive(Args)]
+#[command(
+    long_about = "Races everyone who joins room CODE on the relay, showing their \
+                        progress as they type. The room code seeds the text, so all racers \
+                        should give the same test options (any of the usual ones, see `ttt \
+                        --help`). The relay defaults to `relay` in config.toml; run one with \
+                        `ttt relay`.\n\n\
+                        On a local network, one racer can --host the race instead, and the \
+                        others --join it at the address it shows. With only --bot racers and \
+                        no room to join, the race needs no network at all.\n\n\
+                        Each round begins in a lobby listing who is in the room. Press Enter \
+                        there to say you are ready; the host, whoever has been in the room \
+                        longest, presses Enter to start once everyone is. The relay then \
+                        counts everyone down together, and keys typed before the start don't \
+                        count. Press Enter after a round for a rematch on new words, which \
+                        everyone who has finished joins right away. The fastest racer wins \
+                        each round; with --best-of N the first to win more than half of N \
+                        rounds takes the series."
+)]
+pub struct RaceArgs {
+    /// Room to join on the relay
+    #[arg(long, value_name = "CODE")]
+    pub room: Option<String>,
+    /// Relay to race through, instead of `relay` in config.toml
+    #[arg(long, value_name = "HOST[:PORT]")]
+    pub relay: Option<String>,
+    /// Host a race on the local network
+    #[arg(long, conflicts_with = "join")]
+    pub host: bool,
+    /// Port to host the race on
+    #[arg(long, default_value_t = DEFAULT_PORT, requires = "host")]
+    pub port: u16,
+    /// Join a race hosted on the local network
+    #[arg(long, value_name = "HOST[:PORT]")]
+    pub join: Option<String>,
+    /// Name shown to the other racers, instead of $USER
+    #[arg(long)]
+    pub name: Option<String>,
+    /// Race until someone has won more than half of N rounds
+    #[arg(long, value_name = "N", default_value_t = 1,
+          value_parser = clap::value_parser!(u64).range(1..))]
+    pub best_of: u64,
+    /// Watch the race without typing
+    #[arg(long, conflicts_with = "bot")]
+    pub spectate: bool,
+    /// Race a bot typing at about WPM; give it more than once for more bots
+    #[arg(long, value_name = "WPM", value_parser = positive_wpm)]
+    pub bot: Vec<f64>,
+
+    #[command(flatten)]
+    pub test: TestOptions,
+}
+
+#[derive(Args, Clone, Default)]
+pub struct TestOptions {
+    /// Generate text using COUNT number of words. Without it, words keep
+    /// coming as you type until the time is up
+    #[arg(short, long, value_name = "COUNT")]
+    pub count: Option<usize>,
+    /// Time limit in SECONDS
+    #[arg(short, long, value_name = "SECONDS")]
+    pub seconds: Option<usize>,
+    /// Use text from file at PATH. Give it more than once, or a directory, to
+    /// type a different file in each test
+    #[arg(short, long, value_name = "PATH")]
+    pub text: Vec<String>,
+    /// Download plain text from URL (a raw gist, a Project Gutenberg .txt) and
+    /// use it like a -text file
+    #[arg(long, conflicts_with = "text")]
+    pub url: Option<String>,
+    /// Keep the indentation and line breaks of the -text file, pressing Enter
+    /// at the end of each line. On by default for source files
+    #[arg(long)]
+    pub code: bool,
+    /// Only use lines START to END of the -text file, counting from 1. Either
+    /// end can be left out
+    #[arg(long, value_name = "START:END", value_parser = parse_line_range)]
+    pub lines: Option<(usize, usize)>,
+    /// Type a -text file as one paragraph, with every line break and run of
+    /// spaces turned into a single space
+    #[arg(long)]
+    pub normalize: bool,
+    /// Continue a -text file where the last session stopped, to type through a
+    /// whole book over several sessions
+    #[arg(long)]
+    pub resume: bool,
+    /// Remove Markdown and HTML syntax (heading markers, link syntax, tags)
+    /// from -text or -url, leaving the prose
+    #[arg(long)]
+    pub strip_markup: bool,
+    /// Split a long -text file into tests of N words each, showing the stats
+    /// of each chunk and of all so far
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    pub chunk: Option<u64>,
+    /// With -text, type one sentence at a time and see the speed and accuracy
+    /// of each as soon as it is done
+    #[arg(long)]
+    pub sentences: bool,
+    /// Use dictionary file at PATH to generate a random text. Files with
+    /// `word<TAB>count` lines are sampled by frequency
+    #[arg(short, long, value_name = "PATH")]
+    pub dict: Option<String>,
+    /// Use built-in word list NAME to generate a random text: english-200,
+    /// english-1k or english-10k
+    #[arg(short, long, value_name = "NAME")]
+    pub language: Option<String>,
+    /// Generate a random text from several sources, given as NAME:WEIGHT pairs
+    /// separated by commas. NAME is a built-in word list, a -charset preset or
+    /// a dictionary path, e.g. english-1k:0.8,numbers:0.2
+    #[arg(long, value_name = "SOURCES")]
+    pub mix: Option<String>,
+    /// Use a random built-in quote, optionally limited to LENGTH
+    #[arg(short, long, value_name = "LENGTH", value_enum)]
+    pub quote: Option<Option<QuoteLength>>,
+    /// Add punctuation to the generated text
+    #[arg(short, long)]
+    pub punctuation: bool,
+    /// Mix numbers into the generated text
+    #[arg(short, long)]
+    pub numbers: bool,
+    /// Put SEP between generated words instead of a space: newline for one
+    /// word per line, or any single character
+    #[arg(long, value_name = "SEP", value_parser = parse_separator, allow_hyphen_values = true)]
+    pub separator: Option<Separator>,
+    /// Capitalize the start of each sentence, and optionally PCT percent of
+    /// all words
+    #[arg(long, value_name = "PCT", value_parser = clap::value_parser!(u32).range(0..=100))]
+    pub capitalize: Option<Option<u32>>,
+    /// Prefer words with characters you often mistype
+    #[arg(short, long)]
+    pub adaptive: bool,
+    /// Practice runs of common bigrams or trigrams; with -adaptive your
+    /// weakest ones
+    #[arg(long, value_name = "KIND", value_enum)]
+    pub drill: Option<DrillKind>,
+    /// Practice random groups of CHARS, or of a preset: homerow, toprow,
+    /// bottomrow, numbers or symbols
+    #[arg(long, value_name = "CHARS", value_parser = parse_charset_arg, allow_hyphen_values = true)]
+    pub charset: Option<CharsetArg>,
+    /// Hide what you type and which characters are wrong until the test is
+    /// over
+    #[arg(long)]
+    pub blind: bool,
+    /// Repeat the same text until it is typed without mistakes N times in a
+    /// row
+    #[arg(long, value_name = "N", conflicts_with_all = ["until_wpm", "until_acc"])]
+    pub repeat: Option<usize>,
+    /// With -repeat, only count runs at WPM or faster
+    #[arg(long, value_name = "WPM", requires = "repeat")]
+    pub repeat_wpm: Option<usize>,
+    /// Fail the test as soon as its WPM drops below WPM, after the first few
+    /// seconds
+    #[arg(long, value_name = "WPM")]
+    pub min_wpm: Option<usize>,
+    /// Fail the test as soon as its accuracy drops below PCT
+    #[arg(long, value_name = "PCT", value_parser = clap::value_parser!(u32).range(0..=100))]
+    pub min_acc: Option<u32>,
+    /// Repeat the same text until a run reaches WPM, showing the attempts so
+    /// far and the best of them
+    #[arg(long, value_name = "WPM")]
+    pub until_wpm: Option<usize>,
+    /// Likewise until a run reaches PCT percent accuracy; the two can be
+    /// combined
+    #[arg(long, value_name = "PCT", value_parser = clap::value_parser!(u32).range(0..=100))]
+    pub until_acc: Option<u32>,
+    /// Exit with status 0 only if the last test before quitting reached WPM,
+    /// and 1 otherwise
+    #[arg(long, value_name = "WPM")]
+    pub require_wpm: Option<usize>,
+    /// Likewise for an accuracy of PCT percent
+    #[arg(long, value_name = "PCT", value_parser = clap::value_parser!(u32).range(0..=100))]
+    pub require_acc: Option<u32>,
+    /// Generate the same text every time for the same N, so runs can be
+    /// repeated or raced on identical words
+    #[arg(long, value_name = "N")]
+    pub seed: Option<u64>,
+    /// Only use words typed entirely with the left or right hand, by the
+    /// finger mapping in the config; not with -mix
+    #[arg(long, value_enum, conflicts_with = "mix")]
+    pub hand: Option<Hand>,
+    /// Practice pseudo-words built from a growing set of letters, unlocking a
+    /// new letter once you are fast and accurate
+    #[arg(long)]
+    pub train: bool,
+    /// Keep generating words as you type, with no time or word limit, until
+    /// <leader> s stops the test
+    #[arg(long)]
+    pub zen: bool,
+    /// Start in practice mode: results are saved but left out of `ttt stats`
+    /// averages and bests. <leader> p toggles it
+    #[arg(long)]
+    pub practice: bool,
+    /// Run the tests listed in PATH back to back, one line of options per
+    /// test, and report aggregate results
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["lesson", "course"])]
+    pub playlist: Option<String>,
+    /// Run the lesson file at PATH until its goal is met
+    #[arg(long, value_name = "PATH", conflicts_with = "course")]
+    pub lesson: Option<String>,
+    /// Run the lesson files in DIR in order, moving on to the next lesson only
+    /// once the current one is passed
+    #[arg(long, value_name = "DIR")]
+    pub course: Option<String>,
+    /// Write newline-delimited JSON events (test_start, keystroke, test_end)
+    /// to file descriptor N
+    #[arg(long, value_name = "N", conflicts_with = "events_file")]
+    pub events_fd: Option<i32>,
+    /// Append the same events to the file at PATH
+    #[arg(long, value_name = "PATH")]
+    pub events_file: Option<String>,
+    /// Serve the live WPM, accuracy and progress as JSON over a WebSocket on
+    /// localhost:PORT, e.g. for a stream overlay
+    #[arg(long, value_name = "PORT")]
+    pub serve_stats: Option<u16>,
+    /// Keep the live stats as one line of key=value pairs in the file or named
+    /// pipe at PATH, e.g. for a status bar
+    #[arg(long, value_name = "PATH")]
+    pub stats_file: Option<String>,
+    /// After quitting, print each result of the session to stdout as a line
+    /// of JSON, as saved in results.jsonl
+    #[arg(long)]
+    pub json: bool,
+    /// Write the last test's text, keystrokes, options and stats to PATH, to
+    /// be checked with `ttt verify-bundle`
+    #[arg(long, value_name = "PATH")]
+    pub bundle: Option<String>,
+}
+
+// The text put between generated words; None is a space.
+#[derive(Clone, Copy)]
+pub struct Separator(pub Option<char>);
+
+fn parse_separator(value: &str) -> Result<Separator, String> {
+    let mut chars = value.chars();
+
+    match (value, chars.next(), chars.next()) {
+        ("space", _, _) => Ok(Separator(None)),
+        ("newline", _, _) => Ok(Separator(Some('\n'))),
+        (_, Some(c), None) => Ok(Separator(Some(c))),
+        _ => Err("expected space, newline or a single character".to_string()),
+    }
+}
+
+#[derive(Clone)]
+pub struct CharsetArg(pub Vec<char>);
+
+fn parse_charset_arg(value: &str) -> Result<CharsetArg, String> {
+    let chars = parse_charset(value);
+    if chars.is_empty() {
+        return Err("the character set is empty".to_string());
+    }
+
+    Ok(CharsetArg(chars))
+}
+
+// `START:END`, `START:` or `:END`, 1-based and inclusive.
+fn parse_line_range(value: &str) -> Result<(usize, usize), String> {
+    let invalid = || "expected START:END, START: or :END".to_string();
+    let (start, end) = value.split_once(':').ok_or_else(invalid)?;
+
+    let start = match start.trim() {
+        "" => 1,
+        s => s.parse().map_err(|_| invalid())?,
+    };
+    let end = match end.trim() {
+        "" => usize::MAX - 1,
+        s => s.parse().map_err(|_| invalid())?,
+    };
+
+    if start < 1 || start > end {
+        return Err("START has to be at least 1 and no more than END".to_string());
+    }
+
+    // The lines are counted up to END + 1, which has to fit.
+    if end == usize::MAX {
+        return Err(format!("END has to be less than {}", usize::MAX));
+    }
+
+    Ok((start, end))
+}
+
+fn positive_wpm(value: &str) -> Result<f64, String> {
+    value
+        .parse::<f64>()
+        .ok()
+        .filter(|wpm| *wpm > 0.0)
+        .ok_or_else(|| "expected a speed above 0".to_string())
+}
+
+// Test options on their own, as on a line of a playlist or in a lesson.
+#[derive(Parser)]
+#[command(name = "ttt", no_binary_name = true)]
+struct TestLine {
+    #[command(flatten)]
+    test: TestOptions,
+}
+
+pub fn parse_cli() -> Cli {
+    Cli::parse_from(with_double_dashes(env::args_os()))
+}
+
+pub fn parse_test_line(args: impl IntoIterator<Item = String>) -> TestOptions {
+    TestLine::parse_from(with_double_dashes(args)).test
+}
+
+// Options were long written with a single dash (-seconds 30), which clap
+// doesn't read, so those are turned into their double-dash form first.
+fn with_double_dashes<T: Into<OsString>>(args: impl IntoIterator<Item = T>) -> Vec<OsString> {
+    let mut names = HashSet::new();
+    collect_long_names(&Cli::command(), &mut names);
+
+    args.into_iter()
+        .map(|arg| {
+            let arg: OsString = arg.into();
+            let long = arg
+                .to_str()
+                .and_then(|s| s.strip_prefix('-'))
+                .filter(|name| !name.starts_with('-'))
+                .filter(|name| {
+                    let name = name.split_once('=').map_or(*name, |(name, _)| name);
+                    name.len() > 1 && names.contains(name)
+                });
+
+            match long {
+                Some(name) => format!("--{}", name).into(),
+                None => arg,
+            }
+        })
+        .collect()
+}
+
+fn collect_long_names(command: &clap::Command, names: &mut HashSet<String>) {
+    for arg in command.get_arguments() {
+        if let Some(long) = arg.get_long() {
+            names.insert(long.to_string());
+        }
+    }
+
+    for subcommand in command.get_subcommands() {
+        collect_long_names(subcommand, names);
+    }
+}
+
+// For problems clap can't see by itself, such as options that only make
+// sense for some sources of text.
+pub fn usage_error(message: impl fmt::Display) -> ! {
+    Cli::command()
+        .error(ErrorKind::ArgumentConflict, message)
+        .exit()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn line_range_forms() {
+        assert_eq!(parse_line_range("3:7"), Ok((3, 7)));
+        assert_eq!(parse_line_range("3:"), Ok((3, usize::MAX - 1)));
+        assert_eq!(parse_line_range(":7"), Ok((1, 7)));
+        assert!(parse_line_range("7:3").is_err());
+        assert!(parse_line_range("0:3").is_err());
+    }
+
+    // Taking END + 1 - START lines mustn't overflow.
+    #[test]
+    fn line_range_end_fits() {
+        let max = usize::MAX.to_string();
+
+        assert!(parse_line_range(&format!("1:{}", max)).is_err());
+        assert!(parse_line_range(&format!("1:{}", usize::MAX - 1)).is_ok());
+    }
+}
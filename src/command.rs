@@ -0,0 +1,101 @@
+use crate::helpers::LANGUAGES;
+
+// What can be typed at the `:` prompt between tests.
+pub enum Command {
+    Restart,
+    Retry,
+    Quit,
+    Time(usize),
+    Words(usize),
+    // None for the default word list.
+    Language(Option<String>),
+    // None toggles.
+    Punctuation(Option<bool>),
+    Numbers(Option<bool>),
+    Typed(Option<bool>),
+    Focus(Option<bool>),
+    Practice,
+    Settings,
+    History,
+    Help,
+}
+
+pub const COMMAND_HELP: &str = "restart, retry, quit, time SECONDS, words COUNT, \
+                                language NAME, punctuation [on|off], numbers [on|off], \
+                                typed [on|off], focus [on|off], practice, settings, \
+                                history";
+
+pub fn parse_command(line: &str) -> Result<Command, String> {
+    let mut words = line.split_whitespace();
+    let Some(name) = words.next() else {
+        return Err("No command given".to_string());
+    };
+    let arg = words.next();
+
+    if let Some(extra) = words.next() {
+        return Err(format!("Unexpected argument: {}", extra));
+    }
+
+    let number = |what: &str| -> Result<usize, String> {
+        let arg = arg.ok_or_else(|| format!("{} needs a number", what))?;
+
+        arg.parse::<usize>()
+            .ok()
+            .filter(|n| *n > 0 || what == "words")
+            .ok_or_else(|| format!("Invalid number for {}: {}", what, arg))
+    };
+
+    let switch = || -> Result<Option<bool>, String> {
+        match arg {
+            None => Ok(None),
+            Some("on" | "true") => Ok(Some(true)),
+            Some("off" | "false") => Ok(Some(false)),
+            Some(other) => Err(format!("Expected on or off, not {}", other)),
+        }
+    };
+
+    let command = match name {
+        "r" | "restart" => Command::Restart,
+        "retry" => Command::Retry,
+        "q" | "quit" => Command::Quit,
+        "t" | "time" => Command::Time(number("time")?),
+        "w" | "words" | "count" => Command::Words(number("words")?),
+        "l" | "lang" | "language" => match arg {
+            None | Some("default") => Command::Language(None),
+            Some(name) if LANGUAGES.contains(&name) => Command::Language(Some(name.to_string())),
+            Some(name) => {
+                return Err(format!(
+                    "Unknown language: {} (available: {})",
+                    name,
+                    LANGUAGES.join(", ")
+                ));
+            }
+        },
+        "p" | "punctuation" => Command::Punctuation(switch()?),
+        "n" | "numbers" => Command::Numbers(switch()?),
+        "typed" => Command::Typed(switch()?),
+        "focus" => Command::Focus(switch()?),
+        "practice" => Command::Practice,
+        "settings" => Command::Settings,
+        "history" => Command::History,
+        "h" | "help" => Command::Help,
+        other => return Err(format!("Unknown command: {}", other)),
+    };
+
+    if arg.is_some()
+        && matches!(
+            command,
+            Command::Restart
+                | Command::Retry
+                | Command::Quit
+                | Command::Practice
+                | Command::Settings
+                | Command::History
+                | Command::Help
+        )
+    {
+        return Err(format!("{} takes no argument", name));
+    }
+
+    Ok(command)
+}
@@ -0,0 +1,428 @@
+use crate::{
+    cli::ConfigCommand,
+    fingers::Finger,
+    types::{Action, PlaybackAction, ReviewAction},
+};
+
+use serde::Deserialize;
+use std::{collections::HashMap, env, fs, io, num::NonZeroUsize, path::PathBuf, process};
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CompletionSignal {
+    #[default]
+    None,
+    Bell,
+    Flash,
+}
+
+// `rolling` shows only the lines before, at and after the caret, `stack`
+// one word per line.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Viewport {
+    #[default]
+    Full,
+    Rolling,
+    Stack,
+}
+
+// `default` leaves the caret as the terminal draws it.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CaretShape {
+    #[default]
+    Default,
+    Block,
+    Underline,
+    Bar,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TextAlign {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+// Results left out of averages and bests by `ttt stats`. Every rule is off
+// unless set.
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutlierRules {
+    pub min_seconds: Option<f64>,
+    pub min_accuracy: Option<f64>,
+    pub max_wpm: Option<f64>,
+    pub max_deviations: Option<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub leader: String,
+    pub show_tour: bool,
+    pub pace_floor: Option<f64>,
+    pub pace_ceiling: Option<f64>,
+    pub pace_alert_seconds: f64,
+    pub completion_signal: CompletionSignal,
+    pub tab_width: NonZeroUsize,
+    pub hard_tabs: bool,
+    pub viewport: Viewport,
+    pub text_align: TextAlign,
+    pub first_line_indent: usize,
+    pub max_width: u16,
+    pub overlay: bool,
+    pub typed_panel: bool,
+    pub focus: bool,
+    pub context_lines: usize,
+    pub center_line: bool,
+    pub progress: bool,
+    pub caret: CaretShape,
+    pub caret_blink: bool,
+    pub space_glyph: char,
+    pub countdown: u64,
+    pub ghost: bool,
+    pub record_replays: bool,
+    pub save_interrupted: bool,
+    pub confirm_quit: bool,
+    pub mouse: bool,
+    pub afk_seconds: u64,
+    pub outliers: OutlierRules,
+    pub fingers: HashMap<Finger, String>,
+    // Replaces the default keys of each action listed.
+    pub keys: HashMap<Action, Vec<String>>,
+    pub review_keys: HashMap<ReviewAction, Vec<String>>,
+    pub playback_keys: HashMap<PlaybackAction, Vec<String>>,
+    pub relay: Option<String>,
+    // Defaults for tests without these options on the command line.
+    pub time: usize,
+    pub count: usize,
+    pub dict: Option<String>,
+    pub language: Option<String>,
+    pub punctuation: bool,
+    pub numbers: bool,
+    #[cfg(feature = "tts")]
+    pub speak_results: bool,
+    #[cfg(feature = "tts")]
+    pub tts_command: String,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            leader: "ctrl+g".to_string(),
+            show_tour: true,
+            pace_floor: None,
+            pace_ceiling: None,
+            pace_alert_seconds: 3.0,
+            completion_signal: CompletionSignal::None,
+            tab_width: NonZeroUsize::new(4).unwrap(),
+            hard_tabs: false,
+            viewport: Viewport::Full,
+            text_align: TextAlign::Left,
+            first_line_indent: 0,
+            max_width: 0,
+            overlay: false,
+            typed_panel: true,
+            focus: false,
+            context_lines: 1,
+            center_line: false,
+            progress: false,
+            caret: CaretShape::Default,
+            caret_blink: true,
+            space_glyph: ' ',
+            countdown: 0,
+            ghost: true,
+            record_replays: true,
+            save_interrupted: true,
+            confirm_quit: true,
+            mouse: false,
+            afk_seconds: 0,
+            outliers: OutlierRules::default(),
+            fingers: HashMap::new(),
+            keys: HashMap::new(),
+            review_keys: HashMap::new(),
+            playback_keys: HashMap::new(),
+            relay: None,
+            time: 0,
+            count: 0,
+            dict: None,
+            language: None,
+            punctuation: false,
+            numbers: false,
+            #[cfg(feature = "tts")]
+            speak_results: false,
+            #[cfg(feature = "tts")]
+            tts_command: "espeak".to_string(),
+        }
+    }
+}
+
+pub fn config_dir() -> Option<PathBuf> {
+    env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .map(|dir| dir.join("ttt"))
+}
+
+pub fn config_path() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join("config.toml"))
+}
+
+pub fn load_config() -> Config {
+    let path = config_path();
+
+    let content = match path.as_ref().map(fs::read_to_string) {
+        Some(Ok(content)) => content,
+        Some(Err(e)) if e.kind() != io::ErrorKind::NotFound => {
+            eprintln!(
+                "Failed to read config file at {}: {}",
+                path.unwrap_or_default().display(),
+                e
+            );
+
+            process::exit(1);
+        }
+        _ => String::new(),
+    };
+    let shown = path.unwrap_or_default().display().to_string();
+
+    let mut table: toml::Table = toml::from_str(&content).unwrap_or_else(|e| {
+        eprintln!("Failed to parse config file at {}: {}", shown, e);
+
+        process::exit(1);
+    });
+    let overrides = apply_env_overrides(&mut table);
+
+    Config::deserialize(table).unwrap_or_else(|e| {
+        if overrides.is_empty() {
+            eprintln!("Failed to parse config file at {}: {}", shown, e);
+        } else {
+            eprintln!(
+                "Failed to parse config file at {} with {} set: {}",
+                shown,
+                overrides.join(", "),
+                e
+            );
+        }
+
+        process::exit(1);
+    })
+}
+
+#[derive(Clone, Copy)]
+enum EnvKind {
+    Text,
+    Number,
+    Switch,
+}
+
+// The settings TTT_<KEY> can override, by the type they take. Text is used as
+// it is, so TTT_LEADER=1 is the key 1 and not a number.
+const ENV_KEYS: &[(&str, EnvKind)] = &[
+    ("leader", EnvKind::Text),
+    ("show_tour", EnvKind::Switch),
+    ("pace_floor", EnvKind::Number),
+    ("pace_ceiling", EnvKind::Number),
+    ("pace_alert_seconds", EnvKind::Number),
+    ("completion_signal", EnvKind::Text),
+    ("tab_width", EnvKind::Number),
+    ("hard_tabs", EnvKind::Switch),
+    ("viewport", EnvKind::Text),
+    ("text_align", EnvKind::Text),
+    ("first_line_indent", EnvKind::Number),
+    ("max_width", EnvKind::Number),
+    ("overlay", EnvKind::Switch),
+    ("typed_panel", EnvKind::Switch),
+    ("focus", EnvKind::Switch),
+    ("context_lines", EnvKind::Number),
+    ("center_line", EnvKind::Switch),
+    ("progress", EnvKind::Switch),
+    ("caret", EnvKind::Text),
+    ("caret_blink", EnvKind::Switch),
+    ("space_glyph", EnvKind::Text),
+    ("countdown", EnvKind::Number),
+    ("ghost", EnvKind::Switch),
+    ("record_replays", EnvKind::Switch),
+    ("save_interrupted", EnvKind::Switch),
+    ("confirm_quit", EnvKind::Switch),
+    ("mouse", EnvKind::Switch),
+    ("afk_seconds", EnvKind::Number),
+    ("relay", EnvKind::Text),
+    ("time", EnvKind::Number),
+    ("count", EnvKind::Number),
+    ("dict", EnvKind::Text),
+    ("language", EnvKind::Text),
+    ("punctuation", EnvKind::Switch),
+    ("numbers", EnvKind::Switch),
+    ("speak_results", EnvKind::Switch),
+    ("tts_command", EnvKind::Text),
+];
+
+// TTT_<KEY> takes the place of a top-level key of the config file, e.g.
+// TTT_VIEWPORT=rolling. A value that doesn't fit its setting is left out with
+// a warning. Returns the names of the variables used.
+fn apply_env_overrides(table: &mut toml::Table) -> Vec<String> {
+    let mut names = Vec::new();
+
+    for &(key, kind) in ENV_KEYS {
+        let name = format!("TTT_{}", key.to_uppercase());
+        let Ok(text) = env::var(&name) else {
+            continue;
+        };
+        if text.is_empty() {
+            continue;
+        }
+
+        let value = match kind {
+            EnvKind::Text => Ok(toml::Value::String(text.clone())),
+            EnvKind::Number => text
+                .parse::<i64>()
+                .map(toml::Value::Integer)
+                .or_else(|_| text.parse::<f64>().map(toml::Value::Float))
+                .map_err(|_| "expected a number".to_string()),
+            EnvKind::Switch => match text.to_lowercase().as_str() {
+                "true" | "on" | "yes" | "1" => Ok(toml::Value::Boolean(true)),
+                "false" | "off" | "no" | "0" => Ok(toml::Value::Boolean(false)),
+                _ => Err("expected true or false".to_string()),
+            },
+        };
+
+        // Checked on its own, so the warning is about this variable alone.
+        let checked = value.and_then(|value| {
+            let single = toml::Table::from_iter([(key.to_string(), value.clone())]);
+            Config::deserialize(single)
+                .map(|_| value)
+                .map_err(|e| e.message().to_string())
+        });
+
+        match checked {
+            Ok(value) => {
+                table.insert(key.to_string(), value);
+                names.push(name);
+            }
+            Err(e) => eprintln!("Ignoring {}={}: {}", name, text, e),
+        }
+    }
+
+    names
+}
+
+// A TOML value as given on the command line, where bare words are taken as
+// strings so `rolling` works without shell-quoting the TOML quotes.
+fn parse_value(value: &str) -> toml::Value {
+    format!("v = {}", value)
+        .parse::<toml::Table>()
+        .ok()
+        .and_then(|mut table| table.remove("v"))
+        .unwrap_or_else(|| toml::Value::String(value.to_string()))
+}
+
+// Sets a top-level `key = value` in the config file, keeping the rest of the
+// file (comments included) as is.
+pub fn set_config_value(key: &str, value: &str) -> io::Result<()> {
+    let Some(path) = config_path() else {
+        return Ok(());
+    };
+
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e),
+    };
+
+    let entry = format!("{} = {}", key, value);
+    let mut replaced = false;
+    let mut in_table = false;
+    let mut lines: Vec<String> = Vec::new();
+
+    for line in content.lines() {
+        in_table |= line.trim_start().starts_with('[');
+
+        let is_key = line.split_once('=').is_some_and(|(k, _)| k.trim() == key);
+
+        if is_key && !in_table && !replaced {
+            lines.push(entry.clone());
+            replaced = true;
+        } else {
+            lines.push(line.to_string());
+        }
+    }
+
+    // Top-level keys have to come before any [table] header.
+    if !replaced {
+        lines.insert(0, entry);
+    }
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    fs::write(path, lines.join("\n") + "\n")
+}
+
+pub fn run_config(command: ConfigCommand) {
+    let Some(path) = config_path() else {
+        eprintln!("Couldn't find the config directory: neither XDG_CONFIG_HOME nor HOME is set");
+
+        process::exit(1);
+    };
+
+    match command {
+        ConfigCommand::Path => println!("{}", path.display()),
+        ConfigCommand::Get { key } => {
+            let content = fs::read_to_string(&path).unwrap_or_default();
+            let table: toml::Table = toml::from_str(&content).unwrap_or_else(|e| {
+                eprintln!("Failed to parse config file at {}: {}", path.display(), e);
+
+                process::exit(1);
+            });
+
+            // Dotted keys reach into tables, e.g. outliers.max_wpm.
+            let mut parts = key.split('.');
+            let mut value = parts.next().and_then(|part| table.get(part));
+            for part in parts {
+                value = value.and_then(|v| v.get(part));
+            }
+
+            match value {
+                Some(toml::Value::String(s)) => println!("{}", s),
+                Some(value) => println!("{}", value),
+                None => {
+                    eprintln!("{} is not set", key);
+
+                    process::exit(1);
+                }
+            }
+        }
+        ConfigCommand::Set { key, value } => {
+            let value = parse_value(&value).to_string();
+
+            let previous = fs::read_to_string(&path).ok();
+            let result = set_config_value(&key, &value).and_then(|_| fs::read_to_string(&path));
+
+            match result {
+                Ok(content) => {
+                    if let Err(e) = toml::from_str::<Config>(&content) {
+                        eprintln!("Invalid value for {}: {}", key, e.message());
+
+                        let _ = match previous {
+                            Some(previous) => fs::write(&path, previous),
+                            None => fs::remove_file(&path),
+                        };
+
+                        process::exit(1);
+                    }
+                }
+                Err(e) => {
+                    eprintln!("Failed to write config file at {}: {}", path.display(), e);
+
+                    process::exit(1);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,102 @@
+use crate::{history::ErrorHistory, types::DrillKind};
+
+use rand::{Rng, seq::IndexedRandom};
+use std::iter;
+
+pub const DRILL_TOKEN_COUNT: usize = 60;
+
+const RUN_LENGTH: usize = 5;
+const CHARSET_MIN_WORD_LEN: usize = 2;
+const CHARSET_MAX_WORD_LEN: usize = 6;
+const WEAKEST_POOL: usize = 10;
+
+// Most common English letter bigrams and trigrams, most frequent first.
+const BIGRAMS: &[&str] = &[
+    "th", "he", "in", "er", "an", "re", "on", "at", "en", "nd", "ti", "es", "or", "te", "of", "ed",
+    "is", "it", "al", "ar", "st", "to", "nt", "ng", "se", "ha", "as", "ou", "io", "le", "ve", "co",
+    "me", "de", "hi", "ri", "ro", "ic", "ne", "ea", "ra", "ce", "li", "ch", "ll", "be", "ma", "si",
+    "om", "ur",
+];
+
+const TRIGRAMS: &[&str] = &[
+    "the", "and", "ing", "ion", "tio", "ent", "ati", "for", "her", "ter", "hat", "tha", "ere",
+    "ate", "his", "con", "res", "ver", "all", "ons", "nce", "men", "ith", "ted", "ers", "pro",
+    "thi", "wit", "are", "ess", "not", "ive", "was", "ect", "rea", "com", "eve", "per", "int",
+    "est", "sta", "cti", "ica", "ist", "ear", "ain", "one", "our", "iti", "rat",
+];
+
+pub const CHARSET_PRESETS: &[(&str, &str)] = &[
+    ("homerow", "asdfghjkl;"),
+    ("toprow", "qwertyuiop"),
+    ("bottomrow", "zxcvbnm,./"),
+    ("numbers", "1234567890"),
+    ("symbols", "!@#$%^&*()-_=+[]{};:'\",.<>/?\\|`~"),
+];
+
+// Accepts either a preset name or the literal characters to drill.
+pub fn parse_charset(value: &str) -> Vec<char> {
+    let chars = CHARSET_PRESETS
+        .iter()
+        .find(|(name, _)| *name == value)
+        .map(|(_, chars)| *chars)
+        .unwrap_or(value);
+
+    let mut out: Vec<char> = Vec::new();
+    for c in chars.chars().filter(|c| !c.is_whitespace()) {
+        if !out.contains(&c) {
+            out.push(c);
+        }
+    }
+
+    out
+}
+
+pub fn charset_word<R: Rng + ?Sized>(charset: &[char], rng: &mut R) -> String {
+    let len = rng.random_range(CHARSET_MIN_WORD_LEN..=CHARSET_MAX_WORD_LEN);
+
+    (0..len).filter_map(|_| charset.choose(rng)).collect()
+}
+
+pub fn generate_charset_drill(charset: &[char], count: usize, rng: &mut impl Rng) -> String {
+    (0..count)
+        .map(|_| charset_word(charset, rng))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+// Builds runs of repeated n-grams. With error history the n-grams are drawn
+// from the ones containing the most error-prone characters, otherwise from
+// the most common ones.
+pub fn generate_drill(
+    kind: DrillKind,
+    count: usize,
+    history: Option<&ErrorHistory>,
+    rng: &mut impl Rng,
+) -> String {
+    let grams = match kind {
+        DrillKind::Bigram => BIGRAMS,
+        DrillKind::Trigram => TRIGRAMS,
+    };
+
+    let pool: Vec<&str> = match history {
+        Some(history) => {
+            let mut scored: Vec<(&str, f64)> = grams
+                .iter()
+                .map(|g| (*g, g.chars().map(|c| history.char_error_rate(c)).sum()))
+                .collect();
+            scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+            scored.iter().take(WEAKEST_POOL).map(|(g, _)| *g).collect()
+        }
+        None => grams.to_vec(),
+    };
+
+    let mut tokens: Vec<&str> = Vec::with_capacity(count);
+    while tokens.len() < count {
+        let gram = pool.choose(rng).copied().unwrap_or("th");
+        let run = RUN_LENGTH.min(count - tokens.len());
+        tokens.extend(iter::repeat_n(gram, run));
+    }
+
+    tokens.join(" ")
+}
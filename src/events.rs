@@ -0,0 +1,78 @@
+use crate::types::{KeystrokeEvent, TestResult};
+
+use serde::Serialize;
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Write},
+};
+
+pub enum EventTarget {
+    Fd(i32),
+    File(String),
+}
+
+// Newline-delimited JSON, one object per event, tagged by `event`.
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event<'a> {
+    TestStart {
+        timestamp: u64,
+        source: &'a str,
+        length: usize,
+    },
+    Keystroke(KeystrokeEvent),
+    TestEnd {
+        timestamp: u64,
+        #[serde(flatten)]
+        result: TestResult,
+    },
+}
+
+pub struct EventSink {
+    out: File,
+}
+
+impl EventSink {
+    pub fn open(target: &EventTarget) -> io::Result<Self> {
+        let out = match target {
+            EventTarget::Fd(fd) => open_fd(*fd)?,
+            EventTarget::File(path) => OpenOptions::new().create(true).append(true).open(path)?,
+        };
+
+        Ok(Self { out })
+    }
+
+    // Each event is written and flushed on its own so readers see it right
+    // away; a reader going away must not disturb the test.
+    pub fn emit(&mut self, event: &Event) {
+        if let Ok(mut line) = serde_json::to_string(event) {
+            line.push('\n');
+            let _ = self.out.write_all(line.as_bytes());
+            let _ = self.out.flush();
+        }
+    }
+}
+
+#[cfg(unix)]
+fn open_fd(fd: i32) -> io::Result<File> {
+    use std::os::fd::FromRawFd;
+
+    if fd <= 2 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "stdin, stdout and stderr are used by the terminal",
+        ));
+    }
+
+    // SAFETY: the descriptor was handed to us on the command line for this
+    // purpose and nothing else in the process uses it.
+    Ok(unsafe { File::from_raw_fd(fd) })
+}
+
+#[cfg(not(unix))]
+fn open_fd(_fd: i32) -> io::Result<File> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "file descriptors are only supported on Unix",
+    ))
+}
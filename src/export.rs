@@ -0,0 +1,121 @@
+use crate::{
+    cli::HistoryCommand,
+    importers::{ImportFormat, import_file},
+    results::{ResultRecord, load_results},
+};
+
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    process,
+};
+
+// Never reordered or renamed, only added to at the end, so sheets and
+// scripts built on an export keep working with the next one.
+const CSV_COLUMNS: &[&str] = &[
+    "timestamp",
+    "date",
+    "wpm",
+    "accuracy",
+    "seconds",
+    "source",
+    "practice",
+    "incomplete",
+    "failed",
+    "replay",
+];
+
+pub fn run_history(command: HistoryCommand) {
+    match command {
+        HistoryCommand::Export { csv } => run_export(&csv),
+        HistoryCommand::Import { monkeytype } => import_file(ImportFormat::Monkeytype, &monkeytype),
+    }
+}
+
+fn run_export(path: &str) {
+    let records = load_results().unwrap_or_else(|e| {
+        eprintln!("Failed to read results: {}", e);
+
+        process::exit(1);
+    });
+
+    let written = if path == "-" {
+        write_csv(&mut io::stdout().lock(), &records)
+    } else {
+        File::create(path).and_then(|file| write_csv(&mut BufWriter::new(file), &records))
+    };
+
+    // Piping into `head` and the like is fine.
+    if let Err(e) = written
+        && e.kind() != io::ErrorKind::BrokenPipe
+    {
+        eprintln!("Failed to write {}: {}", path, e);
+
+        process::exit(1);
+    }
+
+    if path != "-" {
+        println!("Exported {} results to {}", records.len(), path);
+    }
+}
+
+fn write_csv(out: &mut impl Write, records: &[ResultRecord]) -> io::Result<()> {
+    writeln!(out, "{}", CSV_COLUMNS.join(","))?;
+
+    let number = |value: Option<f64>| value.map(|v| format!("{:.2}", v)).unwrap_or_default();
+
+    for record in records {
+        let fields = [
+            record.timestamp.to_string(),
+            format_date(record.timestamp),
+            format!("{:.2}", record.wpm),
+            number(record.accuracy),
+            number(record.seconds),
+            csv_field(&record.source),
+            record.practice.to_string(),
+            record.incomplete.to_string(),
+            record.failed.to_string(),
+            csv_field(record.replay.as_deref().unwrap_or_default()),
+        ];
+
+        writeln!(out, "{}", fields.join(","))?;
+    }
+
+    out.flush()
+}
+
+// Quoted only when it has to be.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+// ISO 8601 in UTC, e.g. 2024-03-09T14:05:00Z.
+pub fn format_date(timestamp: u64) -> String {
+    let days = (timestamp / 86_400) as i64;
+    let seconds = timestamp % 86_400;
+
+    // Howard Hinnant's civil_from_days.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
@@ -0,0 +1,62 @@
+use std::{
+    io::{self, Read},
+    process::{Command, Stdio},
+};
+
+// Plenty for a book from Project Gutenberg, small enough to keep in memory.
+pub const MAX_FETCH_BYTES: u64 = 4 * 1024 * 1024;
+const FETCH_TIMEOUT_SECONDS: u32 = 30;
+
+// Downloads `url` with curl, so ttt needs no TLS stack of its own. Anything
+// past `MAX_FETCH_BYTES` is an error rather than silently cut off.
+pub fn fetch_text(url: &str) -> io::Result<String> {
+    if !url.starts_with("http://") && !url.starts_with("https://") {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "only http and https URLs are supported",
+        ));
+    }
+
+    let mut child = Command::new("curl")
+        .args(["--fail", "--silent", "--show-error", "--location"])
+        .arg("--max-time")
+        .arg(FETCH_TIMEOUT_SECONDS.to_string())
+        .arg("--max-filesize")
+        .arg(MAX_FETCH_BYTES.to_string())
+        .arg("--")
+        .arg(url)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| io::Error::new(e.kind(), format!("failed to run curl: {}", e)))?;
+
+    let mut bytes = Vec::new();
+    if let Some(stdout) = child.stdout.take() {
+        stdout.take(MAX_FETCH_BYTES + 1).read_to_end(&mut bytes)?;
+    }
+
+    if bytes.len() as u64 > MAX_FETCH_BYTES {
+        let _ = child.kill();
+        let _ = child.wait();
+
+        return Err(io::Error::other(format!(
+            "larger than {} MiB",
+            MAX_FETCH_BYTES / 1024 / 1024
+        )));
+    }
+
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        let message = String::from_utf8_lossy(&output.stderr);
+
+        return Err(io::Error::other(
+            message.trim().trim_start_matches("curl: ").to_string(),
+        ));
+    }
+
+    let text = String::from_utf8(bytes)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "not UTF-8 text"))?;
+
+    Ok(text.trim_start_matches('\u{feff}').replace("\r\n", "\n"))
+}
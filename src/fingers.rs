@@ -0,0 +1,184 @@
+use crate::types::KeystrokeEvent;
+
+use clap::ValueEnum;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Hand {
+    Left,
+    Right,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum Finger {
+    LeftPinky,
+    LeftRing,
+    LeftMiddle,
+    LeftIndex,
+    Thumb,
+    RightIndex,
+    RightMiddle,
+    RightRing,
+    RightPinky,
+}
+
+impl Finger {
+    pub fn hand(self) -> Option<Hand> {
+        match self {
+            Finger::LeftPinky | Finger::LeftRing | Finger::LeftMiddle | Finger::LeftIndex => {
+                Some(Hand::Left)
+            }
+            Finger::Thumb => None,
+            _ => Some(Hand::Right),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Finger::LeftPinky => "left pinky",
+            Finger::LeftRing => "left ring",
+            Finger::LeftMiddle => "left middle",
+            Finger::LeftIndex => "left index",
+            Finger::Thumb => "thumb",
+            Finger::RightIndex => "right index",
+            Finger::RightMiddle => "right middle",
+            Finger::RightRing => "right ring",
+            Finger::RightPinky => "right pinky",
+        }
+    }
+}
+
+// Touch typing on a US QWERTY keyboard, shifted characters included.
+const QWERTY: &[(Finger, &str)] = &[
+    (Finger::LeftPinky, "`~1!qaz"),
+    (Finger::LeftRing, "2@wsx"),
+    (Finger::LeftMiddle, "3#edc"),
+    (Finger::LeftIndex, "4$5%rtfgvb"),
+    (Finger::Thumb, " "),
+    (Finger::RightIndex, "6^7&yuhjnm"),
+    (Finger::RightMiddle, "8*ik,<"),
+    (Finger::RightRing, "9(ol.>"),
+    (Finger::RightPinky, "0)-_=+p[{]}\\|;:'\"/?"),
+];
+
+// Which finger types each character. The `[fingers]` table in config.toml
+// replaces the QWERTY default, for other layouts or keyboards.
+pub struct FingerMap {
+    keys: HashMap<char, Finger>,
+}
+
+impl FingerMap {
+    pub fn new(config: &HashMap<Finger, String>) -> Self {
+        let mut keys = HashMap::new();
+
+        if config.is_empty() {
+            for (finger, chars) in QWERTY {
+                keys.extend(chars.chars().map(|c| (c, *finger)));
+            }
+        } else {
+            for (finger, chars) in config {
+                keys.extend(chars.chars().map(|c| (c, *finger)));
+            }
+            keys.insert(' ', Finger::Thumb);
+        }
+
+        Self { keys }
+    }
+
+    // Capitals are typed with the finger of their lowercase letter.
+    pub fn finger(&self, c: char) -> Option<Finger> {
+        self.keys
+            .get(&c)
+            .or_else(|| c.to_lowercase().next().and_then(|l| self.keys.get(&l)))
+            .copied()
+    }
+
+    pub fn hand(&self, c: char) -> Option<Hand> {
+        self.finger(c).and_then(Finger::hand)
+    }
+}
+
+// How many characters each hand types and how often consecutive characters
+// switch hands. Characters without a hand (spaces, unmapped symbols) are
+// skipped, so `the cat` alternates from `e` to `c`.
+#[derive(Debug, Default)]
+pub struct HandBalance {
+    pub left: usize,
+    pub right: usize,
+    pub alternations: usize,
+    pub transitions: usize,
+}
+
+impl HandBalance {
+    pub fn of_text(fingers: &FingerMap, text: &str) -> Self {
+        let mut balance = Self::default();
+        let mut previous: Option<Hand> = None;
+
+        for hand in text.chars().filter_map(|c| fingers.hand(c)) {
+            match hand {
+                Hand::Left => balance.left += 1,
+                Hand::Right => balance.right += 1,
+            }
+
+            if let Some(previous) = previous {
+                balance.transitions += 1;
+                if previous != hand {
+                    balance.alternations += 1;
+                }
+            }
+            previous = Some(hand);
+        }
+
+        balance
+    }
+
+    pub fn left_percent(&self) -> f64 {
+        percent(self.left, self.left + self.right)
+    }
+
+    pub fn right_percent(&self) -> f64 {
+        percent(self.right, self.left + self.right)
+    }
+
+    pub fn alternation_percent(&self) -> f64 {
+        percent(self.alternations, self.transitions)
+    }
+}
+
+fn percent(part: usize, total: usize) -> f64 {
+    if total > 0 {
+        part as f64 / total as f64 * 100.0
+    } else {
+        0.0
+    }
+}
+
+// WPM of each hand, from the time every correct keystroke took since the one
+// before it. `None` when a hand typed nothing.
+pub fn hand_speeds(fingers: &FingerMap, strokes: &[KeystrokeEvent]) -> (Option<f64>, Option<f64>) {
+    let mut left = (0usize, 0.0);
+    let mut right = (0usize, 0.0);
+
+    for pair in strokes.windows(2) {
+        let stroke = pair[1];
+        let Some(c) = stroke.key.filter(|_| stroke.correct) else {
+            continue;
+        };
+
+        let total = match fingers.hand(c) {
+            Some(Hand::Left) => &mut left,
+            Some(Hand::Right) => &mut right,
+            None => continue,
+        };
+        total.0 += 1;
+        total.1 += stroke.seconds - pair[0].seconds;
+    }
+
+    let wpm = |(count, seconds): (usize, f64)| {
+        (count > 0 && seconds > 0.0).then(|| (count as f64 / 5.0) / (seconds / 60.0))
+    };
+
+    (wpm(left), wpm(right))
+}
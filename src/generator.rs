@@ -0,0 +1,164 @@
+use crate::{
+    history::ErrorHistory,
+    provider::TextProvider,
+    types::{Capitalization, TextOptions},
+};
+
+use rand::Rng;
+
+const COMMA_CHANCE: f64 = 0.08;
+const PERIOD_CHANCE: f64 = 0.06;
+const QUESTION_CHANCE: f64 = 0.01;
+const EXCLAMATION_CHANCE: f64 = 0.01;
+const QUOTE_CHANCE: f64 = 0.03;
+const HYPHEN_CHANCE: f64 = 0.02;
+const NUMBER_CHANCE: f64 = 0.1;
+const ADAPTIVE_CANDIDATES: usize = 8;
+const ADAPTIVE_CHAR_BIAS: f64 = 40.0;
+const ADAPTIVE_WORD_BIAS: f64 = 2.0;
+
+pub fn generate_text(
+    dictionary: &dyn TextProvider,
+    count: usize,
+    options: &TextOptions,
+    history: Option<&ErrorHistory>,
+    rng: &mut impl Rng,
+) -> String {
+    let mut words: Vec<String> = (0..count)
+        .map(|_| match history {
+            Some(history) => adaptive_sample(dictionary, history, rng),
+            None => dictionary.sample(rng),
+        })
+        .collect();
+
+    if options.numbers {
+        for word in words.iter_mut() {
+            if rng.random_bool(NUMBER_CHANCE) {
+                *word = random_number(rng);
+            }
+        }
+    }
+
+    if options.punctuation {
+        words = punctuate(words, rng);
+    }
+
+    if let Some(mode) = options.capitalize {
+        capitalize(&mut words, mode, rng);
+    }
+
+    words.join(" ")
+}
+
+// Draws a few candidates and picks one with probability biased towards words
+// containing characters, or being words, that were mistyped before.
+fn adaptive_sample(
+    dictionary: &dyn TextProvider,
+    history: &ErrorHistory,
+    rng: &mut impl Rng,
+) -> String {
+    let candidates: Vec<(String, f64)> = (0..ADAPTIVE_CANDIDATES)
+        .map(|_| {
+            let word = dictionary.sample(rng);
+            let char_score: f64 = word.chars().map(|c| history.char_error_rate(c)).sum();
+            let weight = 1.0
+                + ADAPTIVE_CHAR_BIAS * char_score
+                + ADAPTIVE_WORD_BIAS * history.word_misses(&word) as f64;
+
+            (word, weight)
+        })
+        .collect();
+
+    let total: f64 = candidates.iter().map(|(_, w)| w).sum();
+    let mut roll = rng.random_range(0.0..total);
+
+    for (word, weight) in &candidates {
+        if roll < *weight {
+            return word.clone();
+        }
+        roll -= weight;
+    }
+
+    candidates[candidates.len() - 1].0.clone()
+}
+
+fn random_number(rng: &mut impl Rng) -> String {
+    match rng.random_range(0..4) {
+        0 => rng.random_range(1900..=2030).to_string(),
+        1 => format!(
+            "${}.{:02}",
+            rng.random_range(1..1000),
+            rng.random_range(0..100)
+        ),
+        2 => rng.random_range(100..100_000).to_string(),
+        _ => rng.random_range(0..100).to_string(),
+    }
+}
+
+fn punctuate(words: Vec<String>, rng: &mut impl Rng) -> Vec<String> {
+    let mut out: Vec<String> = Vec::with_capacity(words.len());
+    let mut words = words.into_iter().peekable();
+
+    while let Some(mut word) = words.next() {
+        if words.peek().is_some() && rng.random_bool(HYPHEN_CHANCE) {
+            word = format!("{}-{}", word, words.next().unwrap());
+        }
+
+        if rng.random_bool(QUOTE_CHANCE) {
+            word = format!("\"{}\"", word);
+        }
+
+        if words.peek().is_none() {
+            word.push('.');
+        } else {
+            let roll: f64 = rng.random();
+
+            if roll < COMMA_CHANCE {
+                word.push(',');
+            } else if roll < COMMA_CHANCE + PERIOD_CHANCE {
+                word.push('.');
+            } else if roll < COMMA_CHANCE + PERIOD_CHANCE + QUESTION_CHANCE {
+                word.push('?');
+            } else if roll < COMMA_CHANCE + PERIOD_CHANCE + QUESTION_CHANCE + EXCLAMATION_CHANCE {
+                word.push('!');
+            }
+        }
+
+        out.push(word);
+    }
+
+    out
+}
+
+fn capitalize(words: &mut [String], mode: Capitalization, rng: &mut impl Rng) {
+    let mut sentence_start = true;
+
+    for word in words.iter_mut() {
+        let random = match mode {
+            Capitalization::Sentences => false,
+            Capitalization::Percent(percent) => rng.random_range(0..100) < percent,
+        };
+
+        if sentence_start || random {
+            *word = capitalize_word(word);
+        }
+
+        sentence_start = word.ends_with(['.', '?', '!']);
+    }
+}
+
+fn capitalize_word(word: &str) -> String {
+    let mut out = String::with_capacity(word.len());
+    let mut done = false;
+
+    for ch in word.chars() {
+        if !done && ch.is_alphabetic() {
+            out.extend(ch.to_uppercase());
+            done = true;
+        } else {
+            out.push(ch);
+        }
+    }
+
+    out
+}
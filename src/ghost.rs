@@ -0,0 +1,111 @@
+use crate::{
+    helpers::fnv1a,
+    history::data_dir,
+    recording::{Recording, replays_dir},
+    replay::typed_at,
+    types::KeystrokeEvent,
+};
+
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    fs, io,
+    path::PathBuf,
+};
+
+// Only the most recently set ghosts are kept.
+pub const MAX_GHOSTS: usize = 100;
+
+// The best run on a text, by the file name of its recording.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct GhostEntry {
+    timestamp: u64,
+    wpm: f64,
+    replay: String,
+}
+
+// That run, replayed alongside later tests of the same text.
+pub struct Ghost {
+    pub wpm: f64,
+    keystrokes: Vec<KeystrokeEvent>,
+}
+
+impl Ghost {
+    // How far into the text the ghost was `seconds` into its run.
+    pub fn position(&self, seconds: f64) -> usize {
+        typed_at(&self.keystrokes, seconds).chars().count()
+    }
+}
+
+fn ghosts_path() -> Option<PathBuf> {
+    data_dir().map(|dir| dir.join("ghosts.json"))
+}
+
+// Keyed by a hash of the text, which can be long.
+fn text_key(text: &str) -> String {
+    format!("{:016x}", fnv1a(text.as_bytes()))
+}
+
+fn load_all_ghosts() -> HashMap<String, GhostEntry> {
+    ghosts_path()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+// The file names of the recordings ghosts are kept in.
+pub fn ghost_replays() -> HashSet<String> {
+    load_all_ghosts()
+        .into_values()
+        .map(|entry| entry.replay)
+        .collect()
+}
+
+// No ghost either if its recording has gone missing since.
+pub fn load_ghost(text: &str) -> Option<Ghost> {
+    let entry = load_all_ghosts().remove(&text_key(text))?;
+    let recording = Recording::read(&replays_dir()?.join(&entry.replay)).ok()?;
+
+    Some(Ghost {
+        wpm: entry.wpm,
+        keystrokes: recording.keystrokes,
+    })
+}
+
+// Keeps the run if it beats the ghost of its text, or the text has none yet.
+pub fn save_ghost(text: &str, timestamp: u64, wpm: f64, replay: &str) -> io::Result<()> {
+    let Some(path) = ghosts_path() else {
+        return Ok(());
+    };
+
+    let mut all = load_all_ghosts();
+    let key = text_key(text);
+    if all.get(&key).is_some_and(|best| best.wpm >= wpm) {
+        return Ok(());
+    }
+    all.insert(
+        key,
+        GhostEntry {
+            timestamp,
+            wpm,
+            replay: replay.to_string(),
+        },
+    );
+
+    while all.len() > MAX_GHOSTS {
+        let Some(oldest) = all
+            .iter()
+            .min_by_key(|(_, entry)| entry.timestamp)
+            .map(|(key, _)| key.clone())
+        else {
+            break;
+        };
+        all.remove(&oldest);
+    }
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    fs::write(path, serde_json::to_string(&all)?)
+}
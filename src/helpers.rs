@@ -1,106 +1,594 @@
-use crate::types::{Glyph, Layout, TextSource};
+use crate::{
+    bookmark::load_bookmark,
+    cache::{load_cached_words, store_cached_words},
+    cli::{CharsetArg, Separator, TestOptions, parse_test_line, usage_error},
+    config::{Config, TextAlign},
+    drill::{CHARSET_PRESETS, parse_charset},
+    events::EventTarget,
+    fetch::fetch_text,
+    fingers::{FingerMap, Hand},
+    lesson::{load_course, load_lesson},
+    markup::strip_markup,
+    provider::{CharGroups, MappedWords, MixedWords, TextProvider, WeightedWords},
+    training::{LetterModel, Training, load_training_progress},
+    types::{
+        Args, Capitalization, Glyph, Layout, Passage, Quote, QuoteLength, Repeat, Requirement,
+        TestResult, TextBookmark, TextOptions, TextSource,
+    },
+};
 
-use rand::Rng;
+use base64::{Engine, engine::general_purpose::STANDARD};
+use rand::{Rng, SeedableRng, rngs::StdRng, seq::SliceRandom};
 use ratatui::prelude::*;
-use std::{env, fs, process};
-
-pub fn print_usage_and_exit() -> ! {
-    eprintln!(
-        "Usage: ttt [-count COUNT] [-dict PATH] [-text PATH]
-
-Options:
-  -count   COUNT     Generate text using COUNT number of words
-  -seconds SECONDS   Time limit  in SECONDS
-  -text PATH         Use text from file at PATH
-  -dict PATH         Use dictionary file at PATH to generate a random text.
-By default, a random text using system dictionary is generated."
-    );
+use std::{cmp::Ordering, fs, iter, path::Path, process};
+
+// Words in a generated text that neither has a count nor streams, e.g. one
+// repeated with -repeat.
+const DEFAULT_WORD_COUNT: usize = 512;
+const DEFAULT_SECONDS: usize = 60;
+const QUOTES_JSON: &str = include_str!("../quotes.json");
+const SHORT_QUOTE_MAX_LEN: usize = 100;
+const MEDIUM_QUOTE_MAX_LEN: usize = 250;
+const MMAP_THRESHOLD_BYTES: u64 = 16 * 1024 * 1024;
+const DICT_FILTERS: &str = "trim,lowercase,non-empty";
+const SYSTEM_DICTIONARY: &str = "/usr/share/dict/words";
+const DEFAULT_LANGUAGE: &str = "english-1k";
+const CODE_EXTENSIONS: &[&str] = &[
+    "c", "cc", "cpp", "cs", "go", "h", "hpp", "hs", "java", "js", "jsx", "kt", "lua", "ml", "php",
+    "pl", "py", "rb", "rs", "scala", "sh", "sql", "swift", "ts", "tsx", "zig",
+];
+
+pub const LANGUAGES: &[&str] = &[
+    #[cfg(feature = "english-200")]
+    "english-200",
+    #[cfg(feature = "english-1k")]
+    "english-1k",
+    #[cfg(feature = "english-10k")]
+    "english-10k",
+];
 
-    process::exit(1);
+// A line of options in a playlist or lesson.
+pub fn parse_arg_list(args: impl Iterator<Item = String>, config: &Config) -> Args {
+    resolve_test_options(parse_test_line(args), config, false)
 }
 
-pub fn parse_usize_arg(arg: String, val: Option<String>) -> usize {
-    val.unwrap_or_else(|| {
-        eprintln!("Missing count after {}", arg);
+// Like parse_arg_list, but for options that come with their own text to type,
+// so no file or word list they might name is read.
+pub fn parse_arg_list_with_text(
+    args: impl Iterator<Item = String>,
+    text: String,
+    config: &Config,
+) -> Args {
+    resolve_options(parse_test_line(args), config, false, Some(text))
+}
 
-        print_usage_and_exit()
-    })
-    .parse::<usize>()
-    .unwrap()
+// Turns the test options given on the command line (or a line of a playlist)
+// into the test to run, reading the files and word lists they name. Only a
+// test run from the command line (`command_line`) can be a playlist, lesson
+// or course, and takes its defaults from the config.
+pub fn resolve_test_options(options: TestOptions, config: &Config, command_line: bool) -> Args {
+    resolve_options(options, config, command_line, None)
 }
 
-pub fn parse_args() -> (usize, usize, TextSource) {
-    let mut dict_path: Option<String> = None;
-    let mut text_path: Option<String> = None;
-    let mut count: usize = 0;
-    let mut seconds: usize = 0;
+fn resolve_options(
+    options: TestOptions,
+    config: &Config,
+    command_line: bool,
+    fixed: Option<String>,
+) -> Args {
+    let TestOptions {
+        count,
+        seconds,
+        text: text_paths,
+        url,
+        code,
+        lines: line_range,
+        normalize,
+        resume,
+        strip_markup: strip,
+        chunk,
+        sentences,
+        dict: mut dict_path,
+        mut language,
+        mix,
+        quote,
+        punctuation,
+        numbers,
+        separator,
+        capitalize,
+        adaptive,
+        drill,
+        charset,
+        blind,
+        repeat: repeat_times,
+        repeat_wpm,
+        min_wpm,
+        min_acc: min_accuracy,
+        until_wpm,
+        until_acc: until_accuracy,
+        require_wpm,
+        require_acc: require_accuracy,
+        seed,
+        hand,
+        train,
+        zen,
+        practice,
+        playlist: playlist_path,
+        lesson: lesson_path,
+        course: course_path,
+        events_fd,
+        events_file,
+        serve_stats,
+        stats_file,
+        json,
+        bundle,
+    } = options;
 
-    let mut args = env::args().skip(1);
+    let mut count = count.unwrap_or(0);
+    let mut seconds = seconds.unwrap_or(0);
+    let chunk = chunk.unwrap_or(0) as usize;
+    let repeat_times = repeat_times.unwrap_or(0);
+    let repeat_wpm = repeat_wpm.unwrap_or(0);
+    let charset = charset.map(|CharsetArg(chars)| chars);
+    let min_wpm = min_wpm.map(|wpm| wpm as f64);
+    let min_accuracy = min_accuracy.map(|pct| pct as f64);
+    let require_wpm = require_wpm.map(|wpm| wpm as f64);
+    let require_accuracy = require_accuracy.map(|pct| pct as f64);
+    let events = match (events_fd, events_file) {
+        (Some(fd), _) => Some(EventTarget::Fd(fd)),
+        (None, Some(path)) => Some(EventTarget::File(path)),
+        (None, None) => None,
+    };
 
-    while let Some(arg) = args.next() {
-        match arg.as_str() {
-            "-h" | "--help" => print_usage_and_exit(),
+    let mut text = TextOptions {
+        punctuation,
+        numbers,
+        adaptive,
+        separator: separator.and_then(|Separator(sep)| sep),
+        capitalize: capitalize.map(|percent| match percent {
+            Some(percent) => Capitalization::Percent(percent),
+            None => Capitalization::Sentences,
+        }),
+    };
 
-            "-c" | "-count" | "--count" => {
-                count = parse_usize_arg(arg, args.next());
-            }
+    if !command_line && (playlist_path.is_some() || lesson_path.is_some() || course_path.is_some())
+    {
+        eprintln!("Playlists and lessons can't include other playlists, lessons or courses");
 
-            "-s" | "-seconds" | "--seconds" => {
-                seconds = parse_usize_arg(arg, args.next());
-            }
+        process::exit(1);
+    }
+
+    let require = (require_wpm.is_some() || require_accuracy.is_some()).then(|| Requirement {
+        min_wpm: require_wpm.unwrap_or(0.0),
+        min_accuracy: require_accuracy.unwrap_or(0.0),
+    });
+
+    let minimum = (min_wpm.is_some() || min_accuracy.is_some()).then(|| Requirement {
+        min_wpm: min_wpm.unwrap_or(0.0),
+        min_accuracy: min_accuracy.unwrap_or(0.0),
+    });
+
+    let loaded = if let Some(path) = playlist_path {
+        Some(load_playlist(&path, config))
+    } else if let Some(path) = course_path {
+        Some(load_course(Path::new(&path), config))
+    } else {
+        lesson_path.map(|path| load_lesson(Path::new(&path), config))
+    };
+
+    if let Some(mut args) = loaded {
+        args.events = events;
+        args.serve_stats = serve_stats;
+        args.stats_file = stats_file;
+        args.json = json;
+        args.bundle = bundle;
+        args.practice = practice;
+        args.seed = seed;
+        args.require = require;
+        args.minimum = args.minimum.or(minimum);
+
+        return args;
+    }
+
+    // The config file (or TTT_* variables) stands in for options left off the
+    // command line, but not for those of playlists, lessons or races.
+    if command_line {
+        let picked_source = !text_paths.is_empty()
+            || url.is_some()
+            || dict_path.is_some()
+            || language.is_some()
+            || mix.is_some()
+            || quote.is_some()
+            || charset.is_some()
+            || drill.is_some();
+        if !picked_source {
+            language = config.language.clone().filter(|l| !l.is_empty());
+            dict_path = config.dict.clone().filter(|_| language.is_none());
+        }
+
+        if count == 0 && seconds == 0 && !zen {
+            count = config.count;
+            seconds = config.time;
+        }
+
+        text.punctuation |= config.punctuation;
+        text.numbers |= config.numbers;
+    }
+
+    let text_files = expand_text_paths(&text_paths);
+    let from_url = url.is_some();
+    let mut bookmark: Option<TextBookmark> = None;
+
+    // A -url is typed just like a single -text file.
+    let single_text = if let Some(url) = url {
+        let content = fetch_text(&url).unwrap_or_else(|e| {
+            eprintln!("Failed to fetch {}: {}", url, e);
+
+            process::exit(1);
+        });
+
+        Some((url, content))
+    } else if text_files.len() == 1 {
+        let content = read_text_file(&text_files[0]);
 
-            "-d" | "-dict" | "--dict" => {
-                dict_path = Some(args.next().unwrap_or_else(|| {
-                    eprintln!("Missing path after {}", arg);
+        Some((text_files[0].clone(), content))
+    } else {
+        None
+    };
+
+    let source = if let Some(text) = fixed {
+        TextSource::Fixed(text)
+    } else if text_files.len() > 1 {
+        if sentences || line_range.is_some() || chunk > 0 {
+            usage_error("-sentences, -lines and -chunk take a single -text file")
+        }
+
+        let mut passages: Vec<Passage> = text_files
+            .iter()
+            .map(|path| {
+                let content = read_text_file(path);
+                let content = if strip {
+                    strip_markup(&content)
+                } else {
+                    content
+                };
 
-                    print_usage_and_exit()
-                }));
+                let code = code || is_code_file(path);
+
+                Passage {
+                    text: prepare_text(&content, code, normalize),
+                    code,
+                }
+            })
+            .filter(|passage| !passage.text.is_empty())
+            .collect();
+
+        if passages.is_empty() {
+            eprintln!("All text files are empty");
+
+            process::exit(1);
+        }
+
+        match seed {
+            Some(seed) => passages.shuffle(&mut StdRng::seed_from_u64(seed)),
+            None => passages.shuffle(&mut rand::rng()),
+        }
+
+        TextSource::Passages(passages.into())
+    } else if let Some((path, content)) = single_text {
+        let content = match line_range {
+            Some((start, end)) => {
+                let lines: Vec<&str> = content
+                    .lines()
+                    .skip(start - 1)
+                    .take(end + 1 - start)
+                    .collect();
+
+                if lines.is_empty() {
+                    eprintln!("Text file at {} has fewer than {} lines", path, start);
+
+                    process::exit(1);
+                }
+
+                lines.join("\n")
             }
+            None => content,
+        };
+
+        let content = if strip {
+            strip_markup(&content)
+        } else {
+            content
+        };
+
+        let code = code || is_code_file(&path);
+
+        if chunk > 0 && (code || sentences) {
+            usage_error("-chunk can't be used with -code or -sentences")
+        }
 
-            "-t" | "-text" | "--text" => {
-                text_path = Some(args.next().unwrap_or_else(|| {
-                    eprintln!("Missing path after {}", arg);
+        if code {
+            TextSource::Code(normalize_code(&content))
+        } else if sentences {
+            let sentences = split_sentences(&content);
+            if sentences.is_empty() {
+                eprintln!("Text file at {} contains no sentences", path);
 
-                    print_usage_and_exit()
-                }));
+                process::exit(1);
             }
 
-            other => {
-                eprintln!("Unknown argument: {}", other);
+            TextSource::Sentences(sentences)
+        } else {
+            let text = prepare_text(&content, false, normalize);
+
+            // Only whole local files are bookmarked.
+            let text = if from_url || line_range.is_some() {
+                text
+            } else {
+                let file = fs::canonicalize(&path)
+                    .map(|p| p.display().to_string())
+                    .unwrap_or(path);
+
+                // Once the end is reached, the next -resume starts over.
+                let start = if resume { load_bookmark(&file) } else { 0 };
+                let start = if start < text.chars().count() {
+                    start
+                } else {
+                    0
+                };
+
+                bookmark = Some(TextBookmark { file, start });
 
-                print_usage_and_exit()
+                text.chars().skip(start).collect()
+            };
+
+            if chunk > 0 {
+                TextSource::Chunks(split_chunks(&text, chunk))
+            } else {
+                TextSource::Fixed(text)
             }
         }
-    }
+    } else if sentences {
+        usage_error("-sentences requires -text or -url")
+    } else if line_range.is_some() || chunk > 0 {
+        usage_error("-lines and -chunk require -text or -url")
+    } else if let Some(kind) = drill {
+        TextSource::Drill(kind)
+    } else if let Some(chars) = charset {
+        TextSource::Charset(chars)
+    } else if let Some(length) = quote {
+        let quotes = load_quotes(length);
+        if quotes.is_empty() {
+            usage_error("No quotes of that length")
+        }
 
-    if let Some(path) = text_path {
-        let content = fs::read_to_string(&path).unwrap_or_else(|e| {
-            eprintln!("Failed to read text file at {}: {}", path, e);
+        TextSource::Quote(quotes)
+    } else {
+        let dict = if let Some(spec) = mix {
+            load_mix(&spec)
+        } else if let Some(name) = language {
+            load_language(&name).unwrap_or_else(|e| usage_error(e))
+        } else {
+            let loaded = match dict_path {
+                Some(path) => load_dictionary(&path),
+                None => load_system_dictionary(),
+            };
+
+            loaded.unwrap_or_else(|e| {
+                eprintln!("{}", e);
+
+                process::exit(1);
+            })
+        };
+
+        let dict = match hand {
+            Some(hand) => one_hand_words(dict.as_ref(), hand, &FingerMap::new(&config.fingers)),
+            None => dict,
+        };
+
+        if dict.is_empty() {
+            eprintln!("Dictionary contains no words");
 
             process::exit(1);
-        });
+        }
 
-        let content = content.replace("\r\n", "\n");
+        if train {
+            TextSource::Training(Training {
+                model: LetterModel::build(dict.as_ref()),
+                progress: load_training_progress(),
+            })
+        } else {
+            TextSource::RandomWords(dict)
+        }
+    };
 
-        return (count, seconds, TextSource::Fixed(content));
+    let generated = matches!(
+        source,
+        TextSource::RandomWords(_)
+            | TextSource::Training(_)
+            | TextSource::Drill(_)
+            | TextSource::Charset(_)
+    );
+    if text.separator.is_some() && !generated {
+        usage_error("-separator only applies to generated text")
     }
 
-    let dict = if let Some(path) = dict_path {
-        load_dictionary_from_file(&path)
+    let repeat = if repeat_times > 0 {
+        Some(Repeat {
+            times: repeat_times,
+            min_wpm: repeat_wpm as f64,
+            min_accuracy: 0.0,
+            flawless: true,
+        })
+    } else if until_wpm.is_some() || until_accuracy.is_some() {
+        Some(Repeat {
+            times: 1,
+            min_wpm: until_wpm.unwrap_or(0) as f64,
+            min_accuracy: until_accuracy.unwrap_or(0) as f64,
+            flawless: false,
+        })
     } else {
-        load_system_dictionary()
+        None
     };
 
-    (count, seconds, TextSource::RandomWords(dict))
+    if zen && (!generated || repeat.is_some()) {
+        usage_error("-zen needs a generated text and can't be used with -repeat or -until-wpm")
+    }
+
+    if resume && bookmark.is_none() {
+        usage_error("-resume takes a single -text file of prose")
+    }
+
+    // Without a word count the test runs on time alone, so its text never
+    // has to end.
+    let stream = generated && (zen || (count == 0 && repeat.is_none()));
+
+    Args {
+        count: if count > 0 { count } else { DEFAULT_WORD_COUNT },
+        seconds: if seconds > 0 {
+            seconds
+        } else {
+            DEFAULT_SECONDS
+        },
+        source,
+        text,
+        playlist: Vec::new(),
+        goal: None,
+        repeat,
+        require,
+        minimum,
+        blind,
+        events,
+        serve_stats,
+        stats_file,
+        json,
+        bundle,
+        practice,
+        bookmark,
+        seed,
+        zen,
+        stream,
+    }
 }
 
-pub fn load_dictionary_from_file(path: &str) -> Vec<String> {
-    let content = fs::read_to_string(&path).unwrap_or_else(|e| {
-        eprintln!("Failed to read dictionary file at {}: {}", path, e);
+// Each non-empty, non-comment line of a playlist holds the options for one
+// test. The first test is returned with the rest queued in `playlist`.
+fn load_playlist(path: &str, config: &Config) -> Args {
+    let content = fs::read_to_string(path).unwrap_or_else(|e| {
+        eprintln!("Failed to read playlist at {}: {}", path, e);
 
         process::exit(1);
     });
 
+    let mut entries: Vec<Args> = content
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(|l| parse_arg_list(split_args(l).into_iter(), config))
+        .collect();
+
+    if entries.is_empty() {
+        eprintln!("Playlist at {} has no entries", path);
+
+        process::exit(1);
+    }
+
+    let mut first = entries.remove(0);
+    first.playlist = entries;
+
+    first
+}
+
+pub fn split_args(line: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut has_arg = false;
+
+    for ch in line.chars() {
+        match ch {
+            '"' => {
+                quoted = !quoted;
+                has_arg = true;
+            }
+            c if c.is_whitespace() && !quoted => {
+                if has_arg {
+                    args.push(std::mem::take(&mut current));
+                    has_arg = false;
+                }
+            }
+            c => {
+                current.push(c);
+                has_arg = true;
+            }
+        }
+    }
+
+    if has_arg {
+        args.push(current);
+    }
+
+    args
+}
+
+pub fn load_dictionary(path: &str) -> Result<Box<dyn TextProvider>, String> {
+    let size = fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+
+    if size > MMAP_THRESHOLD_BYTES {
+        let words = MappedWords::open(path)
+            .map_err(|e| format!("Failed to map dictionary file at {}: {}", path, e))?;
+
+        return Ok(Box::new(words));
+    }
+
+    Ok(words_provider(load_dictionary_from_file(path)?))
+}
+
+// Lists where every line is `word<TAB>count` are sampled by frequency, anything
+// else is sampled uniformly.
+fn words_provider(lines: Vec<String>) -> Box<dyn TextProvider> {
+    let weighted = lines.iter().all(|l| l.contains('\t'));
+
+    match weighted.then(|| WeightedWords::parse(&lines)).flatten() {
+        Some(words) => Box::new(words),
+        None => Box::new(lines),
+    }
+}
+
+// Words typed entirely with one hand, as often as they came up before.
+fn one_hand_words(
+    dict: &dyn TextProvider,
+    hand: Hand,
+    fingers: &FingerMap,
+) -> Box<dyn TextProvider> {
+    let counts = (0..dict.len())
+        .map(|i| (dict.get(i), dict.weight(i)))
+        .filter(|(word, _)| word.chars().all(|c| fingers.hand(c) == Some(hand)))
+        .collect();
+
+    Box::new(WeightedWords::new(counts))
+}
+
+pub fn load_dictionary_from_file(path: &str) -> Result<Vec<String>, String> {
+    let mtime = fs::metadata(path).and_then(|m| m.modified()).ok();
+
+    if let Some(words) = mtime.and_then(|t| load_cached_words(path, DICT_FILTERS, t)) {
+        return Ok(words);
+    }
+
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read dictionary file at {}: {}", path, e))?;
+
+    let words = parse_words(&content);
+
+    if let Some(mtime) = mtime {
+        let _ = store_cached_words(path, DICT_FILTERS, mtime, &words);
+    }
+
+    Ok(words)
+}
+
+fn parse_words(content: &str) -> Vec<String> {
     content
         .lines()
         .map(|s| s.trim().to_lowercase())
@@ -108,33 +596,369 @@ pub fn load_dictionary_from_file(path: &str) -> Vec<String> {
         .collect()
 }
 
-pub fn load_system_dictionary() -> Vec<String> {
-    load_dictionary_from_file("/usr/share/dict/words")
+pub fn embedded_wordlist(name: &str) -> Option<&'static str> {
+    match name {
+        #[cfg(feature = "english-200")]
+        "english-200" => Some(include_str!("../wordlists/english-200")),
+        #[cfg(feature = "english-1k")]
+        "english-1k" => Some(include_str!("../wordlists/english-1k")),
+        #[cfg(feature = "english-10k")]
+        "english-10k" => Some(include_str!("../wordlists/english-10k")),
+        _ => None,
+    }
+}
+
+pub fn load_language(name: &str) -> Result<Box<dyn TextProvider>, String> {
+    let content = embedded_wordlist(name).ok_or_else(|| {
+        format!(
+            "Unknown language: {} (available: {})",
+            name,
+            LANGUAGES.join(", ")
+        )
+    })?;
+
+    Ok(words_provider(parse_words(content)))
 }
 
-pub fn generate_text(dictionary: &[String], count: usize) -> String {
-    let mut rng = rand::rng();
+pub fn load_mix(spec: &str) -> Box<dyn TextProvider> {
+    let parts = spec
+        .split(',')
+        .filter(|part| !part.trim().is_empty())
+        .map(|part| {
+            let (name, weight) = match part.rsplit_once(':') {
+                Some((name, weight)) => (name.trim(), weight.trim().parse::<f64>().ok()),
+                None => (part.trim(), Some(1.0)),
+            };
+
+            let Some(weight) = weight.filter(|w| w.is_finite() && *w > 0.0) else {
+                usage_error(format!("Invalid weight in -mix source: {}", part))
+            };
+
+            let provider: Box<dyn TextProvider> = if LANGUAGES.contains(&name) {
+                load_language(name).unwrap_or_else(|e| usage_error(e))
+            } else if CHARSET_PRESETS.iter().any(|(preset, _)| *preset == name) {
+                Box::new(CharGroups::new(parse_charset(name)))
+            } else {
+                load_dictionary(name).unwrap_or_else(|e| {
+                    eprintln!("{}", e);
+
+                    process::exit(1);
+                })
+            };
+
+            if provider.is_empty() {
+                eprintln!("-mix source {} contains no words", name);
 
-    (0..count)
-        .map(|_| {
-            let index = rng.random_range(0..dictionary.len());
-            dictionary[index].clone()
+                process::exit(1);
+            }
+
+            (provider, weight)
         })
-        .collect::<Vec<String>>()
-        .join(" ")
+        .collect::<Vec<_>>();
+
+    if parts.is_empty() {
+        usage_error("-mix needs at least one source")
+    }
+
+    Box::new(MixedWords::new(parts))
+}
+
+pub fn load_system_dictionary() -> Result<Box<dyn TextProvider>, String> {
+    if Path::new(SYSTEM_DICTIONARY).exists() {
+        return load_dictionary(SYSTEM_DICTIONARY);
+    }
+
+    let fallback = LANGUAGES
+        .iter()
+        .find(|l| **l == DEFAULT_LANGUAGE)
+        .or(LANGUAGES.first());
+
+    match fallback {
+        Some(language) => load_language(language),
+        None => load_dictionary(SYSTEM_DICTIONARY),
+    }
 }
 
-pub fn layout_text(text: &str, width: u16) -> Layout {
+// Directories given to -text stand for the files directly inside them.
+fn expand_text_paths(paths: &[String]) -> Vec<String> {
+    let mut files = Vec::new();
+
+    for path in paths {
+        if !Path::new(path).is_dir() {
+            files.push(path.clone());
+
+            continue;
+        }
+
+        let entries = fs::read_dir(path).unwrap_or_else(|e| {
+            eprintln!("Failed to read text directory at {}: {}", path, e);
+
+            process::exit(1);
+        });
+
+        let mut dir_files: Vec<String> = entries
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|p| p.is_file())
+            .filter(|p| {
+                !p.file_name()
+                    .is_some_and(|n| n.to_string_lossy().starts_with('.'))
+            })
+            .map(|p| p.display().to_string())
+            .collect();
+        dir_files.sort();
+
+        if dir_files.is_empty() {
+            eprintln!("Text directory at {} has no files", path);
+
+            process::exit(1);
+        }
+
+        files.extend(dir_files);
+    }
+
+    files
+}
+
+fn read_text_file(path: &str) -> String {
+    let content = fs::read_to_string(path).unwrap_or_else(|e| {
+        eprintln!("Failed to read text file at {}: {}", path, e);
+
+        process::exit(1);
+    });
+
+    content.replace("\r\n", "\n")
+}
+
+fn is_code_file(path: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .is_some_and(|ext| CODE_EXTENSIONS.iter().any(|c| ext == *c))
+}
+
+fn prepare_text(content: &str, code: bool, normalize: bool) -> String {
+    if code {
+        normalize_code(content)
+    } else if normalize {
+        content.split_whitespace().collect::<Vec<_>>().join(" ")
+    } else {
+        join_paragraphs(content)
+    }
+}
+
+// Keeps indentation and line breaks, but drops trailing whitespace nobody
+// could see to type.
+fn normalize_code(content: &str) -> String {
+    let lines: Vec<&str> = content.lines().map(str::trim_end).collect();
+
+    lines.join("\n").trim_matches('\n').to_string()
+}
+
+// Joins the lines of each paragraph so prose wraps to the screen, and keeps
+// one line break between paragraphs that are separated by blank lines.
+fn join_paragraphs(content: &str) -> String {
+    let mut paragraphs: Vec<String> = Vec::new();
+    let mut current: Vec<&str> = Vec::new();
+
+    for line in content.lines().chain(iter::once("")) {
+        if line.trim().is_empty() {
+            if !current.is_empty() {
+                paragraphs.push(current.join(" "));
+                current.clear();
+            }
+        } else {
+            current.extend(line.split_whitespace());
+        }
+    }
+
+    paragraphs.join("\n")
+}
+
+// Cuts text after every `words` words. The whitespace at each cut is dropped
+// and nothing else, so the chunks add up to the text minus one character per
+// cut.
+pub fn split_chunks(text: &str, words: usize) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    let mut count = 0;
+    let mut in_word = false;
+
+    for c in text.chars() {
+        if !c.is_whitespace() {
+            in_word = true;
+            current.push(c);
+
+            continue;
+        }
+
+        if in_word {
+            in_word = false;
+            count += 1;
+
+            if count == words {
+                chunks.push(std::mem::take(&mut current));
+                count = 0;
+
+                continue;
+            }
+        }
+
+        current.push(c);
+    }
+
+    if !current.trim().is_empty() {
+        chunks.push(current);
+    }
+
+    chunks
+}
+
+// Splits text at sentence-ending punctuation, keeping closing quotes and
+// brackets with the sentence they end. A lowercase word after the punctuation
+// (`"Fine!" she said.`) continues the sentence. Line breaks and runs of spaces
+// are collapsed so each sentence is typed as a single line.
+pub fn split_sentences(text: &str) -> Vec<String> {
+    let mut sentences = Vec::new();
+    let mut current: Vec<&str> = Vec::new();
+    let mut words = text.split_whitespace().peekable();
+
+    while let Some(word) = words.next() {
+        current.push(word);
+
+        let ending = word.trim_end_matches(['"', '\'', ')', ']']);
+        let continues = words
+            .peek()
+            .and_then(|next| next.chars().next())
+            .is_some_and(char::is_lowercase);
+
+        if ending.ends_with(['.', '!', '?']) && !continues {
+            sentences.push(current.join(" "));
+            current.clear();
+        }
+    }
+
+    if !current.is_empty() {
+        sentences.push(current.join(" "));
+    }
+
+    sentences
+}
+
+pub fn quote_length(text: &str) -> QuoteLength {
+    let len = text.chars().count();
+
+    if len <= SHORT_QUOTE_MAX_LEN {
+        QuoteLength::Short
+    } else if len <= MEDIUM_QUOTE_MAX_LEN {
+        QuoteLength::Medium
+    } else {
+        QuoteLength::Long
+    }
+}
+
+pub fn load_quotes(length: Option<QuoteLength>) -> Vec<Quote> {
+    let quotes: Vec<Quote> = serde_json::from_str(QUOTES_JSON).unwrap_or_else(|e| {
+        eprintln!("Failed to parse built-in quotes: {}", e);
+
+        process::exit(1);
+    });
+
+    quotes
+        .into_iter()
+        .filter(|q| length.is_none_or(|l| quote_length(&q.text) == l))
+        .collect()
+}
+
+// None when there are no quotes to pick from.
+pub fn pick_quote<'a>(quotes: &'a [Quote], rng: &mut impl Rng) -> Option<&'a Quote> {
+    if quotes.is_empty() {
+        return None;
+    }
+
+    Some(&quotes[rng.random_range(0..quotes.len())])
+}
+
+pub fn typing_stats(target: &str, typed: &str, elapsed: f64) -> TestResult {
+    let total_typed = typed.chars().count() as u32;
+
+    let correct = target
+        .chars()
+        .zip(typed.chars())
+        .filter(|(a, b)| a == b)
+        .count() as u32;
+
+    let wpm = if elapsed > 0.0 {
+        let minutes = elapsed / 60.0;
+        if minutes > 0.0 {
+            (total_typed as f64 / 5.0) / minutes
+        } else {
+            0.0
+        }
+    } else {
+        0.0
+    };
+
+    let accuracy = if total_typed > 0 {
+        (correct as f64 / total_typed as f64) * 100.0
+    } else {
+        100.0
+    };
+
+    TestResult {
+        wpm,
+        accuracy,
+        seconds: elapsed,
+    }
+}
+
+// Words wrap at `width`, and `\n` is a hard break shown as a `↵` glyph that
+// has to be typed with Enter. Spaces at the start of a wrapped line are hidden,
+// indentation after a hard break is kept. A tab takes one glyph per column up
+// to the next multiple of `tab_width`, all pointing at the same character.
+// The first line starts `indent` columns in.
+#[allow(clippy::needless_range_loop)]
+pub fn layout_text(text: &str, width: u16, tab_width: usize, indent: usize) -> Layout {
     let width = width.max(1) as usize;
     let chars: Vec<char> = text.chars().collect();
 
     let mut lines: Layout = vec![Vec::new()];
-    let mut col = 0usize;
+    let mut col = indent.min(width - 1);
+    let mut wrapped = false;
     let mut i = 0usize;
 
     while i < chars.len() {
+        if chars[i] == '\n' {
+            if col + 1 > width {
+                lines.push(Vec::new());
+            }
+
+            lines.last_mut().unwrap().push(Glyph { ch: '\n', idx: i });
+            lines.push(Vec::new());
+            col = 0;
+            wrapped = false;
+            i += 1;
+
+            continue;
+        }
+
+        if chars[i] == '\t' {
+            let span = tab_width.max(1) - col % tab_width.max(1);
+            if col > 0 && col + span > width {
+                lines.push(Vec::new());
+                col = 0;
+                wrapped = true;
+            }
+
+            for _ in 0..span.min(width) {
+                lines.last_mut().unwrap().push(Glyph { ch: '\t', idx: i });
+            }
+            col += span;
+            i += 1;
+
+            continue;
+        }
+
         if chars[i] == ' ' {
-            if col == 0 {
+            if col == 0 && wrapped {
                 i += 1;
 
                 continue;
@@ -143,6 +967,7 @@ pub fn layout_text(text: &str, width: u16) -> Layout {
             if col + 1 > width {
                 lines.push(Vec::new());
                 col = 0;
+                wrapped = true;
                 i += 1;
 
                 continue;
@@ -156,7 +981,7 @@ pub fn layout_text(text: &str, width: u16) -> Layout {
         }
 
         let start = i;
-        while i < chars.len() && chars[i] != ' ' {
+        while i < chars.len() && !matches!(chars[i], ' ' | '\n' | '\t') {
             i += 1;
         }
 
@@ -164,6 +989,7 @@ pub fn layout_text(text: &str, width: u16) -> Layout {
         if col > 0 && col + word_len > width {
             lines.push(Vec::new());
             col = 0;
+            wrapped = true;
         }
 
         for j in start..i {
@@ -183,6 +1009,50 @@ pub fn layout_text(text: &str, width: u16) -> Layout {
     lines
 }
 
+// Screen column where `row` of the layout starts, so centered and
+// right-aligned text and the caret on it line up.
+pub fn line_offset(
+    layout: &Layout,
+    row: usize,
+    width: u16,
+    align: TextAlign,
+    indent: usize,
+) -> u16 {
+    let len = layout.get(row).map_or(0, |line| line.len()) as u16;
+
+    match align {
+        TextAlign::Left if row == 0 => (indent as u16).min(width.saturating_sub(1)),
+        TextAlign::Left => 0,
+        TextAlign::Center => width.saturating_sub(len) / 2,
+        TextAlign::Right => width.saturating_sub(len),
+    }
+}
+
+// The word at `idx`, or the one just before it when `idx` is on the space
+// after it, as a range of char indexes.
+pub fn word_range(text: &str, idx: usize) -> Option<(usize, usize)> {
+    let chars: Vec<char> = text.chars().collect();
+    let at = if chars.get(idx).is_none_or(|c| c.is_whitespace()) {
+        idx.checked_sub(1)?
+    } else {
+        idx
+    };
+    if chars.get(at)?.is_whitespace() {
+        return None;
+    }
+
+    let start = chars[..at]
+        .iter()
+        .rposition(|c| c.is_whitespace())
+        .map_or(0, |i| i + 1);
+    let end = chars[at..]
+        .iter()
+        .position(|c| c.is_whitespace())
+        .map_or(chars.len(), |i| at + i);
+
+    Some((start, end))
+}
+
 pub fn cursor_row_col_from_layout(layout: &Layout, cursor_idx: usize) -> (u16, u16) {
     for (row, line) in layout.iter().enumerate() {
         for (col, glyph) in line.iter().enumerate() {
@@ -202,16 +1072,32 @@ pub fn cursor_row_col_from_layout(layout: &Layout, cursor_idx: usize) -> (u16, u
     }
 }
 
+// How the target is drawn apart from the text and what was typed: the review
+// selection, the word being typed, syntax colors, the glyph shown for spaces,
+// where the ghost of the best run on this text is, whether mistakes show what
+// was typed instead of the target and whether the text still to come is dimmed.
+pub struct TargetStyle<'a> {
+    pub selection: Option<(usize, usize)>,
+    pub word: Option<(usize, usize)>,
+    pub colors: &'a [Option<Color>],
+    pub space: char,
+    pub ghost: Option<usize>,
+    pub overlay: bool,
+    pub dim_untyped: bool,
+}
+
+#[allow(clippy::needless_range_loop)]
 pub fn build_target_lines_from_layout(
     layout: &Layout,
     typed: &str,
     scroll_y: u16,
     visible_height: u16,
+    target_style: &TargetStyle,
 ) -> Vec<Line<'static>> {
     let typed_chars: Vec<char> = typed.chars().collect();
 
-    let start = scroll_y as usize;
     let end = (scroll_y + visible_height).min(layout.len() as u16) as usize;
+    let start = (scroll_y as usize).min(end);
 
     let mut lines_out: Vec<Line<'static>> = Vec::new();
 
@@ -222,21 +1108,55 @@ pub fn build_target_lines_from_layout(
             let ch = glyph.ch;
             let idx = glyph.idx;
 
-            let style = if let Some(uc) = typed_chars.get(idx) {
-                if *uc == ch {
+            let typed_char = typed_chars.get(idx).copied();
+            let mistake = typed_char.filter(|&uc| uc != ch);
+
+            let style = if let Some(uc) = typed_char {
+                if uc == ch {
                     Style::default().fg(Color::Green)
+                } else if ch == ' ' || ch == '\t' || (target_style.overlay && uc.is_whitespace()) {
+                    Style::default().bg(Color::Red)
                 } else {
-                    if ch == ' ' {
-                        Style::default().bg(Color::Red)
-                    } else {
-                        Style::default().fg(Color::Red)
-                    }
+                    Style::default().fg(Color::Red)
                 }
+            } else if let Some(Some(color)) = target_style.colors.get(idx) {
+                Style::default().fg(*color)
+            } else if ch == ' ' || target_style.dim_untyped {
+                Style::default().add_modifier(Modifier::DIM)
             } else {
                 Style::default()
             };
 
-            spans.push(Span::styled(ch.to_string(), style));
+            let style = match target_style.word {
+                Some((start, end)) if (start..end).contains(&idx) => style
+                    .remove_modifier(Modifier::DIM)
+                    .add_modifier(Modifier::BOLD | Modifier::UNDERLINED),
+                _ => style,
+            };
+
+            let style = match target_style.selection {
+                Some((start, end)) if (start..end).contains(&idx) => {
+                    style.add_modifier(Modifier::REVERSED)
+                }
+                _ => style,
+            };
+
+            let style = if target_style.ghost == Some(idx) {
+                style.bg(Color::DarkGray)
+            } else {
+                style
+            };
+
+            let ch = match mistake {
+                Some(uc) if target_style.overlay => uc,
+                _ => ch,
+            };
+            let shown = if ch == ' ' {
+                target_style.space
+            } else {
+                display_char(ch)
+            };
+            spans.push(Span::styled(shown.to_string(), style));
         }
         lines_out.push(Line::from(spans));
     }
@@ -244,6 +1164,102 @@ pub fn build_target_lines_from_layout(
     lines_out
 }
 
+// The caret's line with the ones before and after it, spaced out and centered
+// in `height` rows. `offset` shifts everything down (or up) a row while the
+// view is rolling to a new line.
+pub fn build_rolling_lines(
+    layout: &Layout,
+    typed: &str,
+    caret_row: u16,
+    height: u16,
+    offset: i16,
+    target_style: &TargetStyle,
+) -> Vec<Line<'static>> {
+    let mut lines_out = vec![Line::default(); height as usize];
+    let gap = if height >= 5 { 2 } else { 1 };
+    let center = (height / 2) as i16 + offset;
+
+    for step in -1i16..=1 {
+        let Some(row) = caret_row.checked_add_signed(step) else {
+            continue;
+        };
+        let y = center + step * gap;
+        if y < 0 || y >= height as i16 {
+            continue;
+        }
+
+        let Some(line) = build_target_lines_from_layout(layout, typed, row, 1, target_style).pop()
+        else {
+            continue;
+        };
+
+        let style = if step == 0 {
+            Style::default().add_modifier(Modifier::BOLD)
+        } else {
+            Style::default().add_modifier(Modifier::DIM)
+        };
+
+        lines_out[y as usize] = line.patch_style(style).centered();
+    }
+
+    lines_out
+}
+
+// One word per row, each with the whitespace after it so that is seen and
+// typed too.
+pub fn stack_layout(text: &str) -> Layout {
+    let mut lines: Layout = vec![Vec::new()];
+
+    for (idx, ch) in text.chars().enumerate() {
+        let after_space = lines
+            .last()
+            .and_then(|line| line.last())
+            .is_some_and(|glyph| glyph.ch.is_whitespace());
+        if after_space && !ch.is_whitespace() {
+            lines.push(Vec::new());
+        }
+
+        lines.last_mut().unwrap().push(Glyph { ch, idx });
+    }
+
+    lines
+}
+
+// The word stack, the caret's word held on a fixed row a third of the way
+// down, with the words done above it and the ones to come below.
+pub fn build_stack_lines(
+    layout: &Layout,
+    typed: &str,
+    caret_row: u16,
+    height: u16,
+    target_style: &TargetStyle,
+) -> Vec<Line<'static>> {
+    let fixed = height / 3;
+
+    (0..height)
+        .map(|y| {
+            let Some(row) = (caret_row + y).checked_sub(fixed) else {
+                return Line::default();
+            };
+
+            let Some(line) =
+                build_target_lines_from_layout(layout, typed, row, 1, target_style).pop()
+            else {
+                return Line::default();
+            };
+
+            let style = match row.cmp(&caret_row) {
+                Ordering::Less => Style::default().add_modifier(Modifier::DIM),
+                Ordering::Equal => Style::default().add_modifier(Modifier::BOLD),
+                Ordering::Greater => Style::default(),
+            };
+
+            line.patch_style(style).centered()
+        })
+        .collect()
+}
+
+#[allow(clippy::needless_range_loop)]
 pub fn build_typed_visible_from_layout(
     layout: &Layout,
     scroll_y: u16,
@@ -254,9 +1270,32 @@ pub fn build_typed_visible_from_layout(
 
     let mut lines: Vec<String> = Vec::new();
     for row in start..end {
-        let s: String = layout[row].iter().map(|g| g.ch).collect();
+        let s: String = layout[row].iter().map(|g| display_char(g.ch)).collect();
         lines.push(s);
     }
 
     lines.join("\n")
 }
+
+fn display_char(ch: char) -> char {
+    match ch {
+        '\n' => '↵',
+        '\t' => ' ',
+        ch => ch,
+    }
+}
+
+// The OSC 52 escape sequence, so copying works over SSH and without any
+// platform clipboard tooling as long as the terminal supports it.
+pub fn clipboard_sequence(text: &str) -> String {
+    format!("\x1b]52;c;{}\x07", STANDARD.encode(text))
+}
+
+// FNV-1a, for hashes that have to agree between builds and platforms.
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+pub const BELL: &str = "\x07";
@@ -0,0 +1,89 @@
+use ratatui::style::Color;
+
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "case", "catch", "class", "const", "continue", "def",
+    "default", "defer", "do", "elif", "else", "enum", "export", "extern", "false", "final",
+    "finally", "fn", "for", "from", "func", "function", "go", "if", "impl", "import", "in", "is",
+    "let", "loop", "match", "mod", "mut", "new", "nil", "none", "null", "package", "private",
+    "pub", "public", "return", "self", "static", "struct", "super", "switch", "this", "throw",
+    "trait", "true", "try", "type", "use", "var", "void", "where", "while", "yield",
+];
+
+const KEYWORD_COLOR: Color = Color::Magenta;
+const STRING_COLOR: Color = Color::Yellow;
+const NUMBER_COLOR: Color = Color::Cyan;
+const COMMENT_COLOR: Color = Color::DarkGray;
+
+// Colors for each character of a source file, from a small language-agnostic
+// lexer that knows about keywords, strings, numbers and `//`, `/* */` and `#`
+// comments. Good enough to make practice code readable, not to be correct.
+pub fn highlight_code(text: &str) -> Vec<Option<Color>> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut colors = vec![None; chars.len()];
+    let mut line_start = true;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+
+        let end = if c == '/' && next == Some('/') || c == '#' && line_start {
+            let end = chars[i..]
+                .iter()
+                .position(|&c| c == '\n')
+                .map_or(chars.len(), |p| i + p);
+            colors[i..end].fill(Some(COMMENT_COLOR));
+
+            end
+        } else if c == '/' && next == Some('*') {
+            let end = (i + 2..chars.len().saturating_sub(1))
+                .find(|&j| chars[j] == '*' && chars[j + 1] == '/')
+                .map_or(chars.len(), |j| j + 2);
+            colors[i..end].fill(Some(COMMENT_COLOR));
+
+            end
+        } else if c == '"' || (c == '\'' && is_char_literal(&chars[i..])) {
+            let mut j = i + 1;
+            while j < chars.len() && chars[j] != c && chars[j] != '\n' {
+                j += if chars[j] == '\\' { 2 } else { 1 };
+            }
+            let end = (j + 1).min(chars.len());
+            colors[i..end].fill(Some(STRING_COLOR));
+
+            end
+        } else if c.is_alphanumeric() || c == '_' {
+            let len = chars[i..]
+                .iter()
+                .take_while(|c| c.is_alphanumeric() || **c == '_')
+                .count();
+            let word: String = chars[i..i + len].iter().collect();
+
+            if c.is_ascii_digit() {
+                colors[i..i + len].fill(Some(NUMBER_COLOR));
+            } else if KEYWORDS.contains(&word.as_str()) {
+                colors[i..i + len].fill(Some(KEYWORD_COLOR));
+            }
+
+            i + len
+        } else {
+            i + 1
+        };
+
+        for &c in &chars[i..end] {
+            if c == '\n' {
+                line_start = true;
+            } else if !c.is_whitespace() {
+                line_start = false;
+            }
+        }
+
+        i = end;
+    }
+
+    colors
+}
+
+// Tells `'a'` and `'\n'` apart from Rust lifetimes and apostrophes in text.
+fn is_char_literal(chars: &[char]) -> bool {
+    matches!(chars, ['\'', '\\', _, ..] | ['\'', _, '\'', ..])
+}
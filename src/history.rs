@@ -0,0 +1,90 @@
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, env, fs, io, path::PathBuf};
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
+pub struct CharStats {
+    pub typed: u64,
+    pub missed: u64,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ErrorHistory {
+    pub chars: HashMap<char, CharStats>,
+    pub words: HashMap<String, u64>,
+}
+
+impl ErrorHistory {
+    pub fn record(&mut self, target: &str, typed: &str) {
+        let mut word = String::new();
+        let mut word_missed = false;
+
+        for (expected, actual) in target.chars().zip(typed.chars()) {
+            let stats = self.chars.entry(expected).or_default();
+            stats.typed += 1;
+            if expected != actual {
+                stats.missed += 1;
+                word_missed = true;
+            }
+
+            if expected == ' ' {
+                self.finish_word(&mut word, &mut word_missed);
+            } else {
+                word.push(expected);
+            }
+        }
+
+        self.finish_word(&mut word, &mut word_missed);
+    }
+
+    fn finish_word(&mut self, word: &mut String, missed: &mut bool) {
+        if *missed && !word.is_empty() {
+            *self.words.entry(word.to_lowercase()).or_default() += 1;
+        }
+
+        word.clear();
+        *missed = false;
+    }
+
+    // Smoothed miss rate, so characters with little data don't dominate.
+    pub fn char_error_rate(&self, ch: char) -> f64 {
+        let stats = self.chars.get(&ch).copied().unwrap_or_default();
+
+        (stats.missed as f64 + 1.0) / (stats.typed as f64 + 50.0)
+    }
+
+    pub fn word_misses(&self, word: &str) -> u64 {
+        self.words.get(word).copied().unwrap_or(0)
+    }
+}
+
+pub fn data_dir() -> Option<PathBuf> {
+    env::var_os("XDG_DATA_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))
+        .map(|dir| dir.join("ttt"))
+}
+
+fn errors_path() -> Option<PathBuf> {
+    data_dir().map(|dir| dir.join("errors.json"))
+}
+
+pub fn load_error_history() -> ErrorHistory {
+    errors_path()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+pub fn save_error_history(history: &ErrorHistory) -> io::Result<()> {
+    let Some(path) = errors_path() else {
+        return Ok(());
+    };
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    fs::write(path, serde_json::to_string(history)?)
+}
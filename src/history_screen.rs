@@ -0,0 +1,101 @@
+use crate::{export::format_date, results::ResultRecord};
+
+use ratatui::{
+    crossterm::event::{KeyCode, KeyEvent},
+    prelude::*,
+};
+
+// How many results are shown at once; the rest are scrolled to.
+const ROWS: usize = 10;
+
+// The saved results, newest first, for looking back through between tests.
+pub struct HistoryScreen {
+    records: Vec<ResultRecord>,
+    selected: usize,
+    // The first row shown.
+    top: usize,
+}
+
+impl HistoryScreen {
+    pub fn new(mut records: Vec<ResultRecord>) -> Self {
+        records.reverse();
+
+        Self {
+            records,
+            selected: 0,
+            top: 0,
+        }
+    }
+
+    // Whether to close the screen.
+    pub fn handle_key(&mut self, key: KeyEvent) -> bool {
+        match key.code {
+            KeyCode::Up | KeyCode::Char('k') => self.scroll(false),
+            KeyCode::Down | KeyCode::Char('j') => self.scroll(true),
+            KeyCode::Enter | KeyCode::Esc | KeyCode::Char('q') => return true,
+            _ => {}
+        }
+
+        false
+    }
+
+    // A click on a row picks it.
+    pub fn click(&mut self, row: usize) {
+        if row < ROWS && self.top + row < self.records.len() {
+            self.selected = self.top + row;
+        }
+    }
+
+    pub fn scroll(&mut self, down: bool) {
+        self.selected = if down {
+            (self.selected + 1).min(self.records.len().saturating_sub(1))
+        } else {
+            self.selected.saturating_sub(1)
+        };
+
+        if self.selected < self.top {
+            self.top = self.selected;
+        } else if self.selected >= self.top + ROWS {
+            self.top = self.selected + 1 - ROWS;
+        }
+    }
+
+    pub fn lines(&self) -> Vec<Line<'static>> {
+        if self.records.is_empty() {
+            return vec![Line::from("No results yet.")];
+        }
+
+        let rows = self.records.iter().enumerate().skip(self.top).take(ROWS);
+        let mut lines: Vec<Line> = rows
+            .map(|(i, record)| {
+                let date = format_date(record.timestamp);
+                let accuracy = record
+                    .accuracy
+                    .map(|a| format!("{:.1}%", a))
+                    .unwrap_or_default();
+                let line = Line::from(format!(
+                    "{} {:>6.1} wpm {:>6} {}",
+                    date[..16].replacen('T', " ", 1),
+                    record.wpm,
+                    accuracy,
+                    record.source
+                ));
+
+                if i == self.selected {
+                    line.style(Style::default().add_modifier(Modifier::REVERSED))
+                } else {
+                    line
+                }
+            })
+            .collect();
+
+        // Where the picked result's keystrokes can be watched again.
+        let replay = match &self.records[self.selected].replay {
+            Some(name) => format!("ttt replay {}", name),
+            None => "No replay recorded".to_string(),
+        };
+        lines.push(Line::from(replay).style(Style::default().add_modifier(Modifier::DIM)));
+
+        lines
+    }
+}
@@ -0,0 +1,372 @@
+use crate::results::{ResultRecord, append_results};
+
+use clap::ValueEnum;
+use serde::Deserialize;
+use std::{fs, process};
+
+const CHARS_PER_WORD: f64 = 5.0;
+
+// Typing trainers whose results `ttt import` reads.
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ImportFormat {
+    /// typespeed high score file
+    Typespeed,
+    /// gtypist best speeds file (usually ~/.gtypist)
+    Gtypist,
+    /// output of tt -json or tt -csv
+    Tt,
+    /// CSV export of Monkeytype results (Account, Export CSV)
+    Monkeytype,
+}
+
+pub fn import_file(format: ImportFormat, path: &str) {
+    // Monkeytype's CSV needs its header, so it isn't read line by line.
+    let parse: Option<fn(&str) -> Option<ResultRecord>> = match format {
+        ImportFormat::Typespeed => Some(parse_typespeed_line),
+        ImportFormat::Gtypist => Some(parse_gtypist_line),
+        ImportFormat::Tt => Some(parse_tt_line),
+        ImportFormat::Monkeytype => None,
+    };
+
+    let content = fs::read_to_string(path).unwrap_or_else(|e| {
+        eprintln!("Failed to read {}: {}", path, e);
+
+        process::exit(1);
+    });
+
+    let lines: Vec<&str> = content
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .collect();
+    let (records, rows) = match parse {
+        Some(parse) => (lines.iter().filter_map(|l| parse(l)).collect(), lines.len()),
+        None => parse_monkeytype(&lines),
+    };
+
+    if let Err(e) = append_results(&records) {
+        eprintln!("Failed to save results: {}", e);
+
+        process::exit(1);
+    }
+
+    println!(
+        "Imported {} results from {} ({} lines skipped)",
+        records.len(),
+        path,
+        rows - records.len()
+    );
+}
+
+// typespeed keeps one tab-separated line per high score:
+// `name  score  cps  tcps  ...  unix-time`. Speeds are in characters per
+// second, the time is the last numeric field when present.
+fn parse_typespeed_line(line: &str) -> Option<ResultRecord> {
+    let fields: Vec<&str> = line.split('\t').map(str::trim).collect();
+    let cps: f64 = fields.get(2)?.parse().ok()?;
+    let timestamp = fields
+        .iter()
+        .skip(4)
+        .rev()
+        .find_map(|f| f.parse::<u64>().ok())
+        .unwrap_or(0);
+
+    Some(ResultRecord {
+        timestamp,
+        wpm: cps * 60.0 / CHARS_PER_WORD,
+        accuracy: None,
+        seconds: None,
+        source: "typespeed".to_string(),
+        practice: false,
+        incomplete: false,
+        failed: false,
+        replay: None,
+    })
+}
+
+// gtypist records the best speed per lesson as
+// `script label YYYY-MM-DD HH:MM:SS wpm`, with optional fields in between.
+fn parse_gtypist_line(line: &str) -> Option<ResultRecord> {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    let wpm: f64 = fields.last()?.parse().ok()?;
+
+    let timestamp = fields
+        .iter()
+        .position(|f| parse_date(f).is_some())
+        .and_then(|i| {
+            let days = parse_date(fields[i])?;
+            let seconds = fields.get(i + 1).and_then(|t| parse_time(t)).unwrap_or(0);
+
+            Some(days * 86_400 + seconds)
+        })
+        .unwrap_or(0);
+
+    let lesson = fields.iter().take(2).copied().collect::<Vec<_>>().join(" ");
+
+    Some(ResultRecord {
+        timestamp,
+        wpm,
+        accuracy: None,
+        seconds: None,
+        source: format!("gtypist {}", lesson),
+        practice: false,
+        incomplete: false,
+        failed: false,
+        replay: None,
+    })
+}
+
+#[derive(Deserialize)]
+struct TtResult {
+    wpm: f64,
+    accuracy: Option<f64>,
+    timestamp: Option<u64>,
+}
+
+// tt prints one result per test, either as JSON (`-json`) or as
+// `test,wpm,cpm,accuracy,timestamp` (`-csv`), where the header line is skipped.
+fn parse_tt_line(line: &str) -> Option<ResultRecord> {
+    let result = if line.starts_with('{') {
+        serde_json::from_str::<TtResult>(line).ok()?
+    } else {
+        let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+
+        TtResult {
+            wpm: fields.get(1)?.parse().ok()?,
+            accuracy: fields.get(3).and_then(|a| a.parse().ok()),
+            timestamp: fields.get(4).and_then(|t| t.parse().ok()),
+        }
+    };
+
+    Some(ResultRecord {
+        timestamp: result.timestamp.unwrap_or(0),
+        wpm: result.wpm,
+        accuracy: result.accuracy,
+        seconds: None,
+        source: "tt".to_string(),
+        practice: false,
+        incomplete: false,
+        failed: false,
+        replay: None,
+    })
+}
+
+// Monkeytype's export has a header naming its columns, which have changed
+// order over time, so they are looked up by name. `timestamp` is in
+// milliseconds and `testDuration` in seconds; tests given up on (`bailedOut`)
+// are kept as incomplete. Returns the records and the number of rows.
+fn parse_monkeytype(lines: &[&str]) -> (Vec<ResultRecord>, usize) {
+    let Some((header, rows)) = lines.split_first() else {
+        return (Vec::new(), 0);
+    };
+
+    let columns = split_csv_line(header);
+    let column = |name: &str| columns.iter().position(|c| c == name);
+    let (Some(wpm), Some(timestamp)) = (column("wpm"), column("timestamp")) else {
+        return (Vec::new(), rows.len());
+    };
+    let accuracy = column("acc");
+    let seconds = column("testDuration");
+    let mode = column("mode");
+    let mode2 = column("mode2");
+    let bailed_out = column("bailedOut");
+
+    let records = rows
+        .iter()
+        .filter_map(|row| {
+            let fields = split_csv_line(row);
+            let field = |i: Option<usize>| i.and_then(|i| fields.get(i)).map(String::as_str);
+            let number = |i: Option<usize>| field(i).and_then(|v| v.parse::<f64>().ok());
+
+            let source = [
+                "monkeytype",
+                field(mode).unwrap_or_default(),
+                field(mode2).unwrap_or_default(),
+            ]
+            .into_iter()
+            .filter(|part| !part.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ");
+
+            Some(ResultRecord {
+                timestamp: number(Some(timestamp))? as u64 / 1000,
+                wpm: number(Some(wpm))?,
+                accuracy: number(accuracy),
+                seconds: number(seconds),
+                source,
+                practice: false,
+                incomplete: field(bailed_out) == Some("true"),
+                failed: false,
+                replay: None,
+            })
+        })
+        .collect();
+
+    (records, rows.len())
+}
+
+// Fields may be quoted, with `""` for a quote inside one.
+fn split_csv_line(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    fields.push(field);
+
+    fields
+}
+
+// Days since the Unix epoch for a `YYYY-MM-DD` date.
+fn parse_date(value: &str) -> Option<u64> {
+    let mut parts = value.splitn(3, '-');
+    let year: i64 = parts.next()?.parse().ok()?;
+    let month: i64 = parts.next()?.parse().ok()?;
+    let day: i64 = parts.next()?.parse().ok()?;
+
+    if !(1970..=9999).contains(&year) || !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    // Howard Hinnant's days_from_civil.
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y / 400;
+    let yoe = y - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+
+    u64::try_from(era * 146_097 + doe - 719_468).ok()
+}
+
+fn parse_time(value: &str) -> Option<u64> {
+    let mut parts = value.splitn(3, ':');
+    let hours: u64 = parts.next()?.parse().ok()?;
+    let minutes: u64 = parts.next()?.parse().ok()?;
+    let seconds: u64 = parts.next().map_or(Some(0), |s| s.parse().ok())?;
+
+    Some(hours * 3600 + minutes * 60 + seconds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn typespeed_line() {
+        let record = parse_typespeed_line("alice\t1520\t6.25\t6.80\t12\t1700000000").unwrap();
+
+        assert_eq!(record.wpm, 75.0);
+        assert_eq!(record.timestamp, 1_700_000_000);
+        assert_eq!(record.accuracy, None);
+        assert_eq!(record.source, "typespeed");
+    }
+
+    #[test]
+    fn typespeed_line_without_time() {
+        let record = parse_typespeed_line("alice\t1520\t6.25\t6.80").unwrap();
+
+        assert_eq!(record.timestamp, 0);
+    }
+
+    #[test]
+    fn malformed_typespeed_lines() {
+        assert!(parse_typespeed_line("").is_none());
+        assert!(parse_typespeed_line("alice 1520 6.25 6.80").is_none());
+        assert!(parse_typespeed_line("alice\t1520\tfast\t6.80").is_none());
+    }
+
+    #[test]
+    fn gtypist_line() {
+        let record = parse_gtypist_line("gtypist.typ S_LESSON1 2023-11-14 22:13:20 42.7").unwrap();
+
+        assert_eq!(record.wpm, 42.7);
+        assert_eq!(record.timestamp, 1_700_000_000);
+        assert_eq!(record.source, "gtypist gtypist.typ S_LESSON1");
+    }
+
+    #[test]
+    fn malformed_gtypist_lines() {
+        assert!(parse_gtypist_line("").is_none());
+        assert!(parse_gtypist_line("gtypist.typ S_LESSON1 2023-11-14 22:13:20").is_none());
+    }
+
+    #[test]
+    fn tt_json_line() {
+        let record =
+            parse_tt_line(r#"{"wpm":81.2,"cpm":406,"accuracy":96.5,"timestamp":1700000000}"#)
+                .unwrap();
+
+        assert_eq!(record.wpm, 81.2);
+        assert_eq!(record.accuracy, Some(96.5));
+        assert_eq!(record.timestamp, 1_700_000_000);
+    }
+
+    #[test]
+    fn tt_csv_line() {
+        let record = parse_tt_line("1,81.2,406,96.5,1700000000").unwrap();
+
+        assert_eq!(record.wpm, 81.2);
+        assert_eq!(record.accuracy, Some(96.5));
+        assert_eq!(record.timestamp, 1_700_000_000);
+    }
+
+    #[test]
+    fn malformed_tt_lines() {
+        assert!(parse_tt_line("test,wpm,cpm,accuracy,timestamp").is_none());
+        assert!(parse_tt_line(r#"{"accuracy":96.5}"#).is_none());
+        assert!(parse_tt_line("{not json").is_none());
+        assert!(parse_tt_line("1").is_none());
+    }
+
+    #[test]
+    fn monkeytype_rows() {
+        let lines = [
+            "_id,wpm,acc,mode,mode2,testDuration,timestamp",
+            "a,92.5,97.1,time,30,30,1700000000000",
+            "b,fast,97.1,time,30,30,1700000000000",
+        ];
+        let (records, rows) = parse_monkeytype(&lines);
+
+        assert_eq!(rows, 2);
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].wpm, 92.5);
+        assert_eq!(records[0].timestamp, 1_700_000_000);
+        assert_eq!(records[0].source, "monkeytype time 30");
+    }
+
+    #[test]
+    fn monkeytype_rows_without_mode() {
+        let (records, _) = parse_monkeytype(&["wpm,timestamp", "92.5,1700000000000"]);
+
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].source, "monkeytype");
+    }
+
+    #[test]
+    fn dates() {
+        assert_eq!(parse_date("1970-01-01"), Some(0));
+        assert_eq!(parse_date("2000-02-29"), Some(11_016));
+        assert_eq!(parse_date("2023-11-14"), Some(19_675));
+    }
+
+    #[test]
+    fn malformed_dates() {
+        assert_eq!(parse_date("1969-12-31"), None);
+        assert_eq!(parse_date("2023-13-01"), None);
+        assert_eq!(parse_date("2023-11-00"), None);
+        assert_eq!(parse_date("2023-11"), None);
+        assert_eq!(parse_date("22:13:20"), None);
+    }
+}
@@ -0,0 +1,294 @@
+use crate::{
+    config::Config,
+    types::{Action, PlaybackAction, ReviewAction},
+};
+
+use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::{collections::HashMap, hash::Hash};
+
+// Keys of the test screen unless the config's `[keys]` table says otherwise.
+// A binding is a key or a chord of keys separated by spaces, where `<leader>`
+// stands for the leader key.
+const DEFAULT_BINDINGS: &[(Action, &[&str])] = &[
+    (Action::Quit, &["esc", "<leader> q"]),
+    (Action::Restart, &["f5", "tab", "<leader> r"]),
+    (Action::Retry, &["ctrl+r"]),
+    (Action::TogglePractice, &["<leader> p"]),
+    (Action::Stop, &["<leader> s"]),
+    (Action::Pause, &["<leader> space"]),
+    (Action::Settings, &["<leader> ,"]),
+    (Action::ToggleTyped, &["<leader> t"]),
+    (Action::ToggleFocus, &["<leader> f"]),
+    (
+        Action::DeleteWord,
+        &["ctrl+w", "ctrl+backspace", "alt+backspace"],
+    ),
+    (Action::Suspend, &["ctrl+z"]),
+    (Action::Interrupt, &["ctrl+c"]),
+];
+
+// These work on every screen, so the main loop looks for them before anything
+// else sees the key, and they can't be chords.
+const GLOBAL_ACTIONS: &[Action] = &[Action::Suspend, Action::Interrupt];
+
+// Keys for moving through the text after a test, from `[review_keys]`.
+const REVIEW_BINDINGS: &[(ReviewAction, &[&str])] = &[
+    (ReviewAction::Left, &["left", "h"]),
+    (ReviewAction::Right, &["right", "l"]),
+    (ReviewAction::WordLeft, &["b"]),
+    (ReviewAction::WordRight, &["w"]),
+    (ReviewAction::Mark, &["v"]),
+    (ReviewAction::Copy, &["y"]),
+    (ReviewAction::Replay, &["p"]),
+    (ReviewAction::Hands, &["f"]),
+];
+
+// Keys of `ttt replay`, from `[playback_keys]`.
+const PLAYBACK_BINDINGS: &[(PlaybackAction, &[&str])] = &[
+    (PlaybackAction::Pause, &["space"]),
+    (PlaybackAction::Back, &["left"]),
+    (PlaybackAction::Forward, &["right"]),
+    (PlaybackAction::Faster, &["up", "plus"]),
+    (PlaybackAction::Slower, &["down", "-"]),
+    (PlaybackAction::Quit, &["esc", "q"]),
+];
+
+pub enum KeyResult<A> {
+    Action(A),
+    Pending,
+    Cancelled,
+    Unbound(KeyEvent),
+}
+
+// Maps single keys and multi-key chords (e.g. `<leader> r`) to screen specific
+// actions. Each screen owns a keymap over its own action type.
+pub struct Keymap<A> {
+    bindings: Vec<(Vec<KeyEvent>, A)>,
+    pending: Vec<KeyEvent>,
+}
+
+impl<A: Copy> Keymap<A> {
+    pub fn new() -> Self {
+        Self {
+            bindings: Vec::new(),
+            pending: Vec::new(),
+        }
+    }
+
+    pub fn bind(&mut self, keys: Vec<KeyEvent>, action: A) {
+        self.bindings.push((keys, action));
+    }
+
+    pub fn pending(&self) -> &[KeyEvent] {
+        &self.pending
+    }
+
+    // The first keys bound to `action`, written out for hints. `None` if the
+    // config left it without any.
+    pub fn describe(&self, action: A) -> Option<String>
+    where
+        A: PartialEq,
+    {
+        let (keys, _) = self.bindings.iter().find(|(_, a)| *a == action)?;
+        let keys: Vec<String> = keys.iter().map(format_key).collect();
+
+        Some(keys.join(" "))
+    }
+
+    pub fn feed(&mut self, key: KeyEvent) -> KeyResult<A> {
+        self.pending.push(KeyEvent::new(key.code, key.modifiers));
+
+        if let Some((_, action)) = self.bindings.iter().find(|(k, _)| *k == self.pending) {
+            self.pending.clear();
+
+            return KeyResult::Action(*action);
+        }
+
+        if self
+            .bindings
+            .iter()
+            .any(|(k, _)| k.starts_with(&self.pending))
+        {
+            return KeyResult::Pending;
+        }
+
+        let chord = self.pending.len() > 1;
+        self.pending.clear();
+
+        if chord {
+            KeyResult::Cancelled
+        } else {
+            KeyResult::Unbound(key)
+        }
+    }
+}
+
+// The keymaps of every screen, from the config.
+pub struct Keymaps {
+    pub test: Keymap<Action>,
+    pub review: Keymap<ReviewAction>,
+    pub playback: Keymap<PlaybackAction>,
+}
+
+pub fn load_keymaps(config: &Config) -> Result<Keymaps, String> {
+    let leader = parse_key(&config.leader)
+        .ok_or_else(|| format!("Invalid leader key in config: {}", config.leader))?;
+
+    let test = DEFAULT_BINDINGS
+        .iter()
+        .filter(|(action, _)| !GLOBAL_ACTIONS.contains(action))
+        .copied();
+
+    Ok(Keymaps {
+        test: build_keymap(test, leader, &config.keys, "keys")?,
+        review: build_keymap(
+            REVIEW_BINDINGS.iter().copied(),
+            leader,
+            &config.review_keys,
+            "review_keys",
+        )?,
+        playback: build_keymap(
+            PLAYBACK_BINDINGS.iter().copied(),
+            leader,
+            &config.playback_keys,
+            "playback_keys",
+        )?,
+    })
+}
+
+// Keys of the actions in GLOBAL_ACTIONS.
+pub fn global_keys(config: &Config) -> Result<Vec<(KeyEvent, Action)>, String> {
+    let mut keys = Vec::new();
+
+    for (action, defaults) in DEFAULT_BINDINGS {
+        if !GLOBAL_ACTIONS.contains(action) {
+            continue;
+        }
+
+        let bindings: Vec<&str> = match config.keys.get(action) {
+            Some(keys) => keys.iter().map(String::as_str).collect(),
+            None => defaults.to_vec(),
+        };
+
+        for binding in bindings {
+            let key = parse_key(binding).ok_or_else(|| {
+                format!(
+                    "Invalid key in [keys]: {} (only a single key here)",
+                    binding
+                )
+            })?;
+            keys.push((key, *action));
+        }
+    }
+
+    Ok(keys)
+}
+
+fn build_keymap<'a, A: Copy + Eq + Hash + 'a>(
+    defaults: impl Iterator<Item = (A, &'a [&'a str])>,
+    leader: KeyEvent,
+    overrides: &HashMap<A, Vec<String>>,
+    table: &str,
+) -> Result<Keymap<A>, String> {
+    let mut keymap = Keymap::new();
+
+    for (action, defaults) in defaults {
+        let bindings: Vec<&str> = match overrides.get(&action) {
+            Some(keys) => keys.iter().map(String::as_str).collect(),
+            None => defaults.to_vec(),
+        };
+
+        for binding in bindings {
+            let keys = parse_binding(binding, leader)
+                .ok_or_else(|| format!("Invalid key in [{}]: {}", table, binding))?;
+            keymap.bind(keys, action);
+        }
+    }
+
+    Ok(keymap)
+}
+
+// The action of a key that works on every screen, if it is one.
+pub fn global_action(global: &[(KeyEvent, Action)], key: KeyEvent) -> Option<Action> {
+    let key = KeyEvent::new(key.code, key.modifiers);
+
+    global
+        .iter()
+        .find(|(bound, _)| *bound == key)
+        .map(|(_, action)| *action)
+}
+
+fn parse_binding(s: &str, leader: KeyEvent) -> Option<Vec<KeyEvent>> {
+    let keys: Option<Vec<KeyEvent>> = s
+        .split_whitespace()
+        .map(|key| match key {
+            "<leader>" => Some(leader),
+            key => parse_key(key),
+        })
+        .collect();
+
+    keys.filter(|keys| !keys.is_empty())
+}
+
+pub fn parse_key(s: &str) -> Option<KeyEvent> {
+    let mut modifiers = KeyModifiers::NONE;
+    let mut parts: Vec<&str> = s.split('+').collect();
+    let key = parts.pop()?;
+
+    for part in parts {
+        modifiers |= match part.to_lowercase().as_str() {
+            "ctrl" => KeyModifiers::CONTROL,
+            "alt" => KeyModifiers::ALT,
+            "shift" => KeyModifiers::SHIFT,
+            _ => return None,
+        };
+    }
+
+    let code = match key.to_lowercase().as_str() {
+        "esc" | "escape" => KeyCode::Esc,
+        "enter" | "return" => KeyCode::Enter,
+        "tab" => KeyCode::Tab,
+        "backspace" => KeyCode::Backspace,
+        "space" => KeyCode::Char(' '),
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "plus" => KeyCode::Char('+'),
+        f if f.len() > 1 && f.starts_with('f') => KeyCode::F(f[1..].parse().ok()?),
+        _ => {
+            let mut chars = key.chars();
+            let ch = chars.next()?;
+            if chars.next().is_some() {
+                return None;
+            }
+
+            KeyCode::Char(ch)
+        }
+    };
+
+    Some(KeyEvent::new(code, modifiers))
+}
+
+pub fn format_key(key: &KeyEvent) -> String {
+    let mut out = String::new();
+
+    if key.modifiers.contains(KeyModifiers::CONTROL) {
+        out.push_str("Ctrl+");
+    }
+    if key.modifiers.contains(KeyModifiers::ALT) {
+        out.push_str("Alt+");
+    }
+    if key.modifiers.contains(KeyModifiers::SHIFT) {
+        out.push_str("Shift+");
+    }
+
+    match key.code {
+        KeyCode::Char(' ') => out.push_str("Space"),
+        KeyCode::Char(c) => out.push(c),
+        KeyCode::F(n) => out.push_str(&format!("F{}", n)),
+        code => out.push_str(&format!("{:?}", code)),
+    }
+
+    out
+}
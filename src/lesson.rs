@@ -0,0 +1,180 @@
+use crate::{
+    config::Config,
+    helpers::{parse_arg_list, parse_arg_list_with_text, split_args},
+    history::data_dir,
+    types::{Args, CourseLesson, Goal, TestResult},
+};
+
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    process,
+};
+
+// A lesson file is TOML with a name, the text to type (either literal `text`
+// or generator `options` in command line syntax) and the pass criteria.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LessonFile {
+    name: String,
+    text: Option<String>,
+    #[serde(default)]
+    options: String,
+    #[serde(default)]
+    min_wpm: f64,
+    #[serde(default)]
+    min_accuracy: f64,
+}
+
+pub fn load_lesson(path: &Path, config: &Config) -> Args {
+    let content = fs::read_to_string(path).unwrap_or_else(|e| {
+        eprintln!("Failed to read lesson at {}: {}", path.display(), e);
+
+        process::exit(1);
+    });
+
+    let lesson: LessonFile = toml::from_str(&content).unwrap_or_else(|e| {
+        eprintln!("Failed to parse lesson at {}: {}", path.display(), e);
+
+        process::exit(1);
+    });
+
+    let options = split_args(&lesson.options).into_iter();
+    let mut args = match lesson.text {
+        Some(text) => parse_arg_list_with_text(options, text.trim().to_string(), config),
+        None => parse_arg_list(options, config),
+    };
+
+    args.goal = Some(Goal {
+        name: lesson.name,
+        min_wpm: lesson.min_wpm,
+        min_accuracy: lesson.min_accuracy,
+        lesson: None,
+    });
+
+    args
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct LessonProgress {
+    pub passed: bool,
+    pub best_wpm: f64,
+    pub best_accuracy: f64,
+}
+
+// Saved per course directory; `position` is the lesson to resume at.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct CourseProgress {
+    pub position: usize,
+    pub lessons: HashMap<String, LessonProgress>,
+}
+
+fn progress_path() -> Option<PathBuf> {
+    data_dir().map(|dir| dir.join("courses.json"))
+}
+
+fn load_all_progress() -> HashMap<String, CourseProgress> {
+    progress_path()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+pub fn record_lesson_result(
+    lesson: &CourseLesson,
+    result: &TestResult,
+    passed: bool,
+) -> io::Result<()> {
+    let Some(path) = progress_path() else {
+        return Ok(());
+    };
+
+    let mut all = load_all_progress();
+    let course = all.entry(lesson.course.clone()).or_default();
+    let progress = course.lessons.entry(lesson.file.clone()).or_default();
+
+    progress.best_wpm = progress.best_wpm.max(result.wpm);
+    progress.best_accuracy = progress.best_accuracy.max(result.accuracy);
+
+    if passed {
+        progress.passed = true;
+        course.position = course.position.max(lesson.index + 1);
+    }
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    fs::write(path, serde_json::to_string(&all)?)
+}
+
+// Runs every `*.toml` lesson in the directory in file name order, so courses
+// are usually laid out as `01-home-row.toml`, `02-top-row.toml` and so on.
+// Picks up after the last lesson passed in an earlier session, or from the
+// start once the whole course has been passed.
+pub fn load_course(dir: &Path, config: &Config) -> Args {
+    let entries = fs::read_dir(dir).unwrap_or_else(|e| {
+        eprintln!(
+            "Failed to read course directory at {}: {}",
+            dir.display(),
+            e
+        );
+
+        process::exit(1);
+    });
+
+    let mut paths: Vec<_> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+        .collect();
+    paths.sort();
+
+    if paths.is_empty() {
+        eprintln!("Course at {} has no lessons", dir.display());
+
+        process::exit(1);
+    }
+
+    let course = fs::canonicalize(dir)
+        .unwrap_or_else(|_| dir.to_path_buf())
+        .display()
+        .to_string();
+    let total = paths.len();
+
+    let mut lessons: Vec<Args> = paths
+        .iter()
+        .enumerate()
+        .map(|(index, path)| {
+            let mut args = load_lesson(path, config);
+            if let Some(goal) = &mut args.goal {
+                goal.lesson = Some(CourseLesson {
+                    course: course.clone(),
+                    file: path
+                        .file_name()
+                        .map(|name| name.to_string_lossy().into_owned())
+                        .unwrap_or_default(),
+                    index,
+                    total,
+                });
+            }
+
+            args
+        })
+        .collect();
+
+    let position = load_all_progress()
+        .get(&course)
+        .map_or(0, |progress| progress.position);
+    if position < total {
+        lessons.drain(..position);
+    }
+
+    let mut first = lessons.remove(0);
+    first.playlist = lessons;
+
+    first
+}
@@ -0,0 +1,488 @@
+use base64::{Engine, engine::general_purpose::STANDARD};
+use serde::Serialize;
+use std::{
+    fmt,
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{Shutdown, TcpListener, TcpStream},
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex,
+        mpsc::{self, Sender},
+    },
+    thread,
+    time::Duration,
+};
+
+// Appended to a client's key to prove the server speaks WebSocket (RFC 6455).
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+const MAX_HEADER_LINES: usize = 100;
+const HANDSHAKE_TIMEOUT_MILLIS: u64 = 2000;
+const WRITE_TIMEOUT_MILLIS: u64 = 500;
+// Clients only ever send control frames, which are far shorter.
+const MAX_FRAME_BYTES: u64 = 4096;
+const OPCODE_TEXT: u8 = 0x1;
+const OPCODE_CLOSE: u8 = 0x8;
+const OPCODE_PING: u8 = 0x9;
+const OPCODE_PONG: u8 = 0xA;
+
+// What an overlay shows, sent as a JSON text message whenever it changes.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct LiveStats {
+    // waiting, typing, paused or finished.
+    pub state: &'static str,
+    pub wpm: f64,
+    pub accuracy: f64,
+    pub seconds: f64,
+    pub typed: usize,
+    pub total: usize,
+}
+
+impl fmt::Display for LiveStats {
+    // One line of key=value pairs, easy to pick apart in a shell.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "state={} wpm={:.1} accuracy={:.1} seconds={:.1} typed={} total={}",
+            self.state, self.wpm, self.accuracy, self.seconds, self.typed, self.total
+        )
+    }
+}
+
+// A WebSocket server on localhost that pushes the live stats to every
+// connected client, e.g. an OBS browser source. Clients are written to on a
+// thread of their own so a slow one never holds up the test. Each client also
+// has a thread reading from it, to answer pings and closes and to notice
+// when it has gone.
+pub struct StatsServer {
+    updates: Sender<String>,
+    last: Option<LiveStats>,
+}
+
+// Writes to a client are made with the list locked, so frames from the
+// broadcast and from a client's own thread never interleave.
+type Clients = Arc<Mutex<Vec<(usize, TcpStream)>>>;
+
+impl StatsServer {
+    pub fn start(port: u16) -> io::Result<Self> {
+        let listener = TcpListener::bind(("127.0.0.1", port))?;
+        let clients: Clients = Arc::default();
+        let latest = Arc::new(Mutex::new(None::<String>));
+
+        {
+            let clients = Arc::clone(&clients);
+            let latest = Arc::clone(&latest);
+
+            thread::spawn(move || {
+                for (id, stream) in listener.incoming().enumerate() {
+                    let Ok(stream) = stream else {
+                        continue;
+                    };
+                    let clients = Arc::clone(&clients);
+                    let latest = Arc::clone(&latest);
+
+                    thread::spawn(move || serve_client(id, stream, &clients, &latest));
+                }
+            });
+        }
+
+        let (updates, incoming) = mpsc::channel::<String>();
+        thread::spawn(move || {
+            for message in incoming {
+                *latest.lock().unwrap_or_else(|e| e.into_inner()) = Some(message.clone());
+
+                let frame = text_frame(&message);
+                clients
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .retain_mut(|(_, client)| client.write_all(&frame).is_ok());
+            }
+        });
+
+        Ok(Self {
+            updates,
+            last: None,
+        })
+    }
+
+    pub fn publish(&mut self, stats: LiveStats) {
+        if self.last.as_ref() == Some(&stats) {
+            return;
+        }
+
+        if let Ok(message) = serde_json::to_string(&stats) {
+            let _ = self.updates.send(message);
+        }
+        self.last = Some(stats);
+    }
+}
+
+fn serve_client(id: usize, stream: TcpStream, clients: &Clients, latest: &Mutex<Option<String>>) {
+    let Ok((mut out, mut reader)) = handshake(stream) else {
+        return;
+    };
+
+    {
+        let mut clients = clients.lock().unwrap_or_else(|e| e.into_inner());
+
+        // Newcomers get the stats as they are right away.
+        let current = latest.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        if let Some(message) = current
+            && out.write_all(&text_frame(&message)).is_err()
+        {
+            return;
+        }
+
+        clients.push((id, out));
+    }
+
+    // Whatever else a client sends is of no interest.
+    while let Ok((opcode, payload)) = read_frame(&mut reader) {
+        let reply = match opcode {
+            OPCODE_PING => frame(OPCODE_PONG, &payload),
+            OPCODE_CLOSE => frame(OPCODE_CLOSE, &payload),
+            _ => continue,
+        };
+
+        let mut clients = clients.lock().unwrap_or_else(|e| e.into_inner());
+        let Some((_, client)) = clients.iter_mut().find(|(client, _)| *client == id) else {
+            break;
+        };
+        if client.write_all(&reply).is_err() || opcode == OPCODE_CLOSE {
+            break;
+        }
+    }
+
+    let mut clients = clients.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(i) = clients.iter().position(|(client, _)| *client == id) {
+        let (_, client) = clients.remove(i);
+        let _ = client.shutdown(Shutdown::Both);
+    }
+}
+
+// Reads the HTTP upgrade request and answers it. Anything but a WebSocket
+// request is turned away, as is a client too slow to send one. Returns the
+// stream to write to and a reader for what the client sends next.
+fn handshake(stream: TcpStream) -> io::Result<(TcpStream, BufReader<TcpStream>)> {
+    stream.set_read_timeout(Some(Duration::from_millis(HANDSHAKE_TIMEOUT_MILLIS)))?;
+    stream.set_write_timeout(Some(Duration::from_millis(WRITE_TIMEOUT_MILLIS)))?;
+    let mut out = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+
+    let mut key = None;
+    for _ in 0..MAX_HEADER_LINES {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            break;
+        }
+
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+
+        if let Some((name, value)) = line.split_once(':')
+            && name.trim().eq_ignore_ascii_case("sec-websocket-key")
+        {
+            key = Some(value.trim().to_string());
+        }
+    }
+
+    let Some(key) = key else {
+        out.write_all(b"HTTP/1.1 426 Upgrade Required\r\nContent-Length: 0\r\n\r\n")?;
+
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "not a WebSocket request",
+        ));
+    };
+
+    write!(
+        out,
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+         Sec-WebSocket-Accept: {}\r\n\r\n",
+        accept_key(&key)
+    )?;
+
+    // Clients can stay quiet for as long as they like once connected.
+    reader.get_ref().set_read_timeout(None)?;
+
+    Ok((out, reader))
+}
+
+fn accept_key(key: &str) -> String {
+    STANDARD.encode(sha1(format!("{}{}", key, WEBSOCKET_GUID).as_bytes()))
+}
+
+// One frame from a client, unmasked: its opcode and payload. Fragments of a
+// longer message come as separate frames.
+fn read_frame(reader: &mut impl Read) -> io::Result<(u8, Vec<u8>)> {
+    let mut head = [0u8; 2];
+    reader.read_exact(&mut head)?;
+
+    let len = match head[1] & 0x7f {
+        126 => {
+            let mut len = [0u8; 2];
+            reader.read_exact(&mut len)?;
+            u16::from_be_bytes(len) as u64
+        }
+        127 => {
+            let mut len = [0u8; 8];
+            reader.read_exact(&mut len)?;
+            u64::from_be_bytes(len)
+        }
+        len => len as u64,
+    };
+    if len > MAX_FRAME_BYTES {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "frame too long"));
+    }
+
+    let mut mask = [0u8; 4];
+    if head[1] & 0x80 != 0 {
+        reader.read_exact(&mut mask)?;
+    }
+
+    let mut payload = vec![0u8; len as usize];
+    reader.read_exact(&mut payload)?;
+    for (i, byte) in payload.iter_mut().enumerate() {
+        *byte ^= mask[i % 4];
+    }
+
+    Ok((head[0] & 0x0f, payload))
+}
+
+fn text_frame(message: &str) -> Vec<u8> {
+    frame(OPCODE_TEXT, message.as_bytes())
+}
+
+// A single unmasked frame, as servers send them.
+fn frame(opcode: u8, payload: &[u8]) -> Vec<u8> {
+    let mut frame = vec![0x80 | opcode];
+
+    match payload.len() {
+        len if len < 126 => frame.push(len as u8),
+        len if len <= u16::MAX as usize => {
+            frame.push(126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+
+    frame.extend_from_slice(payload);
+
+    frame
+}
+
+// SHA-1 is only needed for the handshake, which is small enough to do here.
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+
+    for block in message.chunks(64) {
+        let mut w = [0u32; 80];
+        for (i, word) in block.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(*word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+
+        for (h, v) in h.iter_mut().zip([a, b, c, d, e]) {
+            *h = h.wrapping_add(v);
+        }
+    }
+
+    let mut digest = [0u8; 20];
+    for (i, word) in h.iter().enumerate() {
+        digest[i * 4..i * 4 + 4].copy_from_slice(&word.to_be_bytes());
+    }
+
+    digest
+}
+
+// -stats-file: the live stats as a single line at PATH. A regular file is
+// replaced whole on each change, so a reader never sees half a line; a named
+// pipe gets a line per change, for as long as someone reads it.
+pub struct StatsFile {
+    updates: Sender<String>,
+    last: Option<LiveStats>,
+}
+
+impl StatsFile {
+    pub fn open(path: &str) -> io::Result<Self> {
+        let path = PathBuf::from(path);
+        let pipe = is_fifo(&path);
+        // Next to PATH, named after all of it so no other file is touched.
+        let mut temp = path.clone().into_os_string();
+        temp.push(".tmp");
+        if !pipe {
+            fs::write(&path, "")?;
+        }
+
+        let (updates, incoming) = mpsc::channel::<String>();
+        thread::spawn(move || {
+            let mut out: Option<File> = None;
+
+            while let Ok(mut line) = incoming.recv() {
+                // Only the newest line matters if writing fell behind.
+                while let Ok(newer) = incoming.try_recv() {
+                    line = newer;
+                }
+                line.push('\n');
+
+                if !pipe {
+                    let _ = fs::write(&temp, &line).and_then(|_| fs::rename(&temp, &path));
+
+                    continue;
+                }
+
+                // Opening a pipe waits for a reader, and a reader going away
+                // closes it until the next one comes.
+                if out.is_none() {
+                    out = OpenOptions::new().write(true).open(&path).ok();
+                }
+                if let Some(file) = &mut out
+                    && file.write_all(line.as_bytes()).is_err()
+                {
+                    out = None;
+                }
+            }
+        });
+
+        Ok(Self {
+            updates,
+            last: None,
+        })
+    }
+
+    pub fn publish(&mut self, stats: LiveStats) {
+        if self.last.as_ref() == Some(&stats) {
+            return;
+        }
+
+        let _ = self.updates.send(stats.to_string());
+        self.last = Some(stats);
+    }
+}
+
+#[cfg(unix)]
+fn is_fifo(path: &Path) -> bool {
+    use std::os::unix::fs::FileTypeExt;
+
+    fs::metadata(path).is_ok_and(|m| m.file_type().is_fifo())
+}
+
+#[cfg(not(unix))]
+fn is_fifo(_path: &Path) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    #[test]
+    fn sha1_vectors() {
+        assert_eq!(hex(&sha1(b"")), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+        assert_eq!(
+            hex(&sha1(b"abc")),
+            "a9993e364706816aba3e25717850c26c9cd0d89d"
+        );
+        assert_eq!(
+            hex(&sha1(b"The quick brown fox jumps over the lazy dog")),
+            "2fd4e1c67a2d28fced849ee1bb76e7391b93eb12"
+        );
+        // Two blocks once padded.
+        assert_eq!(
+            hex(&sha1(
+                b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"
+            )),
+            "84983e441c3bd26ebaae4aa1f95129e5e54670f1"
+        );
+    }
+
+    // The example handshake of RFC 6455, section 1.3.
+    #[test]
+    fn accept_key_of_rfc_example() {
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+    }
+
+    // The frames of RFC 6455, section 5.7.
+    #[test]
+    fn text_frames() {
+        assert_eq!(text_frame("Hello"), b"\x81\x05Hello");
+
+        let long = "x".repeat(256);
+        let frame = text_frame(&long);
+        assert_eq!(frame[..4], [0x81, 126, 0x01, 0x00]);
+        assert_eq!(frame.len(), 4 + 256);
+
+        let huge = "x".repeat(65536);
+        let frame = text_frame(&huge);
+        assert_eq!(frame[..10], [0x81, 127, 0, 0, 0, 0, 0, 1, 0, 0]);
+        assert_eq!(frame.len(), 10 + 65536);
+    }
+
+    #[test]
+    fn masked_client_frames() {
+        let mut hello: &[u8] = b"\x81\x85\x37\xfa\x21\x3d\x7f\x9f\x4d\x51\x58";
+        assert_eq!(
+            read_frame(&mut hello).unwrap(),
+            (OPCODE_TEXT, b"Hello".to_vec())
+        );
+
+        let mut ping: &[u8] = b"\x89\x85\x37\xfa\x21\x3d\x7f\x9f\x4d\x51\x58";
+        assert_eq!(
+            read_frame(&mut ping).unwrap(),
+            (OPCODE_PING, b"Hello".to_vec())
+        );
+        assert_eq!(frame(OPCODE_PONG, b"Hello"), b"\x8a\x05Hello");
+    }
+
+    #[test]
+    fn overlong_and_cut_off_frames() {
+        let mut overlong: &[u8] = &[0x81, 127, 0, 0, 0, 0, 0, 1, 0, 0];
+        assert!(read_frame(&mut overlong).is_err());
+
+        let mut cut_off: &[u8] = b"\x81\x85\x37\xfa";
+        assert!(read_frame(&mut cut_off).is_err());
+    }
+}
@@ -1,60 +1,319 @@
 mod app;
+mod bookmark;
+mod bot;
+mod bundle;
+mod cache;
+mod cast;
+mod cli;
+mod command;
+mod config;
+mod drill;
+mod events;
+mod export;
+mod fetch;
+mod fingers;
+mod generator;
+mod ghost;
 mod helpers;
+mod highlight;
+mod history;
+mod history_screen;
+mod importers;
+mod keymap;
+mod lesson;
+mod live;
+mod markup;
+mod popup;
+mod provider;
+mod race;
+mod recording;
+mod replay;
+mod results;
+mod screen;
+mod settings;
+mod signals;
+#[cfg(feature = "tts")]
+mod speech;
+mod stats;
+mod tour;
+mod training;
 mod types;
 
-use crate::{app::App, helpers::parse_args};
+use crate::{
+    app::App,
+    bundle::run_verify_bundle,
+    cast::run_export_cast,
+    cli::{Command, TestOptions, parse_cli},
+    config::{load_config, run_config},
+    events::EventSink,
+    export::run_history,
+    helpers::resolve_test_options,
+    importers::import_file,
+    keymap::{global_action, global_keys, load_keymaps},
+    live::{StatsFile, StatsServer},
+    race::{Race, host_relay, race_options, room_seed, run_relay},
+    recording::{load_replay, playback_args},
+    screen::{ScreenGuard, ScreenMode},
+    signals::{Signals, suspend},
+    stats::run_stats,
+    types::Action,
+};
 
 use ratatui::{
-    crossterm::{
-        event::{self, Event, KeyCode},
-        execute,
-        terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
-    },
+    crossterm::event::{self, Event},
     prelude::*,
 };
-use std::{io, time::Duration};
+use std::{
+    env,
+    io::{self, Write},
+    process,
+    time::Duration,
+};
 
-const DEFAULT_WORD_COUNT: usize = 512;
-const DEFAULT_SECONDS: usize = 60;
 const POLLING_RATE_MS: u64 = 16;
+// 128 + SIGINT, as a shell reports it.
+const INTERRUPTED_EXIT_CODE: i32 = 130;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let (count, seconds, source) = parse_args();
+    let cli = parse_cli();
+
+    // Commands that don't take a test are done before the config is read.
+    match cli.command {
+        Some(Command::Import { from, path }) => import_file(from, &path),
+        Some(Command::Stats(options)) => run_stats(options),
+        Some(Command::History { command }) => run_history(command),
+        Some(Command::VerifyBundle { file }) => run_verify_bundle(&file),
+        Some(Command::Config { command }) => run_config(command),
+        Some(Command::Relay { port }) => run_relay(port),
+        command => return run_test(command, cli.test),
+    }
+
+    Ok(())
+}
+
+fn run_test(command: Option<Command>, test: TestOptions) -> Result<(), Box<dyn std::error::Error>> {
+    let config = load_config();
+
+    let (mut args, race, playback) = match command {
+        Some(Command::ExportCast { replay, out }) => {
+            run_export_cast(&replay, &out, &config);
+
+            return Ok(());
+        }
+        Some(Command::Replay { file }) => {
+            let recording = load_replay(&file);
+
+            (playback_args(&recording, &config), None, Some(recording))
+        }
+        Some(Command::Race(race_args)) => {
+            let (mut options, test) = race_options(race_args, config.relay.clone());
+            let mut args = resolve_test_options(test, &config, false);
+            // A hosted race gets new words every time, which its relay hands
+            // to whoever joins. A room on a shared relay is seeded by its code.
+            let seed = args.seed.unwrap_or_else(|| match options.host {
+                Some(_) => rand::random(),
+                None => room_seed(&options.room),
+            });
+
+            if let Some(port) = options.host {
+                let port = host_relay(port, options.offline, seed).unwrap_or_else(|e| {
+                    eprintln!("Failed to host a race on port {}: {}", port, e);
+
+                    process::exit(1);
+                });
+
+                options.host = Some(port);
+                options.relay = format!("127.0.0.1:{}", port);
+            }
+
+            let race = Race::connect(&options, seed).unwrap_or_else(|e| {
+                eprintln!(
+                    "Failed to join room {} on {}: {}",
+                    options.room, options.relay, e
+                );
+
+                process::exit(1);
+            });
+
+            args.seed = Some(race.seed());
+
+            (args, Some(race), None)
+        }
+        Some(Command::Run(test)) => (resolve_test_options(test, &config, true), None, None),
+        _ => (resolve_test_options(test, &config, true), None, None),
+    };
+
+    let events = args.events.take().map(|target| {
+        EventSink::open(&target).unwrap_or_else(|e| {
+            eprintln!("Failed to open event stream: {}", e);
+
+            process::exit(1);
+        })
+    });
+
+    let stats_server = args.serve_stats.map(|port| {
+        StatsServer::start(port).unwrap_or_else(|e| {
+            eprintln!("Failed to serve stats on port {}: {}", port, e);
+
+            process::exit(1);
+        })
+    });
+
+    let stats_file = args.stats_file.take().map(|path| {
+        StatsFile::open(&path).unwrap_or_else(|e| {
+            eprintln!("Failed to open stats file {}: {}", path, e);
+
+            process::exit(1);
+        })
+    });
+
+    let (keymaps, global_keys) = match (load_keymaps(&config), global_keys(&config)) {
+        (Ok(keymaps), Ok(global_keys)) => (keymaps, global_keys),
+        (Err(e), _) | (_, Err(e)) => {
+            eprintln!("{}", e);
+
+            process::exit(1);
+        }
+    };
+
+    let signals = Signals::register()?;
 
-    enable_raw_mode()?;
-    let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen)?;
+    // With -json, stdout is left for the results, so the screen goes to stderr.
+    let mode = ScreenMode {
+        stderr: args.json,
+        mouse: config.mouse,
+        caret: config.caret,
+        caret_blink: config.caret_blink,
+    };
+    let screen: Box<dyn Write> = if mode.stderr {
+        Box::new(io::stderr())
+    } else {
+        Box::new(io::stdout())
+    };
 
-    let backend = CrosstermBackend::new(stdout);
+    // The first text is picked before the screen is taken over, so a source
+    // with nothing to offer is reported on a usable terminal.
+    let mut app = App::new(args, keymaps, &config);
+
+    let guard = ScreenGuard::enter(mode)?;
+
+    let backend = CrosstermBackend::new(screen);
     let mut terminal = Terminal::new(backend)?;
 
-    let mut app = App::new(
-        source,
-        if count > 0 { count } else { DEFAULT_WORD_COUNT },
-        if seconds > 0 {
-            seconds
-        } else {
-            DEFAULT_SECONDS
-        },
-    );
+    if let Some(events) = events {
+        app.set_event_sink(events);
+    }
+    if let Some(server) = stats_server {
+        app.set_stats_server(server);
+    }
+    if let Some(file) = stats_file {
+        app.set_stats_file(file);
+    }
+    if let Some(race) = race {
+        app.set_race(race);
+    }
+    if let Some(recording) = playback {
+        app.set_playback(recording, true);
+    }
+
+    let dumb_terminal = env::var("TERM").is_ok_and(|term| term == "dumb");
+    if dumb_terminal
+        || terminal.show_cursor().is_err()
+        || terminal.set_cursor_position(Position::ORIGIN).is_err()
+    {
+        app.use_soft_cursor();
+    }
+
+    let exit_code = loop {
+        // Terminals that can't position the cursor get a drawn caret instead.
+        if let Err(e) = terminal.draw(|frame| app.draw_ui(frame)) {
+            // The terminal is put back before the error is printed over it.
+            if app.soft_cursor() {
+                drop(terminal);
+                drop(guard);
+
+                return Err(e.into());
+            }
 
-    loop {
-        terminal.draw(|frame| app.draw_ui(frame))?;
-        terminal.show_cursor()?;
+            app.use_soft_cursor();
+
+            continue;
+        }
+
+        if !app.soft_cursor() && terminal.show_cursor().is_err() {
+            app.use_soft_cursor();
+        }
 
+        // Bells and clipboard copies go to the screen, which with -json isn't
+        // stdout.
+        let output = app.take_terminal_output();
+        if !output.is_empty() {
+            let screen = terminal.backend_mut();
+            screen.write_all(output.as_bytes())?;
+            Write::flush(screen)?;
+        }
+
+        let mut suspended = signals.take_suspend();
         if event::poll(Duration::from_millis(POLLING_RATE_MS))? {
-            if let Event::Key(key) = event::read()? {
-                match key.code {
-                    KeyCode::Esc => break,
+            match event::read()? {
+                // Raw mode turns Ctrl+C into a key, which quits like SIGINT.
+                Event::Key(key) => match global_action(&global_keys, key) {
+                    Some(Action::Suspend) => suspended = true,
+                    Some(Action::Interrupt) => break Some(INTERRUPTED_EXIT_CODE),
                     _ => app.handle_key(key),
-                }
+                },
+                Event::Mouse(mouse) => app.handle_mouse(mouse),
+                Event::FocusLost => app.focus_lost(),
+                Event::FocusGained => app.focus_gained(),
+                _ => {}
             }
         }
+
+        // The shell gets its terminal back while we're stopped, and the test
+        // stays paused until a key after we're continued.
+        if suspended {
+            app.pause_now();
+            mode.leave();
+            suspend()?;
+            signals.take_resume();
+            mode.enter()?;
+            terminal.clear()?;
+        } else if signals.take_resume() {
+            // Stopped by SIGSTOP, which can't be caught, so the terminal may
+            // have been reset under us in the meantime.
+            app.pause_now();
+            mode.enter()?;
+            terminal.clear()?;
+        }
+
+        app.tick();
+
+        if let Some(code) = signals.exit_code() {
+            break Some(code);
+        }
+
+        if app.should_quit() {
+            break None;
+        }
+    };
+
+    drop(guard);
+
+    if let Some(code) = exit_code {
+        if let Err(e) = app.save_partial() {
+            eprintln!("Failed to save results: {}", e);
+        }
+        app.print_results();
+
+        process::exit(code);
     }
 
-    disable_raw_mode()?;
-    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
-    terminal.show_cursor()?;
+    app.print_results();
+
+    let code = app.exit_code();
+    if code != 0 {
+        process::exit(code);
+    }
 
     Ok(())
 }
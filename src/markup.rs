@@ -0,0 +1,330 @@
+// Turns Markdown or HTML into the prose it renders as, so only the words are
+// left to type. This is a best effort: unusual markup may leave some syntax
+// behind, but never drops words.
+pub fn strip_markup(content: &str) -> String {
+    let text = if looks_like_html(content) {
+        strip_html(content)
+    } else {
+        content.to_string()
+    };
+
+    strip_markdown(&text)
+}
+
+fn looks_like_html(content: &str) -> bool {
+    let lower = content.to_lowercase();
+
+    ["<html", "<body", "<p>", "<p ", "<div", "<br", "</a>"]
+        .iter()
+        .any(|tag| lower.contains(tag))
+}
+
+// Block-level tags become paragraph breaks; the contents of script, style and
+// head are dropped.
+fn strip_html(content: &str) -> String {
+    const HIDDEN: &[&str] = &["script", "style", "head"];
+
+    let mut out = String::new();
+    let mut rest = content;
+
+    while let Some(start) = rest.find('<') {
+        out.push_str(&rest[..start]);
+
+        // A `<` that can't start a tag is just text.
+        let opens_tag = rest[start + 1..]
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '/' || c == '!');
+        if !opens_tag {
+            out.push('<');
+            rest = &rest[start + 1..];
+
+            continue;
+        }
+
+        let Some(end) = rest[start..].find('>') else {
+            break;
+        };
+
+        let tag = &rest[start + 1..start + end];
+        rest = &rest[start + end + 1..];
+
+        let closing = tag.starts_with('/');
+        let name = tag
+            .trim_start_matches('/')
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+
+        if !closing && HIDDEN.contains(&name.as_str()) {
+            // Skip straight to the closing tag, `<` in a script is no tag.
+            let close = format!("</{}", name);
+            rest = match rest.to_ascii_lowercase().find(&close) {
+                Some(pos) => &rest[pos..],
+                None => "",
+            };
+        } else if is_block(&name) {
+            out.push_str("\n\n");
+        }
+    }
+
+    out.push_str(rest);
+
+    // Source line breaks mean nothing in HTML, only the block breaks do.
+    let paragraphs: Vec<String> = decode_entities(&out)
+        .split("\n\n")
+        .map(|p| p.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|p| !p.is_empty())
+        .collect();
+
+    paragraphs.join("\n\n")
+}
+
+fn is_block(name: &str) -> bool {
+    const BLOCKS: &str = "p div br li ul ol tr table blockquote pre section article hr";
+
+    BLOCKS.split(' ').any(|block| block == name)
+        || (name.len() == 2 && name.starts_with('h') && name.as_bytes()[1].is_ascii_digit())
+}
+
+fn decode_entities(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let decoded = rest[1..].find(';').filter(|&end| end <= 8).and_then(|end| {
+            let name = &rest[1..end + 1];
+            let ch = match name {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "nbsp" => Some(' '),
+                "mdash" => Some('—'),
+                "ndash" => Some('–'),
+                "hellip" => Some('…'),
+                _ => name
+                    .strip_prefix("#x")
+                    .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                    .or_else(|| name.strip_prefix('#').and_then(|dec| dec.parse().ok()))
+                    .and_then(char::from_u32),
+            };
+
+            ch.map(|ch| (ch, end + 2))
+        });
+
+        match decoded {
+            Some((ch, len)) => {
+                out.push(ch);
+                rest = &rest[len..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    out.push_str(rest);
+
+    out
+}
+
+// Line by line: fenced code, rules and link definitions go, block markers
+// (headings, quotes, list bullets) are cut off, and inline syntax is unwrapped.
+fn strip_markdown(text: &str) -> String {
+    let mut lines = Vec::new();
+    let mut fenced = false;
+
+    for line in text.lines() {
+        let trimmed = line.trim();
+
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            fenced = !fenced;
+
+            continue;
+        }
+
+        if fenced || is_rule(trimmed) || is_link_definition(trimmed) {
+            continue;
+        }
+
+        lines.push(strip_inline(strip_block_marker(trimmed)));
+    }
+
+    lines.join("\n")
+}
+
+fn is_rule(line: &str) -> bool {
+    let compact: String = line.chars().filter(|c| !c.is_whitespace()).collect();
+
+    compact.len() >= 3
+        && ['-', '*', '_', '=']
+            .iter()
+            .any(|&c| compact.chars().all(|x| x == c))
+}
+
+fn is_link_definition(line: &str) -> bool {
+    line.starts_with('[') && line.contains("]:")
+}
+
+fn strip_block_marker(mut line: &str) -> &str {
+    loop {
+        let next = if let Some(rest) = line.strip_prefix('>') {
+            rest.trim_start()
+        } else if line.starts_with('#') {
+            let rest = line.trim_start_matches('#');
+            if !rest.is_empty() && !rest.starts_with(' ') {
+                return line;
+            }
+
+            rest.trim_start()
+        } else if let Some(rest) = ["- ", "* ", "+ "].iter().find_map(|m| line.strip_prefix(m)) {
+            rest.trim_start()
+        } else {
+            let digits = line.chars().take_while(char::is_ascii_digit).count();
+            match line[digits..].strip_prefix(". ") {
+                Some(rest) if digits > 0 => rest.trim_start(),
+                _ => return line,
+            }
+        };
+
+        line = next;
+    }
+}
+
+// Links and images keep their text, emphasis and code markers are dropped.
+// Underscores inside words (snake_case) are left alone, and so are `*`, `~` and
+// backticks without a closing run to match, as in `2 * 3`.
+fn strip_inline(line: &str) -> String {
+    let chars: Vec<char> = line.chars().collect();
+    let mut out = String::with_capacity(line.len());
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+
+        if c == '!' && chars.get(i + 1) == Some(&'[') {
+            i += 1;
+
+            continue;
+        }
+
+        if c == '['
+            && let Some(close) = find_char(&chars, i + 1, ']')
+            && chars.get(close + 1) == Some(&'(')
+            && let Some(paren) = find_char(&chars, close + 2, ')')
+        {
+            out.push_str(&strip_inline(
+                &chars[i + 1..close].iter().collect::<String>(),
+            ));
+            i = paren + 1;
+
+            continue;
+        }
+
+        if c == '*' || c == '`' || c == '~' {
+            let run = chars[i..].iter().take_while(|&&d| d == c).count();
+
+            match find_closing(&chars, i, run) {
+                Some(close) => {
+                    let inner: String = chars[i + run..close].iter().collect();
+                    // Code is kept as written, markup and all.
+                    if c == '`' {
+                        out.push_str(&inner);
+                    } else {
+                        out.push_str(&strip_inline(&inner));
+                    }
+                    i = close + run;
+                }
+                None => {
+                    out.extend(&chars[i..i + run]);
+                    i += run;
+                }
+            }
+
+            continue;
+        }
+
+        if c == '_' {
+            let inside = i > 0
+                && chars[i - 1].is_alphanumeric()
+                && chars.get(i + 1).is_some_and(|n| n.is_alphanumeric());
+            if !inside {
+                i += 1;
+
+                continue;
+            }
+        }
+
+        out.push(c);
+        i += 1;
+    }
+
+    out
+}
+
+// Where the run of `run` delimiters at `open` is closed by another run just as
+// long. Emphasis has to hug its text on both sides; code spans don't.
+fn find_closing(chars: &[char], open: usize, run: usize) -> Option<usize> {
+    let c = chars[open];
+    let start = open + run;
+    let hugs = |i: usize| c == '`' || !chars[i].is_whitespace();
+
+    if start >= chars.len() || !hugs(start) {
+        return None;
+    }
+
+    let mut i = start + 1;
+    while i < chars.len() {
+        if chars[i] != c {
+            i += 1;
+
+            continue;
+        }
+
+        let len = chars[i..].iter().take_while(|&&d| d == c).count();
+        if len == run && hugs(i - 1) {
+            return Some(i);
+        }
+        i += len;
+    }
+
+    None
+}
+
+fn find_char(chars: &[char], from: usize, target: char) -> Option<usize> {
+    chars
+        .iter()
+        .skip(from)
+        .position(|&c| c == target)
+        .map(|p| p + from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_matched_delimiters() {
+        assert_eq!(
+            strip_inline("**bold** and *it* ~~gone~~"),
+            "bold and it gone"
+        );
+        assert_eq!(strip_inline("run `a * b` here"), "run a * b here");
+        assert_eq!(strip_inline("***both***"), "both");
+    }
+
+    #[test]
+    fn keeps_unmatched_delimiters() {
+        assert_eq!(strip_inline("2 * 3 * 4"), "2 * 3 * 4");
+        assert_eq!(strip_inline("about ~5 km"), "about ~5 km");
+        assert_eq!(strip_inline("a ` b"), "a ` b");
+        assert_eq!(strip_inline("*open but not closed"), "*open but not closed");
+    }
+}
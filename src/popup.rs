@@ -0,0 +1,144 @@
+use crate::{history_screen::HistoryScreen, settings::SettingsScreen};
+
+use ratatui::{
+    crossterm::event::{KeyCode, KeyEvent},
+    prelude::*,
+    widgets::*,
+};
+use std::time::Instant;
+
+pub fn centered_rect(width: u16, height: u16, area: Rect) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+
+    Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    }
+}
+
+// Returns where the popup went, for telling mouse clicks on it apart.
+pub fn render_popup(f: &mut Frame, title: &str, body: Vec<Line>, footer: &str) -> Rect {
+    let width = body
+        .iter()
+        .map(|l| l.width() as u16)
+        .chain([title.len() as u16, footer.len() as u16])
+        .max()
+        .unwrap_or(0)
+        + 4;
+
+    // Lines too long for the screen wrap, and the popup grows to fit them.
+    let inner_width = width.min(f.area().width).saturating_sub(4).max(1);
+    let height = body
+        .iter()
+        .map(|l| (l.width() as u16).div_ceil(inner_width).max(1))
+        .sum::<u16>()
+        + 2;
+
+    let area = centered_rect(width, height, f.area());
+
+    let block = Block::default()
+        .title(title.to_string())
+        .title_bottom(Line::from(footer.to_string()).right_aligned())
+        .borders(Borders::ALL);
+
+    let paragraph = Paragraph::new(body)
+        .block(block.padding(Padding::horizontal(1)))
+        .wrap(Wrap { trim: false });
+
+    f.render_widget(Clear, area);
+    f.render_widget(paragraph, area);
+
+    area
+}
+
+// A popup that holds the focus: the screen behind it is dimmed, and the app
+// hands it every key until it is answered. Popups that only show what is
+// going on, like the race lobby or the countdown, are drawn with
+// `render_popup` and leave the keys alone.
+pub enum Dialog {
+    // Any key but Esc goes on with the test; Esc asks to abandon it. A test
+    // paused by losing focus also goes on by itself at `resume_at`.
+    Paused {
+        resume_at: Option<Instant>,
+    },
+    // Whether to abandon the test in progress. `resume` if asking paused it.
+    Quit {
+        resume: bool,
+    },
+    Error(String),
+    Settings {
+        screen: SettingsScreen,
+        resume: bool,
+    },
+    History(HistoryScreen),
+}
+
+pub enum Answer {
+    Yes,
+    No,
+}
+
+impl Dialog {
+    pub fn answer(&mut self, key: KeyEvent) -> Option<Answer> {
+        match (self, key.code) {
+            (Dialog::Settings { screen, .. }, _) => screen
+                .handle_key(key)
+                .map(|save| if save { Answer::Yes } else { Answer::No }),
+            (Dialog::History(screen), _) => screen.handle_key(key).then_some(Answer::No),
+            (Dialog::Paused { .. }, KeyCode::Esc) => Some(Answer::No),
+            (Dialog::Paused { .. }, _) => Some(Answer::Yes),
+            (Dialog::Quit { .. }, KeyCode::Char('y') | KeyCode::Enter) => Some(Answer::Yes),
+            (Dialog::Quit { .. }, KeyCode::Char('n') | KeyCode::Esc) => Some(Answer::No),
+            (Dialog::Error(_), KeyCode::Enter | KeyCode::Esc | KeyCode::Char(' ')) => {
+                Some(Answer::No)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn render(&self, f: &mut Frame) -> Rect {
+        let area = f.area();
+        f.buffer_mut()
+            .set_style(area, Style::default().add_modifier(Modifier::DIM));
+
+        match self {
+            Dialog::Paused { resume_at } => {
+                let mut body = vec![Line::from("The clock is stopped and the text hidden.")];
+                if let Some(resume_at) = resume_at {
+                    let left = resume_at.saturating_duration_since(Instant::now());
+                    body.push(Line::from(""));
+                    body.push(Line::from(format!("Resuming in {}", left.as_secs() + 1)).centered());
+                }
+
+                render_popup(f, "Paused", body, " any key: resume | Esc: quit ")
+            }
+            Dialog::Quit { .. } => render_popup(
+                f,
+                "Abandon test?",
+                vec![Line::from("What was typed so far won't be saved.")],
+                " y: quit | n: keep typing ",
+            ),
+            Dialog::Error(message) => render_popup(
+                f,
+                "Error",
+                vec![Line::from(message.as_str())],
+                " Enter: close ",
+            ),
+            Dialog::Settings { screen, .. } => render_popup(
+                f,
+                "Settings",
+                screen.lines(),
+                " Up/Down: pick | Left/Right: change | Enter: save | Esc: cancel ",
+            ),
+            Dialog::History(screen) => render_popup(
+                f,
+                "History",
+                screen.lines(),
+                " Up/Down: scroll | Esc: close ",
+            ),
+        }
+    }
+}
@@ -0,0 +1,281 @@
+use crate::drill::charset_word;
+
+use memmap2::Mmap;
+use rand::{Rng, RngCore};
+use std::{cell::OnceCell, fs::File, io};
+
+pub trait TextProvider {
+    fn len(&self) -> usize;
+
+    fn get(&self, index: usize) -> String;
+
+    fn sample(&self, rng: &mut dyn RngCore) -> String {
+        self.get(rng.random_range(0..self.len()))
+    }
+
+    // How often the word at `index` comes up, relative to the others.
+    fn weight(&self, _index: usize) -> u64 {
+        1
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl TextProvider for Vec<String> {
+    fn len(&self) -> usize {
+        self.as_slice().len()
+    }
+
+    fn get(&self, index: usize) -> String {
+        self[index].clone()
+    }
+}
+
+// Word list in `word<TAB>count` format, sampled proportionally to the counts.
+pub struct WeightedWords {
+    words: Vec<String>,
+    cumulative: Vec<u64>,
+}
+
+impl WeightedWords {
+    pub fn parse(lines: &[String]) -> Option<Self> {
+        let counts = lines
+            .iter()
+            .map(|line| {
+                let (word, count) = line.split_once('\t')?;
+
+                Some((word.trim().to_string(), count.trim().parse().ok()?))
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        Some(Self::new(counts))
+    }
+
+    // Words with a count of 0 are left out.
+    pub fn new(counts: Vec<(String, u64)>) -> Self {
+        let mut words = Vec::with_capacity(counts.len());
+        let mut cumulative = Vec::with_capacity(counts.len());
+        let mut total = 0u64;
+
+        for (word, count) in counts {
+            if count == 0 {
+                continue;
+            }
+
+            total += count;
+            words.push(word);
+            cumulative.push(total);
+        }
+
+        Self { words, cumulative }
+    }
+}
+
+impl TextProvider for WeightedWords {
+    fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    fn get(&self, index: usize) -> String {
+        self.words[index].clone()
+    }
+
+    fn sample(&self, rng: &mut dyn RngCore) -> String {
+        let total = self.cumulative.last().copied().unwrap_or(0);
+        let roll = rng.random_range(0..total);
+        let index = self.cumulative.partition_point(|&c| c <= roll);
+
+        self.get(index)
+    }
+
+    fn weight(&self, index: usize) -> u64 {
+        let before = index.checked_sub(1).map_or(0, |i| self.cumulative[i]);
+
+        self.cumulative[index] - before
+    }
+}
+
+// Word list backed by a memory-mapped file. Line offsets are indexed on first
+// access and words are trimmed and lowercased on demand, so only the offsets
+// are ever held in memory, along with the counts of a `word<TAB>count` list.
+pub struct MappedWords {
+    map: Mmap,
+    index: OnceCell<MappedIndex>,
+}
+
+struct MappedIndex {
+    lines: Vec<(usize, usize)>,
+    // Running totals of the counts, when every line has one.
+    cumulative: Option<Vec<u64>>,
+}
+
+impl MappedWords {
+    pub fn open(path: &str) -> io::Result<Self> {
+        let file = File::open(path)?;
+        // SAFETY: the mapping is read-only; a word list truncated underneath us
+        // is not something we try to guard against.
+        let map = unsafe { Mmap::map(&file)? };
+
+        Ok(Self {
+            map,
+            index: OnceCell::new(),
+        })
+    }
+
+    fn index(&self) -> &MappedIndex {
+        self.index.get_or_init(|| {
+            let mut lines = Vec::new();
+            let mut cumulative = Some(Vec::new());
+            let mut total = 0u64;
+            let mut start = 0;
+
+            for line in self.map.split(|&b| b == b'\n') {
+                let end = start + line.len();
+                if !line.trim_ascii().is_empty() {
+                    lines.push((start, end));
+
+                    let count = line
+                        .iter()
+                        .position(|&b| b == b'\t')
+                        .and_then(|tab| str::from_utf8(&line[tab + 1..]).ok())
+                        .and_then(|count| count.trim().parse::<u64>().ok());
+                    match (count, &mut cumulative) {
+                        (Some(count), Some(cumulative)) => {
+                            total += count;
+                            cumulative.push(total);
+                        }
+                        _ => cumulative = None,
+                    }
+                }
+                start = end + 1;
+            }
+
+            MappedIndex {
+                lines,
+                cumulative: cumulative.filter(|_| total > 0),
+            }
+        })
+    }
+}
+
+impl TextProvider for MappedWords {
+    fn len(&self) -> usize {
+        self.index().lines.len()
+    }
+
+    fn get(&self, index: usize) -> String {
+        let (start, end) = self.index().lines[index];
+        let line = String::from_utf8_lossy(&self.map[start..end]);
+        let word = line.split('\t').next().unwrap_or_default();
+
+        word.trim().to_lowercase()
+    }
+
+    // By frequency, like WeightedWords, when the list has counts.
+    fn sample(&self, rng: &mut dyn RngCore) -> String {
+        let Some(cumulative) = &self.index().cumulative else {
+            return self.get(rng.random_range(0..self.len()));
+        };
+
+        let total = cumulative.last().copied().unwrap_or(0);
+        let roll = rng.random_range(0..total);
+
+        self.get(cumulative.partition_point(|&c| c <= roll))
+    }
+
+    fn weight(&self, index: usize) -> u64 {
+        let Some(cumulative) = &self.index().cumulative else {
+            return 1;
+        };
+        let before = index.checked_sub(1).map_or(0, |i| cumulative[i]);
+
+        cumulative[index] - before
+    }
+
+    // Without indexing the whole file: any byte that isn't blank is a word,
+    // and one usually comes first.
+    fn is_empty(&self) -> bool {
+        self.map.iter().all(u8::is_ascii_whitespace)
+    }
+}
+
+// Random groups of characters, so charset presets can be mixed with words.
+pub struct CharGroups {
+    chars: Vec<char>,
+}
+
+impl CharGroups {
+    pub fn new(chars: Vec<char>) -> Self {
+        Self { chars }
+    }
+}
+
+impl TextProvider for CharGroups {
+    fn len(&self) -> usize {
+        self.chars.len()
+    }
+
+    fn get(&self, index: usize) -> String {
+        self.chars[index].to_string()
+    }
+
+    fn sample(&self, rng: &mut dyn RngCore) -> String {
+        charset_word(&self.chars, rng)
+    }
+}
+
+// Several providers sampled in proportion to their weights.
+pub struct MixedWords {
+    parts: Vec<Box<dyn TextProvider>>,
+    cumulative: Vec<f64>,
+}
+
+impl MixedWords {
+    pub fn new(parts: Vec<(Box<dyn TextProvider>, f64)>) -> Self {
+        let mut total = 0.0;
+        let mut cumulative = Vec::with_capacity(parts.len());
+
+        let parts = parts
+            .into_iter()
+            .map(|(part, weight)| {
+                total += weight;
+                cumulative.push(total);
+
+                part
+            })
+            .collect();
+
+        Self { parts, cumulative }
+    }
+}
+
+impl TextProvider for MixedWords {
+    fn len(&self) -> usize {
+        self.parts.iter().map(|p| p.len()).sum()
+    }
+
+    fn get(&self, mut index: usize) -> String {
+        for part in &self.parts {
+            if index < part.len() {
+                return part.get(index);
+            }
+
+            index -= part.len();
+        }
+
+        panic!("index out of range");
+    }
+
+    fn sample(&self, rng: &mut dyn RngCore) -> String {
+        let total = self.cumulative.last().copied().unwrap_or(0.0);
+        let roll = rng.random_range(0.0..total);
+        let index = self
+            .cumulative
+            .partition_point(|&c| c <= roll)
+            .min(self.parts.len() - 1);
+
+        self.parts[index].sample(rng)
+    }
+}
@@ -0,0 +1,1281 @@
+use crate::{
+    bot::{Bot, RoundText},
+    cli::{RaceArgs, TestOptions, usage_error},
+    helpers::fnv1a,
+    types::TestResult,
+};
+
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    env,
+    io::{self, BufRead, BufReader, Read, Write},
+    iter,
+    net::{Shutdown, TcpListener, TcpStream, ToSocketAddrs, UdpSocket},
+    process,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{self, Receiver, Sender, TryRecvError},
+    },
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+pub const DEFAULT_PORT: u16 = 7878;
+const CONNECT_TIMEOUT_SECONDS: u64 = 10;
+// A peer that takes longer than this to take a line is given up on.
+const WRITE_TIMEOUT_SECONDS: u64 = 5;
+const MAX_MESSAGE_BYTES: u64 = 4096;
+// A client has this long to say which room it joins.
+const HANDSHAKE_TIMEOUT_SECONDS: u64 = 5;
+// Connections a relay serves at once, handshakes included; more are refused.
+const MAX_CONNECTIONS: usize = 256;
+const CLOCK_SAMPLES: usize = 5;
+const COUNTDOWN_MILLIS: u64 = 3000;
+// The room of a race hosted with --host, which has a relay to itself.
+const LAN_ROOM: &str = "lan";
+// The room of a race against bots alone, on a relay only we use.
+const OFFLINE_ROOM: &str = "offline";
+
+// What a racer has typed so far in a round, sent on every keystroke.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Progress {
+    #[serde(default)]
+    pub round: u64,
+    pub typed: usize,
+    pub total: usize,
+    pub wpm: f64,
+    // Set once the racer has finished the round.
+    #[serde(default)]
+    pub result: Option<TestResult>,
+    // Ready to start the round, in the lobby.
+    #[serde(default)]
+    pub ready: bool,
+}
+
+impl Progress {
+    pub fn finished(&self) -> bool {
+        self.result.is_some()
+    }
+}
+
+// Newline-delimited JSON, tagged by `type`. A client joins a room with its
+// first line and gets `welcome` or `error` back; after that the relay passes
+// their `progress` and `rematch` on to the rest of the room under the name
+// they joined as, but for progress typed before its round started, and sends
+// `leave` for anyone who disconnects. A relay run by a --host racer also
+// sends the seed of their words in `welcome`. `ping` and `request_start` are
+// answered by the relay itself, with `pong` and a `start` for the whole
+// room; anything else a client sends is dropped. Only the host, the racer
+// who has been in the room longest, can start a round, and only once every
+// other racer's last progress says they are ready for it; the relay names
+// the host with `host` whenever that changes. Spectators join like racers
+// but never send progress, and aren't counted among the players.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Message {
+    Join {
+        room: String,
+        name: String,
+        #[serde(default)]
+        spectator: bool,
+    },
+    Welcome {
+        players: Vec<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        seed: Option<u64>,
+    },
+    Error {
+        message: String,
+    },
+    Progress {
+        name: String,
+        progress: Progress,
+    },
+    Rematch {
+        name: String,
+        round: u64,
+    },
+    Leave {
+        name: String,
+    },
+    Ping {
+        sent: u64,
+    },
+    Pong {
+        sent: u64,
+        relay: u64,
+    },
+    RequestStart {
+        round: u64,
+    },
+    Host {
+        name: String,
+    },
+    Start {
+        round: u64,
+        at: u64,
+    },
+}
+
+pub struct RaceOptions {
+    pub relay: String,
+    pub room: String,
+    pub name: String,
+    pub best_of: usize,
+    // Port to run a relay on ourselves, with --host.
+    pub host: Option<u16>,
+    pub spectate: bool,
+    // Speeds of the bots to race, with --bot.
+    pub bots: Vec<f64>,
+    // Racing bots only, with nobody to invite.
+    pub offline: bool,
+}
+
+pub struct Race {
+    name: String,
+    room: String,
+    // Where others can join, when we host the race.
+    hosting: Option<String>,
+    // Watching the others race rather than racing ourselves.
+    spectator: bool,
+    // Only we race them; nobody else in the room sees them.
+    bots: Vec<Bot>,
+    // What this round's bots type on.
+    text: RoundText,
+    // Who starts the rounds. Relays that don't say let anyone.
+    host: Option<String>,
+    socket: TcpStream,
+    out: Sender<String>,
+    incoming: Receiver<Message>,
+    progress: Progress,
+    opponents: BTreeMap<String, Progress>,
+    connected: bool,
+    seed: u64,
+    round: u64,
+    // A later round someone else has started.
+    rematch: Option<u64>,
+    best_of: usize,
+    // WPM of everyone who finished a round not decided yet.
+    results: BTreeMap<u64, BTreeMap<String, f64>>,
+    decided: BTreeSet<u64>,
+    wins: BTreeMap<String, usize>,
+    // Relay clock minus ours, in milliseconds.
+    offset: i64,
+    starts: HashMap<u64, Instant>,
+}
+
+impl Race {
+    pub fn connect(options: &RaceOptions, seed: u64) -> io::Result<Self> {
+        let addr = relay_addr(&options.relay)
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no address for relay"))?;
+        let timeout = Duration::from_secs(CONNECT_TIMEOUT_SECONDS);
+
+        let mut out = TcpStream::connect_timeout(&addr, timeout)?;
+        out.set_nodelay(true)?;
+        send(
+            &mut out,
+            &Message::Join {
+                room: options.room.clone(),
+                name: options.name.clone(),
+                spectator: options.spectate,
+            },
+        )?;
+
+        out.set_read_timeout(Some(timeout))?;
+        let mut reader = BufReader::new(out.try_clone()?);
+        let (players, seed) = match read_message(&mut reader)? {
+            Some(Message::Welcome {
+                players,
+                seed: hosted,
+            }) => (players, hosted.unwrap_or(seed)),
+            Some(Message::Error { message }) => {
+                return Err(io::Error::other(message));
+            }
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "unexpected reply from relay",
+                ));
+            }
+        };
+        let mut early = Vec::new();
+        let offset = clock_offset(&mut out, &mut reader, &mut early)?;
+        out.set_read_timeout(None)?;
+        let writer = spawn_writer(out.try_clone()?);
+
+        // Messages are read on their own thread so a quiet relay never holds
+        // up the test; the app picks them up on each tick.
+        let (sender, incoming) = mpsc::channel();
+        for message in early {
+            let _ = sender.send(message);
+        }
+        thread::spawn(move || {
+            while let Ok(Some(message)) = read_message(&mut reader) {
+                if sender.send(message).is_err() {
+                    break;
+                }
+            }
+        });
+
+        // Bots of the same speed are told apart by number.
+        let bot_name = |wpm: f64| format!("bot {:.0}", wpm);
+        let mut bots: Vec<Bot> = Vec::new();
+        for (i, &wpm) in options.bots.iter().enumerate() {
+            let name = bot_name(wpm);
+            let same = options.bots[..i]
+                .iter()
+                .filter(|&&other| bot_name(other) == name)
+                .count();
+            let name = match same {
+                0 => name,
+                n => format!("{} #{}", name, n + 1),
+            };
+
+            bots.push(Bot::new(name, wpm));
+        }
+
+        let mut opponents: BTreeMap<String, Progress> = players
+            .into_iter()
+            .map(|name| (name, Progress::default()))
+            .collect();
+        for bot in &bots {
+            opponents.insert(
+                bot.name().to_string(),
+                Progress {
+                    ready: true,
+                    ..Progress::default()
+                },
+            );
+        }
+
+        Ok(Self {
+            name: options.name.clone(),
+            room: options.room.clone(),
+            hosting: options.host.filter(|_| !options.offline).map(lan_addr),
+            spectator: options.spectate,
+            bots,
+            text: RoundText::default(),
+            host: None,
+            socket: out,
+            out: writer,
+            incoming,
+            progress: Progress::default(),
+            opponents,
+            connected: true,
+            seed,
+            round: 0,
+            rematch: None,
+            best_of: options.best_of,
+            results: BTreeMap::new(),
+            decided: BTreeSet::new(),
+            wins: BTreeMap::new(),
+            offset,
+            starts: HashMap::new(),
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn room(&self) -> &str {
+        &self.room
+    }
+
+    pub fn hosting(&self) -> Option<&str> {
+        self.hosting.as_deref()
+    }
+
+    pub fn spectator(&self) -> bool {
+        self.spectator
+    }
+
+    pub fn connected(&self) -> bool {
+        self.connected
+    }
+
+    pub fn progress(&self) -> &Progress {
+        &self.progress
+    }
+
+    pub fn opponents(&self) -> &BTreeMap<String, Progress> {
+        &self.opponents
+    }
+
+    // Every round is typed on a text of its own.
+    pub fn seed(&self) -> u64 {
+        self.seed.wrapping_add(self.round)
+    }
+
+    pub fn round(&self) -> u64 {
+        self.round
+    }
+
+    // Everyone in the round, fastest first, once all of them have finished.
+    pub fn standings(&self) -> Option<Vec<(&str, f64)>> {
+        let mut racers: Vec<(&str, &Progress)> = self
+            .opponents
+            .iter()
+            .map(|(name, progress)| (name.as_str(), progress))
+            .chain((!self.spectator).then_some((self.name.as_str(), &self.progress)))
+            .collect();
+
+        let done = racers
+            .iter()
+            .all(|(_, progress)| progress.round == self.round && progress.finished());
+        if racers.is_empty() || !done {
+            return None;
+        }
+
+        racers.sort_by(|a, b| b.1.wpm.total_cmp(&a.1.wpm));
+
+        Some(
+            racers
+                .into_iter()
+                .map(|(name, progress)| (name, progress.wpm))
+                .collect(),
+        )
+    }
+
+    pub fn best_of(&self) -> usize {
+        self.best_of
+    }
+
+    pub fn wins(&self) -> &BTreeMap<String, usize> {
+        &self.wins
+    }
+
+    // When the current round starts, once someone asked for it to.
+    pub fn start(&self) -> Option<Instant> {
+        self.starts.get(&self.round).copied()
+    }
+
+    pub fn started(&self) -> bool {
+        self.start().is_some_and(|start| Instant::now() >= start)
+    }
+
+    pub fn host(&self) -> Option<&str> {
+        self.host.as_deref()
+    }
+
+    pub fn is_host(&self) -> bool {
+        !self.spectator && self.host.as_ref().is_none_or(|host| *host == self.name)
+    }
+
+    // Everyone else in the room is ready for the current round.
+    pub fn all_ready(&self) -> bool {
+        self.opponents
+            .values()
+            .all(|progress| progress.round == self.round && progress.ready)
+    }
+
+    pub fn toggle_ready(&mut self) {
+        if self.spectator || self.start().is_some() {
+            return;
+        }
+
+        self.progress.ready = !self.progress.ready;
+        self.send_progress();
+    }
+
+    // The relay counts down and tells the whole room when to go, once the
+    // host asks and everyone is ready.
+    pub fn request_start(&mut self) {
+        let waiting = self.host.is_some() && !self.all_ready();
+        if !self.is_host() || waiting || self.start().is_some() {
+            return;
+        }
+
+        let message = Message::RequestStart { round: self.round };
+        if queue(&self.out, &message).is_err() {
+            self.connected = false;
+        }
+    }
+
+    pub fn rematch_requested(&self) -> bool {
+        self.rematch.is_some()
+    }
+
+    // Whoever won more than half of the best-of-N rounds.
+    pub fn series_winner(&self) -> Option<&str> {
+        if self.best_of < 2 {
+            return None;
+        }
+
+        self.wins
+            .iter()
+            .find(|(_, wins)| **wins > self.best_of / 2)
+            .map(|(name, _)| name.as_str())
+    }
+
+    // Moves on to the round someone else started, or starts the next one. A
+    // decided series starts over. Spectators only ever follow.
+    pub fn rematch(&mut self) {
+        let round = self.rematch.take().unwrap_or(self.round + 1);
+        if self.series_winner().is_some() {
+            self.wins.clear();
+        }
+
+        self.round = round;
+        self.progress = Progress {
+            round,
+            ..Progress::default()
+        };
+
+        for bot in &mut self.bots {
+            bot.reset();
+            self.opponents.insert(
+                bot.name().to_string(),
+                Progress {
+                    round,
+                    ready: true,
+                    ..Progress::default()
+                },
+            );
+        }
+
+        if self.spectator {
+            return;
+        }
+
+        let message = Message::Rematch {
+            name: self.name.clone(),
+            round,
+        };
+        if queue(&self.out, &message).is_err() {
+            self.connected = false;
+        }
+    }
+
+    // Takes in what arrived since the last call. Newcomers are sent our
+    // progress right away so they don't wait for our next keystroke.
+    pub fn poll(&mut self) {
+        loop {
+            match self.incoming.try_recv() {
+                Ok(Message::Join {
+                    name, spectator, ..
+                }) => {
+                    if !spectator {
+                        self.opponents.insert(name, Progress::default());
+                    }
+                    self.send_progress();
+                }
+                // Nobody can have typed anything before the start; whoever
+                // claims to is ignored.
+                Ok(Message::Progress { progress, .. })
+                    if progress.round == self.round && progress.typed > 0 && !self.started() => {}
+                Ok(Message::Progress { name, progress }) => {
+                    if progress.finished() {
+                        self.record(progress.round, &name, progress.wpm);
+                    }
+                    self.opponents.insert(name, progress);
+                }
+                Ok(Message::Rematch { name, round }) => {
+                    if round > self.round {
+                        self.rematch = Some(round);
+                    }
+                    self.opponents.insert(
+                        name,
+                        Progress {
+                            round,
+                            ..Progress::default()
+                        },
+                    );
+                }
+                Ok(Message::Host { name }) => self.host = Some(name),
+                Ok(Message::Start { round, at }) => {
+                    let start = self.local_instant(at);
+                    self.starts.entry(round).or_insert(start);
+                }
+                Ok(Message::Leave { name }) => {
+                    self.opponents.remove(&name);
+
+                    // They may have been the last one a round waited for.
+                    let rounds: Vec<u64> = self.results.keys().copied().collect();
+                    for round in rounds {
+                        self.decide(round);
+                    }
+                }
+                Ok(_) => {}
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.connected = false;
+
+                    break;
+                }
+            }
+        }
+
+        self.advance_bots();
+    }
+
+    // Bots type on the same text as we do, once the round has started.
+    fn advance_bots(&mut self) {
+        let Some(start) = self.start() else {
+            return;
+        };
+
+        let (round, text) = (self.round, self.text);
+        let progress: Vec<(String, Progress)> = self
+            .bots
+            .iter_mut()
+            .map(|bot| (bot.name().to_string(), bot.advance(round, start, text)))
+            .collect();
+
+        for (name, progress) in progress {
+            if progress.finished() {
+                self.record(round, &name, progress.wpm);
+            }
+            self.opponents.insert(name, progress);
+        }
+    }
+
+    // Set for each round before it starts, as our own progress stops saying
+    // how long the text is once a streamed one is cut short.
+    pub fn set_text(&mut self, text: RoundText) {
+        self.text = text;
+    }
+
+    pub fn update(&mut self, progress: Progress) {
+        if self.spectator {
+            return;
+        }
+
+        self.progress = Progress {
+            round: self.round,
+            ready: self.progress.ready,
+            ..progress
+        };
+        if self.progress.finished() {
+            self.record(self.round, &self.name.clone(), self.progress.wpm);
+        }
+
+        self.send_progress();
+    }
+
+    fn local_instant(&self, relay_millis: u64) -> Instant {
+        let wait = relay_millis as i64 - (now_millis() as i64 + self.offset);
+        let now = Instant::now();
+
+        if wait >= 0 {
+            now + Duration::from_millis(wait as u64)
+        } else {
+            now.checked_sub(Duration::from_millis(wait.unsigned_abs()))
+                .unwrap_or(now)
+        }
+    }
+
+    fn record(&mut self, round: u64, name: &str, wpm: f64) {
+        if self.decided.contains(&round) {
+            return;
+        }
+
+        self.results
+            .entry(round)
+            .or_default()
+            .insert(name.to_string(), wpm);
+        self.decide(round);
+    }
+
+    // A round goes to the fastest racer once everyone in the room finished it.
+    fn decide(&mut self, round: u64) {
+        let Some(results) = self.results.get(&round) else {
+            return;
+        };
+
+        let everyone = iter::once(&self.name)
+            .filter(|_| !self.spectator)
+            .chain(self.opponents.keys())
+            .all(|name| results.contains_key(name));
+        if !everyone {
+            return;
+        }
+
+        let winner = results
+            .iter()
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .map(|(name, _)| name.clone());
+        self.results.remove(&round);
+        self.decided.insert(round);
+
+        if let Some(winner) = winner {
+            *self.wins.entry(winner).or_default() += 1;
+        }
+    }
+
+    fn send_progress(&mut self) {
+        if self.spectator {
+            return;
+        }
+
+        let message = Message::Progress {
+            name: self.name.clone(),
+            progress: self.progress.clone(),
+        };
+
+        if queue(&self.out, &message).is_err() {
+            self.connected = false;
+        }
+    }
+}
+
+// The reader thread holds a clone of the socket, so it has to be shut down
+// for the relay to see us leave.
+impl Drop for Race {
+    fn drop(&mut self) {
+        let _ = self.socket.shutdown(Shutdown::Both);
+    }
+}
+
+// Same room code, same seed: everyone in the room types the same words.
+pub fn room_seed(room: &str) -> u64 {
+    fnv1a(room.as_bytes())
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+// As NTP does: of a few round trips, the quickest tells the relay's clock
+// best, assuming the way there took as long as the way back. Anything else
+// arriving meanwhile is kept for later.
+fn clock_offset(
+    out: &mut TcpStream,
+    reader: &mut impl BufRead,
+    early: &mut Vec<Message>,
+) -> io::Result<i64> {
+    let mut best: Option<(u64, i64)> = None;
+
+    for _ in 0..CLOCK_SAMPLES {
+        let sent = now_millis();
+        send(out, &Message::Ping { sent })?;
+
+        loop {
+            match read_message(reader)? {
+                Some(Message::Pong { sent: s, relay }) if s == sent => {
+                    let received = now_millis();
+                    let round_trip = received.saturating_sub(sent);
+                    let offset = relay as i64 - (sent + received) as i64 / 2;
+
+                    if best.is_none_or(|(best, _)| round_trip < best) {
+                        best = Some((round_trip, offset));
+                    }
+
+                    break;
+                }
+                Some(message) => early.push(message),
+                None => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "relay closed the connection",
+                    ));
+                }
+            }
+        }
+    }
+
+    Ok(best.map_or(0, |(_, offset)| offset))
+}
+
+// The address others on the network reach us at. Connecting a UDP socket
+// sends nothing, but picks the interface a packet out would leave by.
+fn lan_addr(port: u16) -> String {
+    let ip = UdpSocket::bind(("0.0.0.0", 0))
+        .and_then(|socket| {
+            socket.connect(("192.0.2.1", 9))?;
+            socket.local_addr()
+        })
+        .map_or_else(|_| "localhost".to_string(), |addr| addr.ip().to_string());
+
+    format!("{}:{}", ip, port)
+}
+
+fn relay_addr(relay: &str) -> String {
+    if relay.contains(':') {
+        relay.to_string()
+    } else {
+        format!("{}:{}", relay, DEFAULT_PORT)
+    }
+}
+
+fn encode(message: &Message) -> io::Result<String> {
+    let mut line = serde_json::to_string(message)?;
+    line.push('\n');
+
+    Ok(line)
+}
+
+fn send(out: &mut TcpStream, message: &Message) -> io::Result<()> {
+    out.write_all(encode(message)?.as_bytes())
+}
+
+// Fails once the writer has given up on the socket.
+fn queue(out: &Sender<String>, message: &Message) -> io::Result<()> {
+    out.send(encode(message)?)
+        .map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))
+}
+
+// Lines queued for a socket are written on a thread of their own, so a slow
+// peer holds up neither the UI nor the relay's other members. A failed or
+// timed out write shuts the socket down, which ends its reader too.
+fn spawn_writer(mut out: TcpStream) -> Sender<String> {
+    let (sender, lines) = mpsc::channel::<String>();
+    thread::spawn(move || {
+        let timeout = Duration::from_secs(WRITE_TIMEOUT_SECONDS);
+        if out.set_write_timeout(Some(timeout)).is_err() {
+            return;
+        }
+
+        for line in lines {
+            if out.write_all(line.as_bytes()).is_err() {
+                let _ = out.shutdown(Shutdown::Both);
+
+                break;
+            }
+        }
+    });
+
+    sender
+}
+
+// `None` once the other side has gone. Overlong lines end the connection
+// rather than being buffered without limit.
+fn read_message(reader: &mut impl BufRead) -> io::Result<Option<Message>> {
+    loop {
+        let mut line = String::new();
+        let read = reader
+            .by_ref()
+            .take(MAX_MESSAGE_BYTES)
+            .read_line(&mut line)?;
+
+        if read == 0 {
+            return Ok(None);
+        }
+
+        if !line.ends_with('\n') {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "message too long",
+            ));
+        }
+
+        // Unknown messages are skipped, so newer clients can add their own.
+        if let Ok(message) = serde_json::from_str(&line) {
+            return Ok(Some(message));
+        }
+    }
+}
+
+struct Member {
+    id: u64,
+    name: String,
+    spectator: bool,
+    out: Sender<String>,
+    // The round they last said they were ready for.
+    ready: Option<u64>,
+}
+
+#[derive(Default)]
+struct Room {
+    members: Vec<Member>,
+    // Start of each round, on the relay's clock.
+    starts: HashMap<u64, u64>,
+}
+
+type Rooms = Arc<Mutex<HashMap<String, Room>>>;
+
+pub fn run_relay(port: u16) {
+    let listener = TcpListener::bind(("0.0.0.0", port)).unwrap_or_else(|e| {
+        eprintln!("Failed to listen on port {}: {}", port, e);
+
+        process::exit(1);
+    });
+    println!("Relay listening on port {}", port);
+
+    serve(listener, None);
+}
+
+// Runs a relay for a --host race in the background, for as long as we run,
+// handing everyone who joins `seed` for their words. Returns the port it
+// listens on, which port 0 leaves to the system. A race against bots alone
+// is only reachable from this machine.
+pub fn host_relay(port: u16, offline: bool, seed: u64) -> io::Result<u16> {
+    let ip = if offline { "127.0.0.1" } else { "0.0.0.0" };
+    let listener = TcpListener::bind((ip, port))?;
+    let port = listener.local_addr()?.port();
+    thread::spawn(move || serve(listener, Some(seed)));
+
+    Ok(port)
+}
+
+fn serve(listener: TcpListener, seed: Option<u64>) {
+    let rooms: Rooms = Arc::default();
+    let connections = Arc::new(AtomicUsize::new(0));
+
+    for (id, stream) in listener.incoming().enumerate() {
+        let Ok(mut stream) = stream else {
+            continue;
+        };
+
+        // Best effort: the error fits in the socket's buffer, and the
+        // connection is closed either way.
+        if connections.load(Ordering::SeqCst) >= MAX_CONNECTIONS {
+            let full = Message::Error {
+                message: "The relay is full".to_string(),
+            };
+            if let Ok(line) = encode(&full)
+                && stream.set_nonblocking(true).is_ok()
+            {
+                let _ = stream.write_all(line.as_bytes());
+            }
+
+            continue;
+        }
+
+        connections.fetch_add(1, Ordering::SeqCst);
+        let rooms = Arc::clone(&rooms);
+        let connections = Arc::clone(&connections);
+        thread::spawn(move || {
+            relay_member(stream, id as u64, &rooms, seed);
+            connections.fetch_sub(1, Ordering::SeqCst);
+        });
+    }
+}
+
+fn relay_member(stream: TcpStream, id: u64, rooms: &Rooms, seed: Option<u64>) {
+    let Ok(socket) = stream.try_clone() else {
+        return;
+    };
+    let out = spawn_writer(socket);
+    let handshake = Duration::from_secs(HANDSHAKE_TIMEOUT_SECONDS);
+    if stream.set_read_timeout(Some(handshake)).is_err() {
+        return;
+    }
+    let mut reader = BufReader::new(stream);
+
+    let Ok(Some(Message::Join {
+        room,
+        name,
+        spectator,
+    })) = read_message(&mut reader)
+    else {
+        return;
+    };
+
+    // Racers can stay quiet for as long as they like once in a room.
+    if reader.get_ref().set_read_timeout(None).is_err() {
+        return;
+    }
+
+    {
+        let mut rooms = rooms.lock().unwrap_or_else(|e| e.into_inner());
+        let members = &mut rooms.entry(room.clone()).or_default().members;
+
+        // Racers are told apart by name.
+        let reply = if members.iter().any(|m| m.name == name) {
+            Message::Error {
+                message: format!("{} is already in room {}", name, room),
+            }
+        } else {
+            Message::Welcome {
+                players: members
+                    .iter()
+                    .filter(|m| !m.spectator)
+                    .map(|m| m.name.clone())
+                    .collect(),
+                seed,
+            }
+        };
+
+        if queue(&out, &reply).is_err() || matches!(reply, Message::Error { .. }) {
+            return;
+        }
+
+        members.push(Member {
+            id,
+            name: name.clone(),
+            spectator,
+            out: out.clone(),
+            ready: None,
+        });
+
+        broadcast(
+            members,
+            Some(id),
+            &Message::Join {
+                room: room.clone(),
+                name: name.clone(),
+                spectator,
+            },
+        );
+        announce_host(members);
+    }
+
+    // Only progress and rematches are passed on, under the name the racer
+    // joined as; anything else a racer sends is the relay's to say.
+    loop {
+        let mut line = String::new();
+        match reader.by_ref().take(MAX_MESSAGE_BYTES).read_line(&mut line) {
+            Ok(0) | Err(_) => break,
+            Ok(_) if !line.ends_with('\n') => break,
+            Ok(_) => {}
+        }
+
+        let message = match serde_json::from_str(&line) {
+            Ok(Message::Ping { sent }) => {
+                let _ = queue(
+                    &out,
+                    &Message::Pong {
+                        sent,
+                        relay: now_millis(),
+                    },
+                );
+
+                continue;
+            }
+            Ok(message) => message,
+            Err(_) => continue,
+        };
+
+        let mut rooms = rooms.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(room) = rooms.get_mut(&room) else {
+            continue;
+        };
+
+        let message = match message {
+            // The first request for a round sets its start, once everyone
+            // else is ready for it; later ones get the same start again.
+            Message::RequestStart { round } => {
+                let started = room.starts.contains_key(&round);
+                if room_host(&room.members) != Some(&name)
+                    || !started && !others_ready(&room.members, id, round)
+                {
+                    continue;
+                }
+
+                let at = *room
+                    .starts
+                    .entry(round)
+                    .or_insert_with(|| now_millis() + COUNTDOWN_MILLIS);
+
+                broadcast(&room.members, None, &Message::Start { round, at });
+
+                continue;
+            }
+            Message::Progress { progress, .. } => {
+                // Nobody can have typed anything before the start, so
+                // whoever claims to isn't passed on.
+                let started = room
+                    .starts
+                    .get(&progress.round)
+                    .is_some_and(|at| now_millis() >= *at);
+                if progress.typed > 0 && !started {
+                    continue;
+                }
+
+                if let Some(member) = room.members.iter_mut().find(|m| m.id == id) {
+                    member.ready = progress.ready.then_some(progress.round);
+                }
+
+                Message::Progress {
+                    name: name.clone(),
+                    progress,
+                }
+            }
+            Message::Rematch { round, .. } => Message::Rematch {
+                name: name.clone(),
+                round,
+            },
+            _ => continue,
+        };
+
+        broadcast(&room.members, Some(id), &message);
+    }
+
+    let mut rooms = rooms.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(members) = rooms.get_mut(&room).map(|room| &mut room.members) {
+        members.retain(|m| m.id != id);
+        broadcast(members, Some(id), &Message::Leave { name });
+        announce_host(members);
+
+        if members.is_empty() {
+            rooms.remove(&room);
+        }
+    }
+}
+
+// Whether everyone racing but `id` is ready for `round`.
+fn others_ready(members: &[Member], id: u64, round: u64) -> bool {
+    members
+        .iter()
+        .filter(|m| m.id != id && !m.spectator)
+        .all(|m| m.ready == Some(round))
+}
+
+// The longest in the room of those racing.
+fn room_host(members: &[Member]) -> Option<&str> {
+    members
+        .iter()
+        .find(|m| !m.spectator)
+        .map(|m| m.name.as_str())
+}
+
+fn announce_host(members: &[Member]) {
+    if let Some(name) = room_host(members).map(str::to_string) {
+        broadcast(members, None, &Message::Host { name });
+    }
+}
+
+// To everyone in the room but `from`.
+fn broadcast(members: &[Member], from: Option<u64>, message: &Message) {
+    let Ok(line) = encode(message) else {
+        return;
+    };
+
+    for member in members.iter().filter(|m| Some(m.id) != from) {
+        let _ = member.out.send(line.clone());
+    }
+}
+
+// The race options apart from the usual test ones, which are handed back.
+pub fn race_options(args: RaceArgs, relay: Option<String>) -> (RaceOptions, TestOptions) {
+    let RaceArgs {
+        room,
+        relay: given_relay,
+        host,
+        port,
+        join,
+        name,
+        best_of,
+        spectate,
+        bot: bots,
+        test,
+    } = args;
+
+    let mut room = room;
+    let mut host = host;
+    let mut port = port;
+    let lan = host || join.is_some();
+    let mut relay = join.or(given_relay).or(relay);
+
+    // Bots alone are raced on a relay of our own, on any free port.
+    let offline = !bots.is_empty() && !lan && room.is_none();
+    if offline {
+        host = true;
+        port = 0;
+        room = Some(OFFLINE_ROOM.to_string());
+    }
+
+    // A hosted race has the relay to itself, so needs no room code.
+    if host {
+        relay = Some(format!("127.0.0.1:{}", port));
+    }
+
+    let Some(room) = room.or_else(|| lan.then(|| LAN_ROOM.to_string())) else {
+        usage_error("Missing --room, or --host, --join or --bot")
+    };
+
+    let Some(relay) = relay else {
+        usage_error("No relay given, pass --relay or set `relay` in config.toml")
+    };
+
+    let name = name
+        .or_else(|| env::var("USER").ok())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "player".to_string());
+
+    (
+        RaceOptions {
+            relay,
+            room,
+            name,
+            best_of: best_of as usize,
+            host: host.then_some(port),
+            spectate,
+            bots,
+            offline,
+        },
+        test,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn progress_round_trips() {
+        let progress = Progress {
+            round: 2,
+            typed: 312,
+            total: 312,
+            wpm: 72.4,
+            result: Some(TestResult {
+                wpm: 72.4,
+                accuracy: 97.8,
+                seconds: 51.7,
+            }),
+            ready: true,
+        };
+        let message = Message::Progress {
+            name: "alice".to_string(),
+            progress: progress.clone(),
+        };
+
+        let line = encode(&message).unwrap();
+        let Some(Message::Progress {
+            name,
+            progress: parsed,
+        }) = read_message(&mut line.as_bytes()).unwrap()
+        else {
+            panic!("not a progress message: {}", line);
+        };
+
+        assert_eq!(name, "alice");
+        assert_eq!(parsed, progress);
+        assert!(parsed.finished());
+    }
+
+    // Progress of a racer still typing leaves the result out.
+    #[test]
+    fn progress_without_result() {
+        let parsed: Progress =
+            serde_json::from_str(r#"{"typed":10,"total":312,"wpm":40.0}"#).unwrap();
+
+        assert!(!parsed.finished());
+        assert_eq!(parsed.round, 0);
+        assert!(!parsed.ready);
+    }
+
+    fn member(id: u64, spectator: bool, ready: Option<u64>) -> Member {
+        Member {
+            id,
+            name: format!("racer {}", id),
+            spectator,
+            out: mpsc::channel().0,
+            ready,
+        }
+    }
+
+    // The host and spectators don't have to be ready.
+    #[test]
+    fn start_waits_for_everyone_ready() {
+        let members = [
+            member(0, false, None),
+            member(1, false, Some(2)),
+            member(2, true, None),
+        ];
+        assert!(others_ready(&members, 0, 2));
+        assert!(!others_ready(&members, 0, 3));
+
+        let members = [member(0, false, None), member(1, false, None)];
+        assert!(!others_ready(&members, 0, 2));
+    }
+
+    fn join(port: u16, name: &str) -> (TcpStream, BufReader<TcpStream>) {
+        let mut socket = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        socket
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let join = Message::Join {
+            room: "room".to_string(),
+            name: name.to_string(),
+            spectator: false,
+        };
+        socket.write_all(encode(&join).unwrap().as_bytes()).unwrap();
+
+        let mut reader = BufReader::new(socket.try_clone().unwrap());
+        let Some(Message::Welcome { .. }) = read_message(&mut reader).unwrap() else {
+            panic!("{} wasn't welcomed", name);
+        };
+
+        (socket, reader)
+    }
+
+    // A racer can't start a round themselves, nor speak for anyone else.
+    #[test]
+    fn relay_drops_spoofed_messages() {
+        let port = host_relay(0, true, 7).unwrap();
+        let (_alice, mut alice_reader) = join(port, "alice");
+        let (mut bob, _bob_reader) = join(port, "bob");
+
+        let spoofed = [
+            Message::Start { round: 0, at: 0 },
+            Message::Host {
+                name: "bob".to_string(),
+            },
+            Message::Progress {
+                name: "alice".to_string(),
+                progress: Progress::default(),
+            },
+        ];
+        for message in &spoofed {
+            bob.write_all(encode(message).unwrap().as_bytes()).unwrap();
+        }
+
+        loop {
+            match read_message(&mut alice_reader).unwrap() {
+                Some(Message::Start { .. }) => panic!("a racer's start was passed on"),
+                Some(Message::Host { name }) => assert_eq!(name, "alice"),
+                Some(Message::Progress { name, .. }) => {
+                    assert_eq!(name, "bob");
+
+                    break;
+                }
+                Some(_) => {}
+                None => panic!("the relay went away"),
+            }
+        }
+    }
+
+    // A streamed text is cut down to what we typed when our time runs out;
+    // a faster bot still finishes at the time limit and the round is scored.
+    #[test]
+    fn bots_finish_when_the_time_is_up() {
+        let port = host_relay(0, true, 7).unwrap();
+        let options = RaceOptions {
+            relay: format!("127.0.0.1:{}", port),
+            room: OFFLINE_ROOM.to_string(),
+            name: "alice".to_string(),
+            best_of: 3,
+            host: Some(port),
+            spectate: false,
+            bots: vec![300.0],
+            offline: true,
+        };
+        let mut race = Race::connect(&options, 7).unwrap();
+        race.set_text(RoundText {
+            length: 600,
+            endless: true,
+            seconds: Some(1.0),
+        });
+        race.starts
+            .insert(0, Instant::now() - Duration::from_secs(2));
+
+        race.update(Progress {
+            typed: 10,
+            total: 10,
+            wpm: 120.0,
+            result: Some(TestResult {
+                wpm: 120.0,
+                accuracy: 100.0,
+                seconds: 1.0,
+            }),
+            ..Progress::default()
+        });
+        race.poll();
+
+        let bot = &race.opponents()["bot 300"];
+        assert!(bot.finished());
+        assert!(bot.typed > 10);
+        assert_eq!(bot.result.map(|r| r.seconds), Some(1.0));
+        assert_eq!(race.wins().get("bot 300"), Some(&1));
+    }
+}
@@ -0,0 +1,199 @@
+use crate::{
+    config::Config,
+    ghost::{MAX_GHOSTS, ghost_replays},
+    helpers::parse_arg_list_with_text,
+    history::data_dir,
+    types::{Args, KeystrokeEvent, TestResult},
+};
+
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, BufRead, BufReader, Write},
+    iter,
+    path::{Path, PathBuf},
+    process,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const MAGIC: &str = "ttt-replay 1";
+// As many as there can be ghosts.
+const MAX_REPLAYS: usize = MAX_GHOSTS;
+
+// What a recording is of. The keystrokes follow it in the file.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct RecordingHeader {
+    pub timestamp: u64,
+    pub source: String,
+    pub target: String,
+    pub result: TestResult,
+}
+
+// Every key of one test with its time on the test clock, which only moves
+// forward and stops while paused. A file is the `ttt-replay 1` line, the
+// header as JSON, then a line per key: microseconds since the key before it
+// and the key as a JSON string, `null` for Backspace, e.g. `183201 "t"`.
+// Whether a key was right follows from the text, so it isn't stored.
+pub struct Recording {
+    pub header: RecordingHeader,
+    pub keystrokes: Vec<KeystrokeEvent>,
+}
+
+impl Recording {
+    pub fn write(&self, out: &mut impl Write) -> io::Result<()> {
+        let mut text = format!("{}\n{}\n", MAGIC, serde_json::to_string(&self.header)?);
+
+        let mut last = 0;
+        for stroke in &self.keystrokes {
+            let micros = (stroke.seconds * 1_000_000.0).round() as u64;
+            text.push_str(&format!(
+                "{} {}\n",
+                micros.saturating_sub(last),
+                serde_json::to_string(&stroke.key)?
+            ));
+            last = micros;
+        }
+
+        out.write_all(text.as_bytes())
+    }
+
+    pub fn read(path: &Path) -> io::Result<Self> {
+        let invalid = |what: &str| io::Error::new(io::ErrorKind::InvalidData, what.to_string());
+        let mut lines = BufReader::new(fs::File::open(path)?).lines();
+
+        if lines.next().transpose()?.as_deref() != Some(MAGIC) {
+            return Err(invalid("not a ttt replay"));
+        }
+
+        let header: RecordingHeader = match lines.next().transpose()? {
+            Some(line) => serde_json::from_str(&line)?,
+            None => return Err(invalid("missing header")),
+        };
+
+        let target: Vec<char> = header.target.chars().collect();
+        let mut typed = 0;
+        let mut micros = 0;
+        let mut keystrokes = Vec::new();
+
+        for line in lines {
+            let line = line?;
+            let (delta, key) = line
+                .split_once(' ')
+                .ok_or_else(|| invalid("malformed keystroke"))?;
+            micros += delta
+                .parse::<u64>()
+                .map_err(|_| invalid("malformed keystroke"))?;
+            let key: Option<char> = serde_json::from_str(key)?;
+
+            let correct = match key {
+                Some(c) => {
+                    typed += 1;
+                    target.get(typed - 1) == Some(&c)
+                }
+                None => {
+                    typed = typed.saturating_sub(1);
+                    true
+                }
+            };
+
+            keystrokes.push(KeystrokeEvent {
+                seconds: micros as f64 / 1_000_000.0,
+                key,
+                correct,
+            });
+        }
+
+        Ok(Self { header, keystrokes })
+    }
+}
+
+pub fn replays_dir() -> Option<PathBuf> {
+    data_dir().map(|dir| dir.join("replays"))
+}
+
+// Saved under the data directory, named by the time in milliseconds, with a
+// counter after it for any other saved in the same millisecond. Returns the
+// file name, which the result refers to.
+pub fn save_recording(recording: &Recording) -> io::Result<String> {
+    let Some(dir) = replays_dir() else {
+        return Err(io::Error::new(io::ErrorKind::NotFound, "no data directory"));
+    };
+    fs::create_dir_all(&dir)?;
+
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or(0);
+
+    let mut n = 0;
+    let (name, mut file) = loop {
+        let name = match n {
+            0 => format!("{}.ttr", millis),
+            n => format!("{}-{}.ttr", millis, n),
+        };
+        match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(dir.join(&name))
+        {
+            Ok(file) => break (name, file),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => n += 1,
+            Err(e) => return Err(e),
+        }
+    };
+    recording.write(&mut file)?;
+
+    prune_replays(&dir);
+
+    Ok(name)
+}
+
+// Only the newest replays are kept, and those ghosts race against.
+fn prune_replays(dir: &Path) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+
+    let mut replays: Vec<(SystemTime, PathBuf)> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "ttr"))
+        .map(|path| {
+            let modified = fs::metadata(&path).and_then(|m| m.modified());
+            (modified.unwrap_or(UNIX_EPOCH), path)
+        })
+        .collect();
+    if replays.len() <= MAX_REPLAYS {
+        return;
+    }
+
+    replays.sort();
+    let kept = ghost_replays();
+    for (_, path) in &replays[..replays.len() - MAX_REPLAYS] {
+        let name = path.file_name().and_then(|name| name.to_str());
+        if name.is_some_and(|name| !kept.contains(name)) {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+// NAME is a path, or the name of a file in the replays folder as
+// results.jsonl refers to it.
+pub fn load_replay(name: &str) -> Recording {
+    let path = Path::new(name);
+    let path = match replays_dir() {
+        Some(dir) if !path.exists() => dir.join(path),
+        _ => path.to_path_buf(),
+    };
+
+    Recording::read(&path).unwrap_or_else(|e| {
+        eprintln!("Failed to read replay {}: {}", name, e);
+
+        process::exit(1);
+    })
+}
+
+// Default options with the recorded text, for the app to play it back in.
+pub fn playback_args(recording: &Recording, config: &Config) -> Args {
+    parse_arg_list_with_text(iter::empty(), recording.header.target.clone(), config)
+}
@@ -0,0 +1,210 @@
+use crate::types::KeystrokeEvent;
+
+use std::time::Instant;
+
+pub const REPLAY_SPEED: f64 = 0.5;
+
+// Mistakes less than CLUSTER_GAP_SECONDS apart belong to the same cluster, and
+// clusters with at least MAJOR_CLUSTER_MISTAKES of them are worth replaying.
+const CLUSTER_GAP_SECONDS: f64 = 1.0;
+const MAJOR_CLUSTER_MISTAKES: usize = 2;
+const CONTEXT_SECONDS: f64 = 1.5;
+
+// Time windows, in seconds since the start of the test, around each major
+// cluster of mistakes.
+pub fn mistake_windows(strokes: &[KeystrokeEvent]) -> Vec<(f64, f64)> {
+    let mut clusters: Vec<(f64, f64, usize)> = Vec::new();
+
+    for stroke in strokes.iter().filter(|s| !s.correct) {
+        match clusters.last_mut() {
+            Some((_, end, mistakes)) if stroke.seconds - *end <= CLUSTER_GAP_SECONDS => {
+                *end = stroke.seconds;
+                *mistakes += 1;
+            }
+            _ => clusters.push((stroke.seconds, stroke.seconds, 1)),
+        }
+    }
+
+    let mut windows: Vec<(f64, f64)> = Vec::new();
+
+    for (start, end, mistakes) in clusters {
+        if mistakes < MAJOR_CLUSTER_MISTAKES {
+            continue;
+        }
+
+        let start = (start - CONTEXT_SECONDS).max(0.0);
+        let end = end + CONTEXT_SECONDS;
+
+        match windows.last_mut() {
+            Some((_, last_end)) if start <= *last_end => *last_end = end,
+            _ => windows.push((start, end)),
+        }
+    }
+
+    windows
+}
+
+// Text in the input box after every keystroke up to `seconds`.
+pub fn typed_at(strokes: &[KeystrokeEvent], seconds: f64) -> String {
+    let mut typed = String::new();
+
+    for stroke in strokes.iter().take_while(|s| s.seconds <= seconds) {
+        match stroke.key {
+            Some(c) => typed.push(c),
+            None => {
+                typed.pop();
+            }
+        }
+    }
+
+    typed
+}
+
+pub struct Replay {
+    windows: Vec<(f64, f64)>,
+    index: usize,
+    started_at: Instant,
+}
+
+impl Replay {
+    pub fn new(windows: Vec<(f64, f64)>) -> Self {
+        Self {
+            windows,
+            index: 0,
+            started_at: Instant::now(),
+        }
+    }
+
+    // Current point of the replay in test time.
+    pub fn position(&self) -> f64 {
+        let (start, end) = self.windows[self.index];
+
+        (start + self.started_at.elapsed().as_secs_f64() * REPLAY_SPEED).min(end)
+    }
+
+    // Moves on to the next window once the current one has played. Returns
+    // false when there is nothing left to play.
+    pub fn tick(&mut self) -> bool {
+        let (start, end) = self.windows[self.index];
+        if start + self.started_at.elapsed().as_secs_f64() * REPLAY_SPEED <= end {
+            return true;
+        }
+
+        self.index += 1;
+        self.started_at = Instant::now();
+
+        self.index < self.windows.len()
+    }
+
+    pub fn progress(&self) -> (usize, usize) {
+        (self.index + 1, self.windows.len())
+    }
+}
+
+const PLAYBACK_SPEEDS: &[f64] = &[0.25, 0.5, 1.0, 2.0, 4.0];
+const SEEK_SECONDS: f64 = 5.0;
+
+// A whole recorded test played back on its own clock, which can be paused,
+// sped up or slowed down, and moved around in.
+pub struct Playback {
+    duration: f64,
+    // Test time when the clock last changed speed or stopped.
+    position: f64,
+    resumed_at: Option<Instant>,
+    speed: usize,
+    // Whether the keys to control it are shown.
+    controls: bool,
+}
+
+impl Playback {
+    pub fn new(duration: f64) -> Self {
+        Self {
+            duration,
+            position: 0.0,
+            resumed_at: Some(Instant::now()),
+            speed: 2,
+            controls: true,
+        }
+    }
+
+    // Stopped at the start, to be moved through with `seek`.
+    pub fn frozen(duration: f64) -> Self {
+        Self {
+            resumed_at: None,
+            controls: false,
+            ..Self::new(duration)
+        }
+    }
+
+    pub fn position(&self) -> f64 {
+        let played = self
+            .resumed_at
+            .map_or(0.0, |t| t.elapsed().as_secs_f64() * self.speed());
+
+        (self.position + played).min(self.duration)
+    }
+
+    pub fn duration(&self) -> f64 {
+        self.duration
+    }
+
+    pub fn speed(&self) -> f64 {
+        PLAYBACK_SPEEDS[self.speed]
+    }
+
+    pub fn controls(&self) -> bool {
+        self.controls
+    }
+
+    pub fn paused(&self) -> bool {
+        self.resumed_at.is_none()
+    }
+
+    fn settle(&mut self) {
+        self.position = self.position();
+        if self.resumed_at.is_some() {
+            self.resumed_at = Some(Instant::now());
+        }
+    }
+
+    // Playing on from the end starts over.
+    pub fn toggle(&mut self) {
+        self.settle();
+
+        self.resumed_at = if self.paused() {
+            if self.position >= self.duration {
+                self.position = 0.0;
+            }
+
+            Some(Instant::now())
+        } else {
+            None
+        };
+    }
+
+    // Stops the clock at POSITION, for rendering a single moment.
+    pub fn seek(&mut self, position: f64) {
+        self.resumed_at = None;
+        self.position = position.clamp(0.0, self.duration);
+    }
+
+    pub fn seek_back(&mut self) {
+        self.settle();
+        self.position = (self.position - SEEK_SECONDS).max(0.0);
+    }
+
+    pub fn seek_forward(&mut self) {
+        self.settle();
+        self.position = (self.position + SEEK_SECONDS).min(self.duration);
+    }
+
+    pub fn faster(&mut self) {
+        self.settle();
+        self.speed = (self.speed + 1).min(PLAYBACK_SPEEDS.len() - 1);
+    }
+
+    pub fn slower(&mut self) {
+        self.settle();
+        self.speed = self.speed.saturating_sub(1);
+    }
+}
@@ -0,0 +1,148 @@
+use crate::{history::data_dir, types::TestResult};
+
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+// One finished test. Results imported from other trainers may lack some of
+// the numbers ttt records itself. Practice runs, tests cut short by a signal
+// and tests failed by -min-wpm or -min-acc are kept but left out of averages
+// and personal bests.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct ResultRecord {
+    pub timestamp: u64,
+    pub wpm: f64,
+    pub accuracy: Option<f64>,
+    pub seconds: Option<f64>,
+    pub source: String,
+    #[serde(default)]
+    pub practice: bool,
+    #[serde(default)]
+    pub incomplete: bool,
+    #[serde(default)]
+    pub failed: bool,
+    // File name of the test's recording in the replays directory.
+    #[serde(default)]
+    pub replay: Option<String>,
+}
+
+impl ResultRecord {
+    // A test just taken in ttt, which has every number.
+    pub fn new(result: TestResult, source: String) -> Self {
+        Self {
+            timestamp: now_timestamp(),
+            wpm: result.wpm,
+            accuracy: Some(result.accuracy),
+            seconds: Some(result.seconds),
+            source,
+            practice: false,
+            incomplete: false,
+            failed: false,
+            replay: None,
+        }
+    }
+}
+
+fn results_path() -> Option<PathBuf> {
+    data_dir().map(|dir| dir.join("results.jsonl"))
+}
+
+pub fn now_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+// Lines that don't parse are skipped rather than losing the whole history.
+pub fn load_results() -> io::Result<Vec<ResultRecord>> {
+    let Some(path) = results_path() else {
+        return Ok(Vec::new());
+    };
+
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    Ok(content
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+// Results are kept one JSON object per line, so recording one is an append.
+pub fn append_results(records: &[ResultRecord]) -> io::Result<()> {
+    let Some(path) = results_path() else {
+        return Ok(());
+    };
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let mut out = String::new();
+    for record in records {
+        out.push_str(&serde_json::to_string(record)?);
+        out.push('\n');
+    }
+
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?
+        .write_all(out.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RESULT: TestResult = TestResult {
+        wpm: 72.4,
+        accuracy: 97.8,
+        seconds: 60.0,
+    };
+
+    #[test]
+    fn record_round_trips() {
+        let record = ResultRecord {
+            practice: true,
+            replay: Some("1792208941123-0.json".to_string()),
+            ..ResultRecord::new(RESULT, "english-1k".to_string())
+        };
+
+        let json = serde_json::to_string(&record).unwrap();
+        let parsed: ResultRecord = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(parsed, record);
+    }
+
+    // Results from before the flags were added, and imported ones without
+    // accuracy or duration, still load.
+    #[test]
+    fn record_reads_older_lines() {
+        let parsed: ResultRecord = serde_json::from_str(
+            r#"{"timestamp":1700000000,"wpm":55.0,"accuracy":null,"seconds":null,"source":"typespeed"}"#,
+        )
+        .unwrap();
+
+        assert_eq!(parsed.wpm, 55.0);
+        assert_eq!(parsed.accuracy, None);
+        assert!(!parsed.practice && !parsed.incomplete && !parsed.failed);
+        assert_eq!(parsed.replay, None);
+    }
+
+    #[test]
+    fn test_result_round_trips() {
+        let json = serde_json::to_string(&RESULT).unwrap();
+
+        assert_eq!(json, r#"{"wpm":72.4,"accuracy":97.8,"seconds":60.0}"#);
+        assert_eq!(serde_json::from_str::<TestResult>(&json).unwrap(), RESULT);
+    }
+}
@@ -0,0 +1,103 @@
+use crate::config::CaretShape;
+
+use ratatui::crossterm::{
+    cursor::{SetCursorStyle, Show},
+    event::{DisableFocusChange, DisableMouseCapture, EnableFocusChange, EnableMouseCapture},
+    execute,
+    terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
+};
+use std::{
+    io::{self, Write},
+    panic,
+};
+
+// How the TUI takes over the terminal: drawing on stderr rather than stdout,
+// whether it captures the mouse and the shape of the caret.
+#[derive(Clone, Copy)]
+pub struct ScreenMode {
+    pub stderr: bool,
+    pub mouse: bool,
+    pub caret: CaretShape,
+    pub caret_blink: bool,
+}
+
+impl ScreenMode {
+    pub fn enter(self) -> io::Result<()> {
+        enable_raw_mode()?;
+        execute!(self.writer(), EnterAlternateScreen, EnableFocusChange)?;
+        if self.mouse {
+            execute!(self.writer(), EnableMouseCapture)?;
+        }
+        if let Some(style) = self.cursor_style() {
+            execute!(self.writer(), style)?;
+        }
+
+        Ok(())
+    }
+
+    // Best effort: there's nothing left to do if the terminal is gone.
+    pub fn leave(self) {
+        let _ = disable_raw_mode();
+        let _ = execute!(
+            self.writer(),
+            DisableMouseCapture,
+            DisableFocusChange,
+            LeaveAlternateScreen,
+            Show
+        );
+        if self.cursor_style().is_some() {
+            let _ = execute!(self.writer(), SetCursorStyle::DefaultUserShape);
+        }
+    }
+
+    fn cursor_style(self) -> Option<SetCursorStyle> {
+        let style = match (self.caret, self.caret_blink) {
+            (CaretShape::Default, _) => return None,
+            (CaretShape::Block, true) => SetCursorStyle::BlinkingBlock,
+            (CaretShape::Block, false) => SetCursorStyle::SteadyBlock,
+            (CaretShape::Underline, true) => SetCursorStyle::BlinkingUnderScore,
+            (CaretShape::Underline, false) => SetCursorStyle::SteadyUnderScore,
+            (CaretShape::Bar, true) => SetCursorStyle::BlinkingBar,
+            (CaretShape::Bar, false) => SetCursorStyle::SteadyBar,
+        };
+
+        Some(style)
+    }
+
+    fn writer(self) -> Box<dyn Write> {
+        if self.stderr {
+            Box::new(io::stderr())
+        } else {
+            Box::new(io::stdout())
+        }
+    }
+}
+
+// Keeps the terminal in raw mode on the alternate screen for as long as it
+// lives. It puts the terminal back when dropped, which covers returning early
+// with `?`, and from a panic hook, so a panic's message is readable and the
+// shell usable afterwards.
+pub struct ScreenGuard {
+    mode: ScreenMode,
+}
+
+impl ScreenGuard {
+    pub fn enter(mode: ScreenMode) -> io::Result<Self> {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            mode.leave();
+            previous(info);
+        }));
+
+        let guard = Self { mode };
+        mode.enter()?;
+
+        Ok(guard)
+    }
+}
+
+impl Drop for ScreenGuard {
+    fn drop(&mut self) {
+        self.mode.leave();
+    }
+}
@@ -0,0 +1,177 @@
+use crate::helpers::LANGUAGES;
+
+use ratatui::{
+    crossterm::event::{KeyCode, KeyEvent},
+    prelude::*,
+};
+use std::iter;
+
+const TIME_CHOICES: &[usize] = &[15, 30, 60, 120];
+// 0 keeps words coming until the time is up.
+const COUNT_CHOICES: &[usize] = &[0, 10, 25, 50, 100];
+const ROWS: usize = 5;
+
+// The test options the settings screen changes. They are saved to the config
+// file as the defaults for tests started without options.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Settings {
+    pub time: usize,
+    pub count: usize,
+    // None for the default word list: the config's `dict`, or the system one.
+    pub language: Option<String>,
+    pub punctuation: bool,
+    pub numbers: bool,
+}
+
+pub struct SettingsScreen {
+    pub settings: Settings,
+    pub initial: Settings,
+    // Why the last attempt to save failed.
+    pub error: Option<String>,
+    selected: usize,
+}
+
+impl SettingsScreen {
+    pub fn new(settings: Settings) -> Self {
+        Self {
+            initial: settings.clone(),
+            settings,
+            error: None,
+            selected: 0,
+        }
+    }
+
+    // Once the screen is closed, whether to save the changes: Enter does,
+    // Esc throws them away.
+    pub fn handle_key(&mut self, key: KeyEvent) -> Option<bool> {
+        match key.code {
+            KeyCode::Up | KeyCode::Char('k') => self.scroll(false),
+            KeyCode::Down | KeyCode::Char('j') => self.scroll(true),
+            KeyCode::Left | KeyCode::Char('h') => self.change(false),
+            KeyCode::Right | KeyCode::Char('l') | KeyCode::Char(' ') => self.change(true),
+            KeyCode::Enter => return Some(true),
+            KeyCode::Esc => return Some(false),
+            _ => {}
+        }
+
+        None
+    }
+
+    // A click on a row picks it, and on the picked row changes it.
+    pub fn click(&mut self, row: usize) {
+        if row >= ROWS {
+            return;
+        }
+
+        if row == self.selected {
+            self.change(true);
+        } else {
+            self.selected = row;
+        }
+    }
+
+    pub fn scroll(&mut self, down: bool) {
+        self.selected = if down {
+            (self.selected + 1) % ROWS
+        } else {
+            (self.selected + ROWS - 1) % ROWS
+        };
+    }
+
+    fn change(&mut self, forward: bool) {
+        let settings = &mut self.settings;
+
+        match self.selected {
+            0 => settings.time = cycle(TIME_CHOICES, &settings.time, forward),
+            1 => settings.count = cycle(COUNT_CHOICES, &settings.count, forward),
+            2 => {
+                let languages: Vec<Option<String>> = iter::once(None)
+                    .chain(LANGUAGES.iter().map(|l| Some(l.to_string())))
+                    .collect();
+                settings.language = cycle(&languages, &settings.language, forward);
+            }
+            3 => settings.punctuation = !settings.punctuation,
+            _ => settings.numbers = !settings.numbers,
+        }
+    }
+
+    // Config keys and values of the settings that were changed.
+    pub fn changes(&self) -> Vec<(&'static str, String)> {
+        let (new, old) = (&self.settings, &self.initial);
+        let mut changes = Vec::new();
+
+        if new.time != old.time {
+            changes.push(("time", new.time.to_string()));
+        }
+        if new.count != old.count {
+            changes.push(("count", new.count.to_string()));
+        }
+        if new.language != old.language {
+            let name = new.language.as_deref().unwrap_or("");
+            changes.push(("language", toml::Value::from(name).to_string()));
+        }
+        if new.punctuation != old.punctuation {
+            changes.push(("punctuation", new.punctuation.to_string()));
+        }
+        if new.numbers != old.numbers {
+            changes.push(("numbers", new.numbers.to_string()));
+        }
+
+        changes
+    }
+
+    pub fn lines(&self) -> Vec<Line<'static>> {
+        let settings = &self.settings;
+        let on_off = |on: bool| if on { "on" } else { "off" }.to_string();
+
+        let rows = [
+            ("Time", format!("{}s", settings.time)),
+            (
+                "Words",
+                match settings.count {
+                    0 => "until the time is up".to_string(),
+                    count => count.to_string(),
+                },
+            ),
+            (
+                "Language",
+                settings
+                    .language
+                    .clone()
+                    .unwrap_or_else(|| "default".to_string()),
+            ),
+            ("Punctuation", on_off(settings.punctuation)),
+            ("Numbers", on_off(settings.numbers)),
+        ];
+
+        let error = self
+            .error
+            .as_ref()
+            .map(|e| Line::from(e.clone()).style(Style::default().fg(Color::Red)));
+
+        rows.into_iter()
+            .enumerate()
+            .map(|(i, (name, value))| {
+                let line = Line::from(format!("{:<12} < {} >", name, value));
+                if i == self.selected {
+                    line.style(Style::default().add_modifier(Modifier::REVERSED))
+                } else {
+                    line
+                }
+            })
+            .chain(error)
+            .collect()
+    }
+}
+
+// The next or previous choice, wrapping around. A value that isn't one of the
+// choices, e.g. from the command line, moves to the first.
+fn cycle<T: Clone + PartialEq>(choices: &[T], current: &T, forward: bool) -> T {
+    let next = match choices.iter().position(|c| c == current) {
+        Some(i) if forward => (i + 1) % choices.len(),
+        Some(i) => (i + choices.len() - 1) % choices.len(),
+        None => 0,
+    };
+
+    choices[next].clone()
+}
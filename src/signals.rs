@@ -0,0 +1,82 @@
+use std::{
+    io,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
+};
+
+// Signals the main loop handles between frames. In raw mode Ctrl+Z arrives as
+// a key rather than SIGTSTP, but `kill -TSTP` and job control still send it.
+// `resume` is set by SIGCONT, which also follows a SIGSTOP that nobody could
+// catch. `terminate` holds the number of the SIGINT or SIGTERM received, 0 if
+// none.
+pub struct Signals {
+    suspend: Arc<AtomicBool>,
+    resume: Arc<AtomicBool>,
+    terminate: Arc<AtomicUsize>,
+}
+
+impl Signals {
+    #[cfg(unix)]
+    pub fn register() -> io::Result<Self> {
+        use signal_hook::{
+            consts::{SIGCONT, SIGINT, SIGTERM, SIGTSTP},
+            flag,
+        };
+
+        let suspend = Arc::new(AtomicBool::new(false));
+        flag::register(SIGTSTP, Arc::clone(&suspend))?;
+
+        let resume = Arc::new(AtomicBool::new(false));
+        flag::register(SIGCONT, Arc::clone(&resume))?;
+
+        let terminate = Arc::new(AtomicUsize::new(0));
+        for signal in [SIGINT, SIGTERM] {
+            flag::register_usize(signal, Arc::clone(&terminate), signal as usize)?;
+        }
+
+        Ok(Self {
+            suspend,
+            resume,
+            terminate,
+        })
+    }
+
+    #[cfg(not(unix))]
+    pub fn register() -> io::Result<Self> {
+        Ok(Self {
+            suspend: Arc::new(AtomicBool::new(false)),
+            resume: Arc::new(AtomicBool::new(false)),
+            terminate: Arc::new(AtomicUsize::new(0)),
+        })
+    }
+
+    pub fn take_suspend(&self) -> bool {
+        self.suspend.swap(false, Ordering::Relaxed)
+    }
+
+    pub fn take_resume(&self) -> bool {
+        self.resume.swap(false, Ordering::Relaxed)
+    }
+
+    // The exit code for a terminating signal, by the shell's 128 + N rule.
+    pub fn exit_code(&self) -> Option<i32> {
+        match self.terminate.load(Ordering::Relaxed) {
+            0 => None,
+            signal => Some(128 + signal as i32),
+        }
+    }
+}
+
+// Stops the process the way SIGTSTP would have, returning once the shell
+// continues it.
+#[cfg(unix)]
+pub fn suspend() -> io::Result<()> {
+    signal_hook::low_level::emulate_default_handler(signal_hook::consts::SIGTSTP)
+}
+
+#[cfg(not(unix))]
+pub fn suspend() -> io::Result<()> {
+    Ok(())
+}
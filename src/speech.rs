@@ -0,0 +1,27 @@
+use std::{
+    io,
+    process::{Command, Stdio},
+    thread,
+};
+
+// Runs `command` with `text` as its last argument, e.g. `espeak` or `say -v Alex`,
+// without waiting for it so the results show up while it speaks. It is waited
+// for on a thread instead, so it doesn't linger as a zombie once done.
+pub fn speak(command: &str, text: &str) -> io::Result<()> {
+    let mut parts = command.split_whitespace();
+    let Some(program) = parts.next() else {
+        return Ok(());
+    };
+
+    let mut child = Command::new(program)
+        .args(parts)
+        .arg(text)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+
+    thread::spawn(move || child.wait());
+
+    Ok(())
+}
@@ -0,0 +1,248 @@
+use crate::{
+    cli::StatsOptions,
+    config::{OutlierRules, load_config},
+    fingers::{Finger, FingerMap},
+    history::load_error_history,
+    results::{ResultRecord, load_results},
+    types::AggregateStats,
+};
+
+use std::{collections::BTreeMap, process};
+
+const RECENT_COUNT: usize = 10;
+const HISTORY_COUNT: usize = 20;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Filter {
+    Ranked,
+    Practice,
+    All,
+}
+
+impl Filter {
+    fn keeps(self, record: &ResultRecord) -> bool {
+        if record.incomplete || record.failed {
+            return false;
+        }
+
+        match self {
+            Filter::Ranked => !record.practice,
+            Filter::Practice => record.practice,
+            Filter::All => true,
+        }
+    }
+}
+
+pub fn run_stats(options: StatsOptions) {
+    if options.fingers {
+        print_fingers();
+
+        return;
+    }
+
+    let filter = if options.practice {
+        Filter::Practice
+    } else if options.all {
+        Filter::All
+    } else {
+        Filter::Ranked
+    };
+    let history = options.history;
+
+    let records = load_results().unwrap_or_else(|e| {
+        eprintln!("Failed to read results: {}", e);
+
+        process::exit(1);
+    });
+
+    let rules = load_config().outliers;
+
+    let skipped = records
+        .iter()
+        .filter(|r| !r.incomplete && !r.failed && !filter.keeps(r))
+        .count();
+    let records: Vec<&ResultRecord> = records.iter().filter(|r| filter.keeps(r)).collect();
+    let reasons = outlier_reasons(&records, &rules);
+
+    if history {
+        print_history(&records, &reasons);
+    }
+
+    let outliers = reasons.iter().filter(|r| r.is_some()).count();
+    let records: Vec<&ResultRecord> = records
+        .iter()
+        .zip(&reasons)
+        .filter(|(_, reason)| reason.is_none())
+        .map(|(record, _)| *record)
+        .collect();
+
+    match aggregate(&records) {
+        Some(stats) => {
+            println!("{:<16}{}", "Tests:", stats.tests);
+            println!("{:<16}{:.1}", "Average WPM:", stats.average_wpm);
+            println!(
+                "{:<16}{:.1}",
+                format!("Last {} WPM:", stats.recent_count),
+                stats.recent_wpm
+            );
+            println!(
+                "{:<16}{:.1} ({})",
+                "Best WPM:", stats.best_wpm, stats.best_source
+            );
+
+            if let Some(accuracy) = stats.average_accuracy {
+                println!("{:<16}{:.1}%", "Average acc.:", accuracy);
+            }
+        }
+        None => println!("No results yet."),
+    }
+
+    if outliers > 0 {
+        println!("({} outliers not counted, see --history)", outliers);
+    }
+
+    if skipped > 0 && filter == Filter::Ranked {
+        println!("({} practice results not counted, see --practice)", skipped);
+    }
+}
+
+// Why each result is an outlier, if it is. The deviation rule compares
+// against the mean and spread of the results the other rules keep.
+fn outlier_reasons(records: &[&ResultRecord], rules: &OutlierRules) -> Vec<Option<&'static str>> {
+    let mut reasons: Vec<Option<&'static str>> = records
+        .iter()
+        .map(|record| {
+            if rules
+                .min_seconds
+                .is_some_and(|min| record.seconds.is_some_and(|s| s < min))
+            {
+                Some("too short")
+            } else if rules
+                .min_accuracy
+                .is_some_and(|min| record.accuracy.is_some_and(|a| a < min))
+            {
+                Some("low accuracy")
+            } else if rules.max_wpm.is_some_and(|max| record.wpm > max) {
+                Some("implausible speed")
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    let Some(max_deviations) = rules.max_deviations else {
+        return reasons;
+    };
+
+    let kept: Vec<f64> = records
+        .iter()
+        .zip(&reasons)
+        .filter(|(_, reason)| reason.is_none())
+        .map(|(record, _)| record.wpm)
+        .collect();
+    if kept.len() < 2 {
+        return reasons;
+    }
+
+    let mean = kept.iter().sum::<f64>() / kept.len() as f64;
+    let variance = kept.iter().map(|w| (w - mean).powi(2)).sum::<f64>() / kept.len() as f64;
+    let spread = variance.sqrt() * max_deviations;
+
+    for (record, reason) in records.iter().zip(reasons.iter_mut()) {
+        if reason.is_none() && (record.wpm - mean).abs() > spread {
+            *reason = Some("far from average");
+        }
+    }
+
+    reasons
+}
+
+fn print_history(records: &[&ResultRecord], reasons: &[Option<&'static str>]) {
+    let start = records.len().saturating_sub(HISTORY_COUNT);
+
+    for (record, reason) in records[start..].iter().zip(&reasons[start..]) {
+        let accuracy = record
+            .accuracy
+            .map(|a| format!("{:.1}%", a))
+            .unwrap_or_else(|| "-".to_string());
+        let seconds = record
+            .seconds
+            .map(|s| format!("{:.0}s", s))
+            .unwrap_or_else(|| "-".to_string());
+        let flag = reason
+            .map(|reason| format!("  [excluded: {}]", reason))
+            .unwrap_or_default();
+
+        println!(
+            "{:>6.1} WPM {:>7} {:>5}  {}{}{}",
+            record.wpm,
+            accuracy,
+            seconds,
+            record.source,
+            if record.practice { " (practice)" } else { "" },
+            flag
+        );
+    }
+
+    println!();
+}
+
+fn print_fingers() {
+    let fingers = FingerMap::new(&load_config().fingers);
+    let history = load_error_history();
+
+    let mut totals: BTreeMap<Finger, (u64, u64)> = BTreeMap::new();
+    for (c, stats) in &history.chars {
+        if let Some(finger) = fingers.finger(*c) {
+            let total = totals.entry(finger).or_default();
+            total.0 += stats.typed;
+            total.1 += stats.missed;
+        }
+    }
+
+    if totals.is_empty() {
+        println!("No keystrokes recorded yet.");
+
+        return;
+    }
+
+    for (finger, (typed, missed)) in totals {
+        let rate = if typed > 0 {
+            missed as f64 / typed as f64 * 100.0
+        } else {
+            0.0
+        };
+
+        println!(
+            "{:<14}{:>8} typed {:>6} missed {:>6.1}%",
+            finger.name(),
+            typed,
+            missed,
+            rate
+        );
+    }
+}
+
+// `None` without any results to summarize.
+fn aggregate(records: &[&ResultRecord]) -> Option<AggregateStats> {
+    let best = records.iter().max_by(|a, b| a.wpm.total_cmp(&b.wpm))?;
+    let recent = &records[records.len().saturating_sub(RECENT_COUNT)..];
+
+    let accuracies: Vec<f64> = records.iter().filter_map(|r| r.accuracy).collect();
+    let average_accuracy =
+        (!accuracies.is_empty()).then(|| accuracies.iter().sum::<f64>() / accuracies.len() as f64);
+
+    Some(AggregateStats {
+        tests: records.len(),
+        average_wpm: average_wpm(records),
+        recent_count: recent.len(),
+        recent_wpm: average_wpm(recent),
+        best_wpm: best.wpm,
+        best_source: best.source.clone(),
+        average_accuracy,
+    })
+}
+
+fn average_wpm(records: &[&ResultRecord]) -> f64 {
+    records.iter().map(|r| r.wpm).sum::<f64>() / records.len() as f64
+}
@@ -0,0 +1,73 @@
+pub const TOUR_PAGES: &[(&str, &[&str])] = &[
+    (
+        "Welcome to ttt",
+        &[
+            "This short tour walks through the screen, keys and modes.",
+            "",
+            "Use Left/Right to move between pages.",
+        ],
+    ),
+    (
+        "Target Text",
+        &[
+            "The top panel shows the text to type.",
+            "",
+            "Typed characters turn green when correct and red when wrong.",
+            "A mistyped space is shown with a red background.",
+            "The word you are on is underlined, and what is left is dimmed.",
+        ],
+    ),
+    (
+        "Typed Words",
+        &[
+            "The panel below the target echoes what you typed.",
+            "",
+            "The timer starts on your first keystroke.",
+            "Backspace deletes the previous character.",
+        ],
+    ),
+    (
+        "Stats",
+        &[
+            "The bottom panel shows time, WPM and accuracy live.",
+            "",
+            "After the test, move with the arrows, w and b, press v to",
+            "mark a range and y to copy it together with what you typed.",
+        ],
+    ),
+    (
+        "Keys",
+        &[
+            "Esc              quit",
+            "F5, Tab          restart with a new text",
+            "Ctrl+r           restart with the same text",
+            "Enter            restart after finishing",
+            "<leader> r       restart",
+            "<leader> q       quit",
+            "<leader> p       toggle practice mode",
+            "<leader> s       stop a -zen test",
+            "<leader> space   pause, any key resumes",
+            "<leader> ,       settings",
+            "<leader> t       show or hide the typed words",
+            "<leader> f       hide the stats until the end",
+            "Ctrl+w           delete the last word",
+            ":                commands, before or after a test",
+            "Ctrl+z           suspend to the shell, paused",
+            "",
+            "The leader key (Ctrl+g by default) is set in config.toml.",
+        ],
+    ),
+    (
+        "Modes",
+        &[
+            "-quote [short|medium|long]   type a built-in quote",
+            "-language NAME               use a built-in word list",
+            "-text PATH / -dict PATH      use your own text or word list",
+            "-punctuation -numbers        add punctuation and numbers",
+            "-capitalize [PCT]            add capital letters",
+            "-zen                         type on until you stop",
+            "",
+            "Run ttt -h for the full list.",
+        ],
+    ),
+];
@@ -0,0 +1,195 @@
+use crate::{history::data_dir, provider::TextProvider};
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::{fs, io, path::PathBuf};
+
+// Letters in the order they get unlocked, most frequent first.
+pub const LETTER_ORDER: &str = "enitrlsauodychgmpbkvwfzxqj";
+pub const INITIAL_LETTERS: usize = 6;
+pub const UNLOCK_WPM: f64 = 35.0;
+pub const UNLOCK_ACCURACY: f64 = 95.0;
+pub const TRAINING_WORD_COUNT: usize = 40;
+
+const BOUNDARY: usize = 26;
+const MIN_WORD_LEN: usize = 3;
+const MAX_WORD_LEN: usize = 8;
+const NEW_LETTER_CHANCE: f64 = 0.5;
+const NEW_LETTER_TRIES: usize = 10;
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct TrainingProgress {
+    pub unlocked: usize,
+}
+
+impl Default for TrainingProgress {
+    fn default() -> Self {
+        Self {
+            unlocked: INITIAL_LETTERS,
+        }
+    }
+}
+
+impl TrainingProgress {
+    pub fn letters(&self) -> Vec<char> {
+        LETTER_ORDER.chars().take(self.unlocked).collect()
+    }
+
+    pub fn newest_letter(&self) -> Option<char> {
+        LETTER_ORDER.chars().nth(self.unlocked.saturating_sub(1))
+    }
+
+    // Unlocks the next letter when the test was fast and accurate enough and
+    // the most recently unlocked letter was typed accurately too.
+    pub fn evaluate(&mut self, target: &str, typed: &str, wpm: f64, accuracy: f64) -> Option<char> {
+        if self.unlocked >= LETTER_ORDER.len() || wpm < UNLOCK_WPM || accuracy < UNLOCK_ACCURACY {
+            return None;
+        }
+
+        if let Some(newest) = self.newest_letter() {
+            let (hits, total) = target
+                .chars()
+                .zip(typed.chars())
+                .filter(|(expected, _)| *expected == newest)
+                .fold((0, 0), |(hits, total), (expected, actual)| {
+                    (hits + (expected == actual) as usize, total + 1)
+                });
+
+            if total == 0 || (hits as f64 / total as f64) * 100.0 < UNLOCK_ACCURACY {
+                return None;
+            }
+        }
+
+        self.unlocked += 1;
+
+        self.newest_letter()
+    }
+}
+
+// Letter bigram counts used to build pronounceable pseudo-words out of the
+// unlocked letters only.
+pub struct LetterModel {
+    counts: Vec<[u32; BOUNDARY + 1]>,
+}
+
+impl LetterModel {
+    pub fn build(dictionary: &dyn TextProvider) -> Self {
+        let mut counts = vec![[0u32; BOUNDARY + 1]; BOUNDARY + 1];
+
+        for i in 0..dictionary.len() {
+            let word = dictionary.get(i);
+            if !word.chars().all(|c| c.is_ascii_lowercase()) {
+                continue;
+            }
+
+            let mut prev = BOUNDARY;
+            for c in word.bytes() {
+                let next = (c - b'a') as usize;
+                counts[prev][next] += 1;
+                prev = next;
+            }
+            counts[prev][BOUNDARY] += 1;
+        }
+
+        Self { counts }
+    }
+
+    fn word(&self, letters: &[usize], rng: &mut impl Rng) -> String {
+        let mut word = String::new();
+        let mut prev = BOUNDARY;
+
+        while word.len() < MAX_WORD_LEN {
+            let mut options: Vec<(usize, u32)> = letters
+                .iter()
+                .map(|&l| (l, self.counts[prev][l] + 1))
+                .collect();
+            if word.len() >= MIN_WORD_LEN {
+                options.push((BOUNDARY, self.counts[prev][BOUNDARY] + 1));
+            }
+
+            let total: u32 = options.iter().map(|(_, w)| w).sum();
+            let mut roll = rng.random_range(0..total);
+            let mut next = options[options.len() - 1].0;
+            for (letter, weight) in options {
+                if roll < weight {
+                    next = letter;
+                    break;
+                }
+                roll -= weight;
+            }
+
+            if next == BOUNDARY {
+                break;
+            }
+
+            word.push((b'a' + next as u8) as char);
+            prev = next;
+        }
+
+        word
+    }
+
+    pub fn generate(
+        &self,
+        progress: &TrainingProgress,
+        count: usize,
+        rng: &mut impl Rng,
+    ) -> String {
+        let letters: Vec<usize> = progress
+            .letters()
+            .iter()
+            .map(|c| (*c as u8 - b'a') as usize)
+            .collect();
+        let newest = progress.newest_letter();
+
+        (0..count)
+            .map(|_| {
+                let mut word = self.word(&letters, rng);
+
+                // Give the newest letter extra practice.
+                if let Some(c) = newest
+                    && rng.random_bool(NEW_LETTER_CHANCE)
+                {
+                    for _ in 0..NEW_LETTER_TRIES {
+                        if word.contains(c) {
+                            break;
+                        }
+                        word = self.word(&letters, rng);
+                    }
+                }
+
+                word
+            })
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
+}
+
+pub struct Training {
+    pub model: LetterModel,
+    pub progress: TrainingProgress,
+}
+
+fn progress_path() -> Option<PathBuf> {
+    data_dir().map(|dir| dir.join("training.json"))
+}
+
+pub fn load_training_progress() -> TrainingProgress {
+    progress_path()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+pub fn save_training_progress(progress: &TrainingProgress) -> io::Result<()> {
+    let Some(path) = progress_path() else {
+        return Ok(());
+    };
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    fs::write(path, serde_json::to_string(progress)?)
+}
@@ -1,3 +1,9 @@
+use crate::{events::EventTarget, provider::TextProvider, training::Training};
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
 #[derive(Clone, Copy, Debug)]
 pub struct Glyph {
     pub ch: char,
@@ -6,7 +12,249 @@ pub struct Glyph {
 
 pub type Layout = Vec<Vec<Glyph>>;
 
+#[derive(Clone, Debug, Deserialize)]
+pub struct Quote {
+    pub text: String,
+    pub author: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum QuoteLength {
+    Short,
+    Medium,
+    Long,
+}
+
 pub enum TextSource {
-    RandomWords(Vec<String>),
+    RandomWords(Box<dyn TextProvider>),
     Fixed(String),
+    Sentences(Vec<String>),
+    Chunks(Vec<String>),
+    Code(String),
+    Passages(VecDeque<Passage>),
+    Quote(Vec<Quote>),
+    Training(Training),
+    Drill(DrillKind),
+    Charset(Vec<char>),
+}
+
+// One of several text files, which may be code.
+#[derive(Clone, Debug)]
+pub struct Passage {
+    pub text: String,
+    pub code: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum DrillKind {
+    Bigram,
+    Trigram,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Capitalization {
+    Sentences,
+    Percent(u32),
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TextOptions {
+    pub punctuation: bool,
+    pub numbers: bool,
+    pub capitalize: Option<Capitalization>,
+    pub adaptive: bool,
+    // Put between generated words instead of a space.
+    pub separator: Option<char>,
+}
+
+pub struct Args {
+    pub count: usize,
+    pub seconds: usize,
+    pub source: TextSource,
+    pub text: TextOptions,
+    pub playlist: Vec<Args>,
+    pub goal: Option<Goal>,
+    pub repeat: Option<Repeat>,
+    pub require: Option<Requirement>,
+    // -min-wpm and -min-acc: the test fails as soon as it falls below these.
+    pub minimum: Option<Requirement>,
+    pub blind: bool,
+    pub events: Option<EventTarget>,
+    // Port of the live stats WebSocket, with -serve-stats.
+    pub serve_stats: Option<u16>,
+    // File or named pipe to keep the live stats in, with -stats-file.
+    pub stats_file: Option<String>,
+    // Print the session's results as JSON once the TUI is gone, with -json.
+    pub json: bool,
+    pub bundle: Option<String>,
+    pub practice: bool,
+    pub bookmark: Option<TextBookmark>,
+    pub seed: Option<u64>,
+    pub zen: bool,
+    // Generate the text in batches of `count` words as it is typed.
+    pub stream: bool,
+}
+
+// Repeat the same text until it is typed at `min_wpm` or faster and at
+// `min_accuracy` or better, `times` times in a row. With `flawless`, only runs
+// without a single mistake count.
+#[derive(Clone, Copy, Debug)]
+pub struct Repeat {
+    pub times: usize,
+    pub min_wpm: f64,
+    pub min_accuracy: f64,
+    pub flawless: bool,
+}
+
+// -require-wpm and -require-acc: the exit code tells whether the last test
+// met them, so scripts can gate on a test.
+#[derive(Clone, Copy, Debug)]
+pub struct Requirement {
+    pub min_wpm: f64,
+    pub min_accuracy: f64,
+}
+
+impl Requirement {
+    pub fn met(&self, result: &TestResult) -> bool {
+        result.wpm >= self.min_wpm && result.accuracy >= self.min_accuracy
+    }
+}
+
+// Pass criteria of a lesson; a course only moves on once they are met.
+#[derive(Clone, Debug)]
+pub struct Goal {
+    pub name: String,
+    pub min_wpm: f64,
+    pub min_accuracy: f64,
+    pub lesson: Option<CourseLesson>,
+}
+
+// A -text file typed from `start`, so the next session can pick up where
+// this one stops.
+#[derive(Clone, Debug)]
+pub struct TextBookmark {
+    pub file: String,
+    pub start: usize,
+}
+
+// Where a lesson sits in a course, so its progress can be saved.
+#[derive(Clone, Debug)]
+pub struct CourseLesson {
+    pub course: String,
+    pub file: String,
+    pub index: usize,
+    pub total: usize,
+}
+
+impl Goal {
+    pub fn met(&self, result: &TestResult) -> bool {
+        result.wpm >= self.min_wpm && result.accuracy >= self.min_accuracy
+    }
+}
+
+// Keys for these can be changed in the `[keys]` table of the config, by their
+// snake_case names.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Restart,
+    Retry,
+    Quit,
+    TogglePractice,
+    Stop,
+    Pause,
+    Settings,
+    DeleteWord,
+    ToggleTyped,
+    ToggleFocus,
+    // Handled by the main loop before the screen sees the key.
+    Suspend,
+    Interrupt,
+}
+
+// Keys for these are changed in `[review_keys]`.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReviewAction {
+    Left,
+    Right,
+    WordLeft,
+    WordRight,
+    Mark,
+    Copy,
+    Replay,
+    Hands,
+}
+
+// Keys for these are changed in `[playback_keys]`.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PlaybackAction {
+    Pause,
+    Back,
+    Forward,
+    Faster,
+    Slower,
+    Quit,
+}
+
+// The shared data types below are what ttt writes out: to the event stream,
+// bundles, replays, `results.jsonl` and the race protocol. Field names are part
+// of those formats, so they only ever get added to.
+
+// A key typed during a test, as `{"seconds":0.42,"key":"t","correct":true}`.
+// `key` is `null` for Backspace and `seconds` counts from the start of the
+// test. Older bundles call `key` `ch`, which is still read.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct KeystrokeEvent {
+    pub seconds: f64,
+    #[serde(alias = "ch")]
+    pub key: Option<char>,
+    pub correct: bool,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Selection {
+    pub caret: usize,
+    pub mark: Option<usize>,
+}
+
+impl Selection {
+    // Half-open range of target character indices covered by the selection.
+    pub fn range(&self) -> (usize, usize) {
+        let mark = self.mark.unwrap_or(self.caret);
+
+        (mark.min(self.caret), mark.max(self.caret) + 1)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TourAction {
+    Next,
+    Previous,
+    Close,
+    Dismiss,
+}
+
+// The outcome of one test, as `{"wpm":72.4,"accuracy":97.8,"seconds":60.0}`.
+// Accuracy is the percentage of typed characters that were right.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct TestResult {
+    pub wpm: f64,
+    pub accuracy: f64,
+    pub seconds: f64,
+}
+
+// What `ttt stats` reports over the results it counts. `recent_wpm` averages
+// the last `recent_count` of them; `average_accuracy` is `None` when only
+// imported results without accuracy are counted.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct AggregateStats {
+    pub tests: usize,
+    pub average_wpm: f64,
+    pub recent_count: usize,
+    pub recent_wpm: f64,
+    pub best_wpm: f64,
+    pub best_source: String,
+    pub average_accuracy: Option<f64>,
 }